@@ -1,7 +1,43 @@
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Rendering libraries (KaTeX, Mermaid) vendored here are baked into the binary
+// so math and diagrams render without network access. See assets/vendor/README.md.
+const VENDOR_DIR: &str = "assets/vendor";
+const VENDOR_EXTENSIONS: &[&str] = &["js", "css", "woff2", "woff", "ttf"];
+// Files `--asset-mode embedded` loads; without them it falls back to the CDN
+const REQUIRED_ASSETS: &[&str] = &[
+    "katex/katex.min.css",
+    "katex/katex.min.js",
+    "katex/contrib/auto-render.min.js",
+    "mermaid/mermaid.min.js",
+];
+// Set for release builds that must work offline, so an unpopulated vendor
+// directory fails the build instead of producing a CDN-only binary
+const REQUIRE_ASSETS_ENV: &str = "PAPERCRAFT_REQUIRE_EMBEDDED_ASSETS";
 
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
+
+    println!("cargo:rerun-if-env-changed={}", REQUIRE_ASSETS_ENV);
+    match generate_embedded_assets() {
+        Ok(missing) if !missing.is_empty() => {
+            if env::var(REQUIRE_ASSETS_ENV).is_ok() {
+                panic!(
+                    "{} is set but {} is missing {}; see {}/README.md",
+                    REQUIRE_ASSETS_ENV, VENDOR_DIR, missing.join(", "), VENDOR_DIR
+                );
+            }
+            println!(
+                "cargo:warning=No embedded KaTeX/Mermaid: {} is missing {}",
+                VENDOR_DIR, missing.join(", ")
+            );
+            println!("cargo:warning=--asset-mode embedded will fall back to the CDN (or fail with --strict-assets); see {}/README.md", VENDOR_DIR);
+        }
+        Ok(_) => {}
+        Err(e) => println!("cargo:warning=Failed to embed vendored assets: {}", e),
+    }
     
     // Only download Chrome if PAPERCRAFT_BUNDLE_CHROME environment variable is set
     if env::var("PAPERCRAFT_BUNDLE_CHROME").is_ok() {
@@ -35,4 +71,53 @@ fn download_chrome_at_build_time() -> Result<(), Box<dyn std::error::Error>> {
     // For now, we'll skip actual download during build to keep build times reasonable
     
     Ok(())
-}
\ No newline at end of file
+}
+/// Writes `embedded_assets.rs` into OUT_DIR, listing every vendored asset as an
+/// `include_bytes!` entry keyed by its path relative to the vendor directory.
+/// Returns the required assets that were not found.
+fn generate_embedded_assets() -> std::io::Result<Vec<&'static str>> {
+    println!("cargo:rerun-if-changed={}", VENDOR_DIR);
+
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string()));
+    let vendor_dir = manifest_dir.join(VENDOR_DIR);
+
+    let mut files = Vec::new();
+    if vendor_dir.is_dir() {
+        collect_vendor_files(&vendor_dir, &mut files)?;
+    }
+    files.sort();
+
+    let mut source = String::from("pub static EMBEDDED_ASSETS: &[(&str, &[u8])] = &[\n");
+    let mut keys = Vec::new();
+    for file in &files {
+        let relative = file.strip_prefix(&vendor_dir).unwrap_or(file);
+        let key = relative.to_string_lossy().replace('\\', "/");
+        println!("cargo:rerun-if-changed={}", file.display());
+        source.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", key, file.display().to_string()));
+        keys.push(key);
+    }
+    source.push_str("];\n");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap_or_else(|_| ".".to_string()));
+    fs::write(out_dir.join("embedded_assets.rs"), source)?;
+
+    Ok(REQUIRED_ASSETS.iter()
+        .copied()
+        .filter(|required| !keys.iter().any(|key| key == required))
+        .collect())
+}
+
+fn collect_vendor_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_vendor_files(&path, files)?;
+        } else if path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| VENDOR_EXTENSIONS.contains(&ext))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
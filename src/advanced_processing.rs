@@ -2,14 +2,20 @@ use anyhow::Result;
 use regex::Regex;
 use scraper::{Html, Selector};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::bibliography::{Bibliography, CitationStyle};
 use crate::config::{Config, TocStyle, FootnoteNumbering};
+use crate::cross_references::CrossReferencer;
+use crate::error_handler::PapercraftError;
 use lazy_static::lazy_static;
 
+/// Paragraph marking where the reference list goes, like `[TOC]` for the contents
+const BIBLIOGRAPHY_MARKER: &str = "<p>[BIBLIOGRAPHY]</p>";
+
 lazy_static! {
     static ref FOOTNOTE_REGEX: Regex = Regex::new(r"\[\^([^\]]+)\]").expect("Invalid footnote regex");
     static ref FOOTNOTE_DEF_REGEX: Regex = Regex::new(r"(?m)^\[\^([^\]]+)\]:\s*(.+)$").expect("Invalid footnote definition regex");
-    static ref XREF_REGEX: Regex = Regex::new(r"\[@ref:(\w+):([^\]]+)\]").expect("Invalid cross-reference regex");
-    static ref CITATION_REGEX: Regex = Regex::new(r"\[@cite:([^\]]+)\]").expect("Invalid citation regex");
+    pub static ref CITATION_REGEX: Regex = Regex::new(r"\[@cite:([^\]]+)\]").expect("Invalid citation regex");
     static ref CODE_BLOCK_REGEX: Regex = Regex::new(r#"(?s)<pre><code class="language-([^"]*)">(.*?)</code></pre>"#).expect("Invalid code block regex");
     static ref SCRIPT_REGEX: Regex = Regex::new(r"(?i)<script[^>]*>.*?</script>").expect("Invalid script regex");
     static ref STYLE_REGEX: Regex = Regex::new(r"(?i)<style[^>]*>.*?</style>").expect("Invalid style regex");
@@ -19,6 +25,7 @@ lazy_static! {
 
 pub struct AdvancedProcessor {
     config: Config,
+    base_path: PathBuf,
     #[allow(dead_code)]
     footnote_counter: u32,
     #[allow(dead_code)]
@@ -31,12 +38,19 @@ impl AdvancedProcessor {
     pub fn new(config: Config) -> Self {
         Self {
             config,
+            base_path: PathBuf::from("."),
             footnote_counter: 0,
             citation_counter: 0,
             cross_references: HashMap::new(),
         }
     }
 
+    /// Resolve relative resources such as the bibliography file against `base_path`
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
+    }
+
     pub fn process_document(&mut self, html: &str) -> Result<String> {
         let mut processed_html = html.to_string();
 
@@ -46,7 +60,8 @@ impl AdvancedProcessor {
         // Process in order of dependencies
         processed_html = self.process_cross_references(&processed_html)?;
         processed_html = self.process_footnotes(&processed_html)?;
-        processed_html = self.process_citations(&processed_html)?;
+        processed_html = self.process_citations(&processed_html)
+            .map_err(|e| PapercraftError::BibliographyError { reason: format!("{e:#}") })?;
         processed_html = self.enhance_code_blocks(&processed_html)?;
         processed_html = self.generate_advanced_toc(&processed_html)?;
 
@@ -58,9 +73,19 @@ impl AdvancedProcessor {
             return Ok(html.replace("[TOC]", ""));
         }
 
+        let toc_entries = self.collect_headings(html)?;
+        let toc_html = self.render_toc(&toc_entries);
+        Ok(html.replace("[TOC]", &toc_html))
+    }
+
+    /// Headings up to `toc.max_depth`, in document order. Shared by the TOC
+    /// and the PDF outline.
+    pub fn collect_headings(&self, html: &str) -> Result<Vec<TocEntry>> {
         let document = Html::parse_fragment(html);
         let header_selector = Selector::parse("h1, h2, h3, h4, h5, h6")
             .map_err(|e| anyhow::anyhow!("Failed to parse header selector: {:?}", e))?;
+        let anchor_selector = Selector::parse("a[id]")
+            .map_err(|e| anyhow::anyhow!("Failed to parse anchor selector: {:?}", e))?;
         
         let mut toc_entries = Vec::new();
         let mut section_counters = [0u32; 6]; // For h1-h6
@@ -76,14 +101,17 @@ impl AdvancedProcessor {
                 _ => continue,
             };
 
-            if level as u8 > self.config.toc.max_depth {
+            // The TOC's own title is not a document heading
+            if level as u8 > self.config.toc.max_depth || element.value().has_class("toc-title", scraper::CaseSensitivity::CaseSensitive) {
                 continue;
             }
 
             let text = element.text().collect::<String>().trim().to_string();
+            // comrak puts heading ids on an anchor inside the heading
             let id = element.value().attr("id")
-                .unwrap_or(&format!("heading-{}", toc_entries.len()))
-                .to_string();
+                .or_else(|| element.select(&anchor_selector).find_map(|a| a.value().attr("id")))
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| format!("heading-{}", toc_entries.len()));
 
             // Update section counters for numbered style
             section_counters[level - 1] += 1;
@@ -106,8 +134,7 @@ impl AdvancedProcessor {
             });
         }
 
-        let toc_html = self.render_toc(&toc_entries);
-        Ok(html.replace("[TOC]", &toc_html))
+        Ok(toc_entries)
     }
 
     fn generate_section_number(&self, counters: &[u32; 6], level: usize) -> String {
@@ -147,7 +174,7 @@ impl AdvancedProcessor {
                     };
                     
                     let page_number = if self.config.toc.page_numbers {
-                        format!("<span class=\"toc-page-number\" data-ref=\"#{}\"></span>", entry.id)
+                        format!("<span class=\"toc-leader\"></span><span class=\"toc-page-number\" data-ref=\"#{}\"></span>", entry.id)
                     } else {
                         String::new()
                     };
@@ -170,7 +197,7 @@ impl AdvancedProcessor {
                     };
                     
                     let page_number = if self.config.toc.page_numbers {
-                        format!("<span class=\"toc-page-number\" data-ref=\"#{}\"></span>", entry.id)
+                        format!("<span class=\"toc-leader\"></span><span class=\"toc-page-number\" data-ref=\"#{}\"></span>", entry.id)
                     } else {
                         String::new()
                     };
@@ -201,7 +228,7 @@ impl AdvancedProcessor {
                     };
                     
                     let page_number = if self.config.toc.page_numbers {
-                        format!("<span class=\"toc-page-number\" data-ref=\"#{}\"></span>", entry.id)
+                        format!("<span class=\"toc-leader\"></span><span class=\"toc-page-number\" data-ref=\"#{}\"></span>", entry.id)
                     } else {
                         String::new()
                     };
@@ -334,46 +361,103 @@ impl AdvancedProcessor {
             return Ok(html.to_string());
         }
 
-        // Pattern for cross-references: [@ref:type:id]
-        let xref_regex = &*XREF_REGEX;
-        
-        let processed_html = xref_regex.replace_all(html, |caps: &regex::Captures| {
-            let ref_type = &caps[1];
-            let ref_id = &caps[2];
-            
-            match ref_type {
-                "fig" => format!("<a href=\"#fig-{ref_id}\" class=\"cross-ref cross-ref-fig\">Figure <span class=\"ref-number\" data-ref=\"fig-{ref_id}\"></span></a>"),
-                "table" => format!("<a href=\"#table-{ref_id}\" class=\"cross-ref cross-ref-table\">Table <span class=\"ref-number\" data-ref=\"table-{ref_id}\"></span></a>"),
-                "eq" => format!("<a href=\"#eq-{ref_id}\" class=\"cross-ref cross-ref-eq\">Equation <span class=\"ref-number\" data-ref=\"eq-{ref_id}\"></span></a>"),
-                "sec" => format!("<a href=\"#sec-{ref_id}\" class=\"cross-ref cross-ref-sec\">Section <span class=\"ref-number\" data-ref=\"sec-{ref_id}\"></span></a>"),
-                _ => format!("<a href=\"#{ref_id}\" class=\"cross-ref\">{ref_id}</a>"),
-            }
-        });
-
-        Ok(processed_html.to_string())
+        // Number labeled elements, then fill [@ref:type:id] with their numbers
+        let mut cross_referencer = CrossReferencer::new(self.config.references.numbering.per_chapter);
+        Ok(cross_referencer.process(html))
     }
 
     pub fn process_citations(&mut self, html: &str) -> Result<String> {
-        if !self.config.references.bibliography.enabled {
+        let bib_config = &self.config.references.bibliography;
+        if !bib_config.enabled {
             return Ok(html.to_string());
         }
 
         // Pattern for citations: [@cite:key] or [@cite:key1,key2,key3]
         let citation_regex = &*CITATION_REGEX;
-        
-        let processed_html = citation_regex.replace_all(html, |caps: &regex::Captures| {
-            let citation_keys = &caps[1];
-            let keys: Vec<&str> = citation_keys.split(',').map(|s| s.trim()).collect();
-            
-            let citations: Vec<String> = keys.iter().map(|key| {
-                format!("<a href=\"#bib-{}\" class=\"citation\" data-key=\"{}\">[<span class=\"citation-number\" data-cite=\"{}\"></span>]</a>", 
-                       key, key, key)
-            }).collect();
-            
-            citations.join(", ")
-        });
 
-        Ok(processed_html.to_string())
+        // Keys in order of first citation
+        let mut cited: Vec<String> = Vec::new();
+        for caps in citation_regex.captures_iter(html) {
+            for key in caps[1].split(',').map(|s| s.trim()) {
+                if !key.is_empty() && !cited.iter().any(|k| k == key) {
+                    cited.push(key.to_string());
+                }
+            }
+        }
+
+        if cited.is_empty() {
+            return Ok(html.replace(BIBLIOGRAPHY_MARKER, ""));
+        }
+
+        let file = bib_config.file.as_ref().ok_or_else(|| anyhow::anyhow!(
+            "Document cites {} but no bibliography file is configured (set references.bibliography.file or `bibliography` in front matter)",
+            cited.join(", ")
+        ))?;
+        let bib_path = if file.is_relative() && self.base_path.join(file).exists() {
+            self.base_path.join(file)
+        } else {
+            file.clone()
+        };
+
+        let bibliography = Bibliography::load(&bib_path)?;
+        let style = CitationStyle::parse(&bib_config.style)?;
+
+        let unknown: Vec<&str> = cited.iter()
+            .filter(|key| bibliography.get(key).is_none())
+            .map(|key| key.as_str())
+            .collect();
+        if !unknown.is_empty() {
+            anyhow::bail!("Unknown citation keys: {} (not found in {})", unknown.join(", "), bib_path.display());
+        }
+
+        let entries = bibliography.sorted_entries(&cited, &bib_config.sort_by, style);
+        let numbers: HashMap<String, usize> = entries.iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.key.clone(), i + 1))
+            .collect();
+
+        let mut processed_html = citation_regex.replace_all(html, |caps: &regex::Captures| {
+            let keys: Vec<&str> = caps[1].split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+            // `[@cite:,]` names no source, so leave it as the author wrote it
+            let Some(first) = keys.first() else {
+                return caps[0].to_string();
+            };
+            let group: Vec<_> = keys.iter().filter_map(|key| bibliography.get(key)).collect();
+
+            format!(
+                "<a href=\"#bib-{}\" class=\"citation\" data-key=\"{}\">{}</a>",
+                html_escape::encode_double_quoted_attribute(first),
+                html_escape::encode_double_quoted_attribute(&keys.join(",")),
+                html_escape::encode_text(&bibliography.format_citation(&group, style, &numbers))
+            )
+        }).to_string();
+
+        let mut list = format!(
+            r#"<section class="bibliography" id="bibliography"><h2>{}</h2><ol class="bibliography-list">"#,
+            html_escape::encode_text(&bib_config.title)
+        );
+        for entry in &entries {
+            let label = if style == CitationStyle::Ieee {
+                format!(r#"<span class="bibliography-label">[{}]</span> "#, numbers[&entry.key])
+            } else {
+                String::new()
+            };
+            list.push_str(&format!(
+                r#"<li id="bib-{}" class="bibliography-item">{label}{}</li>"#,
+                html_escape::encode_double_quoted_attribute(&entry.key),
+                bibliography.format_reference(entry, style)
+            ));
+        }
+        list.push_str("</ol></section>");
+
+        // Place the list where the author asked for it, otherwise at the end
+        if processed_html.contains(BIBLIOGRAPHY_MARKER) {
+            processed_html = processed_html.replacen(BIBLIOGRAPHY_MARKER, &list, 1).replace(BIBLIOGRAPHY_MARKER, "");
+        } else {
+            processed_html.push_str(&list);
+        }
+
+        Ok(processed_html)
     }
 
     pub fn enhance_code_blocks(&self, html: &str) -> Result<String> {
@@ -473,9 +557,9 @@ impl AdvancedProcessor {
 }
 
 #[derive(Debug)]
-struct TocEntry {
-    level: usize,
-    text: String,
-    id: String,
-    section_number: String,
+pub struct TocEntry {
+    pub level: usize,
+    pub text: String,
+    pub id: String,
+    pub section_number: String,
 }
\ No newline at end of file
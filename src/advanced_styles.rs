@@ -46,7 +46,6 @@ impl AdvancedStyles {
 .toc-link {
     color: #2c3e50;
     text-decoration: none;
-    flex-grow: 1;
 }
 
 .toc-link:hover {
@@ -54,16 +53,23 @@ impl AdvancedStyles {
     text-decoration: underline;
 }
 
+/* Dotted leader between the entry and its right-aligned page number */
+.toc-leader {
+    flex-grow: 1;
+    border-bottom: 1px dotted #adb5bd;
+    margin: 0 0.5rem;
+    transform: translateY(-0.3em);
+}
+
 .toc-page-number {
     font-weight: 500;
     color: #6c757d;
-    margin-left: 0.5rem;
+    font-variant-numeric: tabular-nums;
 }
 
-.toc-page-number::before {
-    content: "...";
-    letter-spacing: 2px;
-    margin-right: 0.25rem;
+/* Page numbers are only known for PDF output */
+.toc-leader:has(+ .toc-page-number:empty) {
+    display: none;
 }
 "#;
 
@@ -328,10 +334,6 @@ impl AdvancedStyles {
     text-decoration: underline;
 }
 
-.citation-number {
-    font-size: 0.9em;
-}
-
 /* Bibliography Styles */
 .bibliography {
     margin-top: 3rem;
@@ -356,19 +358,12 @@ impl AdvancedStyles {
     line-height: 1.5;
 }
 
-.bibliography-item::before {
-    content: "[" counter(bibliography-counter) "]";
-    counter-increment: bibliography-counter;
+.bibliography-label {
     font-weight: 600;
     color: #3498db;
-    margin-right: 0.5rem;
-}
-
-.bibliography {
-    counter-reset: bibliography-counter;
 }
 
-/* Figure and Table Numbering */
+/* Figure, Table and Equation Numbering */
 .figure-number,
 .table-number,
 .equation-number {
@@ -376,33 +371,30 @@ impl AdvancedStyles {
     color: #2c3e50;
 }
 
-/* Enhanced Table Styles */
-table {
-    counter-increment: table-counter;
+.figure {
+    margin: 2rem 0;
+    text-align: center;
 }
 
-.table-caption::before {
-    content: "Table " counter(table-counter) ": ";
-    font-weight: 600;
-    color: #2c3e50;
+.figure-caption,
+.table-caption {
+    font-size: 0.9em;
+    margin: 0.5rem 0;
 }
 
-/* Enhanced Figure Styles */
-.figure {
-    counter-increment: figure-counter;
-    margin: 2rem 0;
-    text-align: center;
+.equation {
+    position: relative;
 }
 
-.figure-caption::before {
-    content: "Figure " counter(figure-counter) ": ";
-    font-weight: 600;
-    color: #2c3e50;
+.equation-number {
+    position: absolute;
+    right: 0;
+    top: 50%;
+    transform: translateY(-50%);
 }
 
-/* Global counters */
-body {
-    counter-reset: figure-counter table-counter equation-counter;
+.cross-ref-missing {
+    color: #e74c3c;
 }
 "#.to_string()
     }
@@ -430,18 +422,123 @@ body {
         font-size: 0.8em;
         color: #666;
     }
-    
-    .toc-page-number::after {
-        content: target-counter(attr(data-ref), page);
-    }
-    
-    .ref-number::after {
-        content: target-counter(attr(data-ref), figure-counter);
+}
+"#.to_string()
     }
-    
-    .citation-number::after {
-        content: target-counter(attr(data-cite), bibliography-counter);
+
+    pub fn generate_book_styles() -> String {
+        r#"
+/* Book Assembly Styles */
+.book-part,
+.book-chapter,
+.book-appendix {
+    page-break-before: always;
+}
+
+.book-part {
+    display: flex;
+    align-items: center;
+    justify-content: center;
+    min-height: 60vh;
+}
+
+.part-title {
+    text-align: center;
+    border-bottom: none;
+}
+
+.book-appendix::before {
+    content: "Appendix " attr(data-appendix);
+    display: block;
+    font-size: 0.9rem;
+    font-weight: 600;
+    letter-spacing: 1px;
+    text-transform: uppercase;
+    color: #6c757d;
+}
+"#.to_string()
     }
+
+    pub fn generate_cover_styles() -> String {
+        r#"
+/* Cover Page */
+.cover {
+    display: flex;
+    flex-direction: column;
+    min-height: 90vh;
+    page-break-after: always;
+    break-after: page;
+}
+
+.cover-main {
+    flex: 1;
+    display: flex;
+    flex-direction: column;
+    justify-content: center;
+}
+
+.cover-title {
+    font-size: 2.6em;
+    font-weight: 700;
+    line-height: 1.2;
+}
+
+.cover-subtitle {
+    font-size: 1.4em;
+    color: #555;
+    margin-top: 0.5em;
+}
+
+.cover-details p,
+.cover-organization {
+    margin: 0.2em 0;
+}
+
+.cover-logo {
+    max-width: 180px;
+    max-height: 90px;
+    object-fit: contain;
+}
+
+.cover-confidentiality {
+    margin-top: 2em;
+    font-size: 0.9em;
+    font-weight: 600;
+    letter-spacing: 1px;
+    text-transform: uppercase;
+    color: #b42318;
+}
+
+.cover-classic {
+    align-items: center;
+    text-align: center;
+}
+
+.cover-modern .cover-band {
+    display: flex;
+    align-items: center;
+    justify-content: space-between;
+    padding-bottom: 1em;
+    border-bottom: 6px solid var(--color-primary, #2563eb);
+}
+
+.cover-modern .cover-title {
+    color: var(--color-primary, #2563eb);
+}
+
+.cover-academic {
+    align-items: center;
+    text-align: center;
+    font-family: var(--font-family-serif, Georgia, serif);
+}
+
+.cover-academic .cover-organization {
+    font-variant: small-caps;
+    font-size: 1.2em;
+}
+
+.cover-academic .cover-title {
+    font-weight: 400;
 }
 "#.to_string()
     }
@@ -460,6 +557,11 @@ body {
         styles.push_str(&Self::generate_code_styles(config));
         styles.push_str(&Self::generate_reference_styles());
         styles.push_str(&Self::generate_advanced_print_styles());
+        styles.push_str(&Self::generate_book_styles());
+
+        if config.cover.enabled {
+            styles.push_str(&Self::generate_cover_styles());
+        }
         
         styles
     }
@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs;
+use std::path::PathBuf;
+use crate::config::{AssetConfig, AssetMode};
+use crate::logger::Logger;
+
+// Vendored KaTeX/Mermaid files are embedded at compile time by build.rs
+// from assets/vendor, mirroring how built-in themes are baked into the binary
+include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+
+const KATEX_CDN: &str = r#"<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/katex.min.css">
+    <script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/katex.min.js"></script>
+    <script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/contrib/auto-render.min.js"></script>"#;
+const MERMAID_CDN: &str = r#"<script src="https://cdn.jsdelivr.net/npm/mermaid@9.4.3/dist/mermaid.min.js"></script>"#;
+
+const KATEX_CSS: &str = "katex/katex.min.css";
+const KATEX_JS: &str = "katex/katex.min.js";
+const KATEX_AUTO_RENDER_JS: &str = "katex/contrib/auto-render.min.js";
+const MERMAID_JS: &str = "mermaid/mermaid.min.js";
+
+lazy_static! {
+    static ref FONT_URL_REGEX: Regex = Regex::new(r#"url\(["']?(fonts/[^"')]+)["']?\)"#).expect("Invalid font url regex");
+}
+
+/// Resolves the `<head>` markup that loads KaTeX and Mermaid, either from the
+/// CDN, from the copies embedded in the binary, or from a local directory.
+pub struct AssetLoader {
+    config: AssetConfig,
+}
+
+impl AssetLoader {
+    pub fn new(config: AssetConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the head tags for the libraries a document actually needs
+    pub fn head_html(&self, needs_math: bool, needs_mermaid: bool) -> Result<String> {
+        let mut head = String::new();
+
+        if needs_math {
+            head.push_str(&self.katex_html()?);
+            head.push('\n');
+        }
+
+        if needs_mermaid {
+            head.push_str(&self.mermaid_html()?);
+            head.push('\n');
+        }
+
+        Ok(head)
+    }
+
+    fn katex_html(&self) -> Result<String> {
+        if self.config.mode == AssetMode::Cdn {
+            return Ok(KATEX_CDN.to_string());
+        }
+
+        let loaded = (|| -> Result<String> {
+            let css = String::from_utf8(self.load_asset(KATEX_CSS)?)
+                .context("KaTeX stylesheet is not valid UTF-8")?;
+            let css = self.inline_fonts(&css);
+            let katex_js = self.load_asset(KATEX_JS)?;
+            let auto_render_js = self.load_asset(KATEX_AUTO_RENDER_JS)?;
+
+            Ok(format!(
+                r#"<link rel="stylesheet" href="{}">
+    <script defer src="{}"></script>
+    <script defer src="{}"></script>"#,
+                data_url("text/css", css.as_bytes()),
+                data_url("text/javascript", &katex_js),
+                data_url("text/javascript", &auto_render_js),
+            ))
+        })();
+
+        self.or_cdn_fallback(loaded, "KaTeX", KATEX_CDN)
+    }
+
+    fn mermaid_html(&self) -> Result<String> {
+        if self.config.mode == AssetMode::Cdn {
+            return Ok(MERMAID_CDN.to_string());
+        }
+
+        let loaded = self.load_asset(MERMAID_JS)
+            .map(|js| format!(r#"<script src="{}"></script>"#, data_url("text/javascript", &js)));
+
+        self.or_cdn_fallback(loaded, "Mermaid", MERMAID_CDN)
+    }
+
+    fn or_cdn_fallback(&self, loaded: Result<String>, library: &str, cdn: &str) -> Result<String> {
+        match loaded {
+            Ok(html) => Ok(html),
+            Err(e) if self.config.strict => {
+                Err(e.context(format!("Failed to load {library} rendering library")))
+            }
+            Err(e) => {
+                Logger::warning(format!("Failed to load {library} locally ({e}), falling back to CDN"));
+                Ok(cdn.to_string())
+            }
+        }
+    }
+
+    fn load_asset(&self, name: &str) -> Result<Vec<u8>> {
+        match self.config.mode {
+            AssetMode::Embedded => EMBEDDED_ASSETS.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, bytes)| bytes.to_vec())
+                .ok_or_else(|| anyhow::anyhow!(
+                    "{name} was not embedded at build time (populate assets/vendor and rebuild)"
+                )),
+            AssetMode::Path => {
+                let path = self.asset_dir()?.join(name);
+                fs::read(&path)
+                    .with_context(|| format!("Failed to read asset: {}", path.display()))
+            }
+            AssetMode::Cdn => anyhow::bail!("{name} is loaded from the CDN"),
+        }
+    }
+
+    fn asset_dir(&self) -> Result<PathBuf> {
+        self.config.path.clone()
+            .ok_or_else(|| anyhow::anyhow!("assets.path must be set when assets.mode is \"path\""))
+    }
+
+    /// Replace relative KaTeX font URLs with data URLs, since the page is loaded
+    /// from a data URL and cannot resolve paths relative to the stylesheet
+    fn inline_fonts(&self, css: &str) -> String {
+        FONT_URL_REGEX.replace_all(css, |caps: &regex::Captures| {
+            let font = format!("katex/{}", &caps[1]);
+            let mime = match font.rsplit('.').next() {
+                Some("woff2") => "font/woff2",
+                Some("woff") => "font/woff",
+                _ => "font/ttf",
+            };
+
+            match self.load_asset(&font) {
+                Ok(bytes) => format!("url({})", data_url(mime, &bytes)),
+                Err(_) => caps[0].to_string(), // Leave unresolved fonts for the browser to skip
+            }
+        }).into_owned()
+    }
+}
+
+fn data_url(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, STANDARD.encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_mode_inlines_fonts() {
+        let dir = tempfile::tempdir().unwrap();
+        let katex_dir = dir.path().join("katex");
+        fs::create_dir_all(katex_dir.join("contrib")).unwrap();
+        fs::create_dir_all(katex_dir.join("fonts")).unwrap();
+        fs::write(katex_dir.join("katex.min.css"), "@font-face{src:url(fonts/KaTeX_Main.woff2) format(\"woff2\")}").unwrap();
+        fs::write(katex_dir.join("katex.min.js"), "var katex={};").unwrap();
+        fs::write(katex_dir.join("contrib/auto-render.min.js"), "function renderMathInElement(){}").unwrap();
+        fs::write(katex_dir.join("fonts/KaTeX_Main.woff2"), [0u8, 1, 2]).unwrap();
+
+        let loader = AssetLoader::new(AssetConfig {
+            mode: AssetMode::Path,
+            path: Some(dir.path().to_path_buf()),
+            strict: true,
+        });
+
+        let head = loader.head_html(true, false).unwrap();
+        assert!(!head.contains("cdn.jsdelivr.net"));
+
+        let css = loader.inline_fonts("src:url(fonts/KaTeX_Main.woff2)");
+        assert_eq!(css, "src:url(data:font/woff2;base64,AAEC)");
+    }
+
+    #[test]
+    fn test_strict_mode_fails_on_missing_assets() {
+        let loader = AssetLoader::new(AssetConfig {
+            mode: AssetMode::Path,
+            path: Some(PathBuf::from("does-not-exist")),
+            strict: true,
+        });
+
+        assert!(loader.head_html(false, true).is_err());
+    }
+}
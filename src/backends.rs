@@ -0,0 +1,307 @@
+use anyhow::Result;
+use serde_json::Map;
+use std::path::Path;
+use crate::book::BookManifest;
+use crate::config::{Config, DocumentMetadata, PdfEngine};
+use crate::docx_converter::DocxConverter;
+use crate::error_handler::PapercraftError;
+use crate::html_converter::{ConversionOptions, HtmlToPdfConverter};
+use crate::latex_converter::LatexConverter;
+use crate::native_pdf::NativePdfConverter;
+
+/// Markdown features an output format can render. Dry runs warn about
+/// documents that use a feature the selected backend would drop.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    pub themes: bool,   // Built-in and custom themes style the output
+    pub math: bool,     // `$$...$$` and fenced `math` blocks
+    pub diagrams: bool, // Fenced `mermaid` blocks
+}
+
+/// An output format selected with `--format`. Single files, books, batch and
+/// watch mode all convert through this trait.
+pub trait Backend: Send + Sync {
+    /// The `--format` value that selects this backend
+    fn name(&self) -> &'static str;
+
+    /// Extension given to output files, without the dot
+    fn extension(&self) -> &'static str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()>;
+
+    /// Convert Markdown held in memory; relative images resolve against `base_path`
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>>;
+
+    /// Convert the chapters of a book manifest into one document
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()>;
+}
+
+/// The backends available to a run, looked up by format name
+pub struct BackendRegistry<'a> {
+    backends: Vec<Box<dyn Backend + 'a>>,
+}
+
+impl<'a> BackendRegistry<'a> {
+    /// Every built-in format. `pdf` renders with `engine`; `metadata` holds
+    /// the command-line title and author, which win over front matter.
+    pub fn builtin(converter: &'a HtmlToPdfConverter, engine: PdfEngine, metadata: DocumentMetadata) -> Self {
+        let mut registry = Self { backends: Vec::new() };
+
+        match engine {
+            PdfEngine::Chrome => registry.register(ChromePdfBackend { converter, metadata: metadata.clone() }),
+            PdfEngine::Native => registry.register(NativePdfBackend { metadata: metadata.clone() }),
+        }
+        registry.register(DocxBackend { metadata: metadata.clone() });
+        registry.register(HtmlBackend { converter, metadata: metadata.clone() });
+        registry.register(EpubBackend { converter, metadata: metadata.clone() });
+        registry.register(LatexBackend { metadata });
+
+        registry
+    }
+
+    /// Add a backend, replacing any registered under the same name
+    pub fn register(&mut self, backend: impl Backend + 'a) {
+        self.backends.retain(|existing| existing.name() != backend.name());
+        self.backends.push(Box::new(backend));
+    }
+
+    pub fn get(&self, format: &str) -> Result<&dyn Backend> {
+        let format = format.to_lowercase();
+        self.backends.iter()
+            .find(|backend| backend.name() == format)
+            .map(|backend| backend.as_ref() as &dyn Backend)
+            .ok_or_else(|| PapercraftError::UnknownFormat { format, available: self.names().join(", ") }.into())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.backends.iter().map(|backend| backend.name()).collect()
+    }
+}
+
+fn options(config: &Config, metadata: &DocumentMetadata) -> ConversionOptions {
+    ConversionOptions {
+        config: config.clone(),
+        metadata: metadata.clone(),
+        source: None,
+        front_matter: Map::new(),
+    }
+}
+
+/// Command-line metadata, then the book's title, then the config file's
+fn book_metadata(metadata: &DocumentMetadata, manifest: &BookManifest, config: &Config) -> DocumentMetadata {
+    metadata.clone()
+        .or(DocumentMetadata { title: manifest.title.clone(), ..Default::default() })
+        .or(config.metadata.clone())
+}
+
+/// PDF printed by headless Chrome, with full theme support
+struct ChromePdfBackend<'a> {
+    converter: &'a HtmlToPdfConverter,
+    metadata: DocumentMetadata,
+}
+
+impl Backend for ChromePdfBackend<'_> {
+    fn name(&self) -> &'static str { "pdf" }
+
+    fn extension(&self) -> &'static str { "pdf" }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { themes: true, math: true, diagrams: true }
+    }
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()> {
+        self.converter.convert_file(input, output, options(config, &self.metadata))
+    }
+
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>> {
+        self.converter.render_pdf(markdown, base_path, options(config, &self.metadata))
+    }
+
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()> {
+        self.converter.convert_book(manifest, output, options(config, &self.metadata))
+    }
+}
+
+/// PDF drawn with printpdf when `output.engine = "native"`
+struct NativePdfBackend {
+    metadata: DocumentMetadata,
+}
+
+impl Backend for NativePdfBackend {
+    fn name(&self) -> &'static str { "pdf" }
+
+    fn extension(&self) -> &'static str { "pdf" }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { themes: false, math: false, diagrams: false }
+    }
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()> {
+        NativePdfConverter::new(config.clone())
+            .with_metadata(self.metadata.clone())
+            .convert_file(input, output)
+    }
+
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>> {
+        NativePdfConverter::new(config.clone())
+            .with_metadata(self.metadata.clone())
+            .with_base_path(base_path)
+            .convert_str(markdown)
+    }
+
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()> {
+        let converter = NativePdfConverter::new(config.clone())
+            .with_metadata(book_metadata(&self.metadata, manifest, config))
+            .with_base_path(&manifest.root);
+        converter.convert_markdown(&manifest.assemble_markdown()?, output)
+    }
+}
+
+struct DocxBackend {
+    metadata: DocumentMetadata,
+}
+
+impl Backend for DocxBackend {
+    fn name(&self) -> &'static str { "docx" }
+
+    fn extension(&self) -> &'static str { "docx" }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { themes: true, math: false, diagrams: false }
+    }
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()> {
+        DocxConverter::new(config.clone())
+            .with_metadata(self.metadata.clone())
+            .convert_file(input, output)
+    }
+
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>> {
+        DocxConverter::new(config.clone())
+            .with_metadata(self.metadata.clone())
+            .with_base_path(base_path)
+            .convert_str(markdown)
+    }
+
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()> {
+        let converter = DocxConverter::new(config.clone())
+            .with_metadata(book_metadata(&self.metadata, manifest, config))
+            .with_base_path(&manifest.root);
+        converter.convert_markdown(&manifest.assemble_markdown()?, output)
+    }
+}
+
+/// Standalone HTML page; math and diagrams render in the reader's browser
+struct HtmlBackend<'a> {
+    converter: &'a HtmlToPdfConverter,
+    metadata: DocumentMetadata,
+}
+
+impl Backend for HtmlBackend<'_> {
+    fn name(&self) -> &'static str { "html" }
+
+    fn extension(&self) -> &'static str { "html" }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { themes: true, math: true, diagrams: true }
+    }
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()> {
+        self.converter.convert_file_to_html(input, output, options(config, &self.metadata))
+    }
+
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>> {
+        self.converter.render_html_document(markdown, base_path, options(config, &self.metadata))
+            .map(String::into_bytes)
+    }
+
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()> {
+        self.converter.convert_book_to_html(manifest, output, options(config, &self.metadata))
+    }
+}
+
+/// EPUB 3 book; most readers run no scripts, so math and diagrams stay as source
+struct EpubBackend<'a> {
+    converter: &'a HtmlToPdfConverter,
+    metadata: DocumentMetadata,
+}
+
+impl Backend for EpubBackend<'_> {
+    fn name(&self) -> &'static str { "epub" }
+
+    fn extension(&self) -> &'static str { "epub" }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { themes: true, math: false, diagrams: false }
+    }
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()> {
+        self.converter.convert_file_to_epub(input, output, options(config, &self.metadata))
+    }
+
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>> {
+        self.converter.render_epub(markdown, base_path, options(config, &self.metadata))
+    }
+
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()> {
+        self.converter.convert_book_to_epub(manifest, output, options(config, &self.metadata))
+    }
+}
+
+struct LatexBackend {
+    metadata: DocumentMetadata,
+}
+
+impl Backend for LatexBackend {
+    fn name(&self) -> &'static str { "latex" }
+
+    fn extension(&self) -> &'static str { "tex" }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { themes: true, math: true, diagrams: false }
+    }
+
+    fn convert(&self, input: &Path, output: &Path, config: &Config) -> Result<()> {
+        LatexConverter::new(config.clone())
+            .with_metadata(self.metadata.clone())
+            .convert_file(input, output)
+    }
+
+    fn convert_str(&self, markdown: &str, base_path: &Path, config: &Config) -> Result<Vec<u8>> {
+        LatexConverter::new(config.clone())
+            .with_metadata(self.metadata.clone())
+            .with_base_path(base_path)
+            .convert_str(markdown)
+            .map(String::into_bytes)
+    }
+
+    fn convert_book(&self, manifest: &BookManifest, output: &Path, config: &Config) -> Result<()> {
+        let converter = LatexConverter::new(config.clone())
+            .with_metadata(book_metadata(&self.metadata, manifest, config))
+            .with_base_path(&manifest.root);
+        converter.convert_markdown(&manifest.assemble_markdown()?, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_resolves_formats_and_engines() {
+        let converter = HtmlToPdfConverter::new().unwrap();
+
+        let registry = BackendRegistry::builtin(&converter, PdfEngine::Chrome, DocumentMetadata::default());
+        assert_eq!(registry.get("LaTeX").unwrap().extension(), "tex");
+        assert!(registry.get("pdf").unwrap().capabilities().math);
+        let error = registry.get("odt").err().unwrap().to_string();
+        assert!(error.contains("pdf, docx, html, epub, latex"));
+
+        let mut registry = BackendRegistry::builtin(&converter, PdfEngine::Chrome, DocumentMetadata::default());
+        registry.register(NativePdfBackend { metadata: DocumentMetadata::default() });
+        assert_eq!(registry.names().len(), 5);
+        assert!(!registry.get("pdf").unwrap().capabilities().math);
+    }
+}
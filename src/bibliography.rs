@@ -0,0 +1,668 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::config::BibliographySort;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CitationStyle {
+    Apa,
+    Mla,
+    Chicago,
+    Ieee,
+}
+
+impl CitationStyle {
+    pub fn parse(style: &str) -> Result<Self> {
+        match style.to_lowercase().as_str() {
+            "apa" => Ok(Self::Apa),
+            "mla" => Ok(Self::Mla),
+            "chicago" => Ok(Self::Chicago),
+            "ieee" => Ok(Self::Ieee),
+            other => anyhow::bail!("Unknown citation style '{other}'. Use apa, mla, chicago or ieee"),
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        self == Self::Ieee
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Name {
+    pub family: String,
+    pub given: Option<String>,
+}
+
+impl Name {
+    /// "Ada Lovelace" → "A."; hyphenated and multiple given names keep each initial
+    fn initials(&self) -> String {
+        self.given.as_deref().unwrap_or("")
+            .split_whitespace()
+            .filter_map(|part| part.chars().next())
+            .map(|c| format!("{c}."))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn given_family(&self) -> String {
+        match &self.given {
+            Some(given) => format!("{given} {}", self.family),
+            None => self.family.clone(),
+        }
+    }
+
+    fn family_given(&self) -> String {
+        match &self.given {
+            Some(given) => format!("{}, {given}", self.family),
+            None => self.family.clone(),
+        }
+    }
+
+    fn family_initials(&self) -> String {
+        match self.initials().as_str() {
+            "" => self.family.clone(),
+            initials => format!("{}, {initials}", self.family),
+        }
+    }
+
+    fn initials_family(&self) -> String {
+        match self.initials().as_str() {
+            "" => self.family.clone(),
+            initials => format!("{initials} {}", self.family),
+        }
+    }
+}
+
+/// A single source, normalized from either BibTeX or CSL-JSON
+#[derive(Debug, Clone, Default)]
+pub struct BibEntry {
+    pub key: String,
+    pub entry_type: String,
+    pub authors: Vec<Name>,
+    pub title: Option<String>,
+    pub year: Option<String>,
+    pub container: Option<String>,
+    pub publisher: Option<String>,
+    pub volume: Option<String>,
+    pub issue: Option<String>,
+    pub pages: Option<String>,
+    pub doi: Option<String>,
+    pub url: Option<String>,
+}
+
+impl BibEntry {
+    fn is_book(&self) -> bool {
+        matches!(self.entry_type.as_str(), "book" | "thesis" | "phdthesis" | "mastersthesis" | "report" | "techreport")
+    }
+
+    fn year_or_nd(&self) -> &str {
+        self.year.as_deref().unwrap_or("n.d.")
+    }
+
+    fn first_author_family(&self) -> &str {
+        self.authors.first().map(|a| a.family.as_str()).unwrap_or("")
+    }
+}
+
+/// Reference database loaded from a `.bib` or CSL-JSON file
+#[derive(Debug, Default)]
+pub struct Bibliography {
+    entries: HashMap<String, BibEntry>,
+}
+
+impl Bibliography {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read bibliography: {}", path.display()))?;
+
+        let entries = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bib") => parse_bibtex(&content),
+            Some("json") => parse_csl_json(&content)
+                .with_context(|| format!("Failed to parse CSL-JSON bibliography: {}", path.display()))?,
+            _ => anyhow::bail!(
+                "Unsupported bibliography format. Use .bib or CSL-JSON (.json): {}",
+                path.display()
+            ),
+        };
+
+        Ok(Self {
+            entries: entries.into_iter().map(|entry| (entry.key.clone(), entry)).collect(),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&BibEntry> {
+        self.entries.get(key)
+    }
+
+    /// Order the cited entries for the reference list. `cited` is in order of
+    /// first citation, which is also the fallback ordering. Numeric styles
+    /// number references by first citation, so they ignore `sort_by`.
+    pub fn sorted_entries<'a>(&'a self, cited: &[String], sort_by: &BibliographySort, style: CitationStyle) -> Vec<&'a BibEntry> {
+        let mut entries: Vec<&BibEntry> = cited.iter().filter_map(|key| self.get(key)).collect();
+        if style.is_numeric() {
+            return entries;
+        }
+
+        match sort_by {
+            BibliographySort::Author => entries.sort_by(|a, b| {
+                a.first_author_family().to_lowercase().cmp(&b.first_author_family().to_lowercase())
+                    .then_with(|| a.year.cmp(&b.year))
+            }),
+            BibliographySort::Title => entries.sort_by_key(|entry| entry.title.as_deref().unwrap_or("").to_lowercase()),
+            BibliographySort::Year => entries.sort_by(|a, b| a.year.cmp(&b.year)
+                .then_with(|| a.first_author_family().cmp(b.first_author_family()))),
+            BibliographySort::Citation => {}
+        }
+
+        entries
+    }
+
+    /// Format an in-text citation for one or more entries. `numbers` maps keys
+    /// to their position in the reference list for numeric styles.
+    pub fn format_citation(&self, entries: &[&BibEntry], style: CitationStyle, numbers: &HashMap<String, usize>) -> String {
+        if style.is_numeric() {
+            return entries.iter()
+                .map(|entry| format!("[{}]", numbers.get(&entry.key).copied().unwrap_or(0)))
+                .collect::<Vec<_>>()
+                .join(", ");
+        }
+
+        let parts: Vec<String> = entries.iter().map(|entry| {
+            let authors = short_author_list(entry, style);
+            match style {
+                CitationStyle::Apa => format!("{authors}, {}", entry.year_or_nd()),
+                CitationStyle::Chicago => format!("{authors} {}", entry.year_or_nd()),
+                _ => authors,
+            }
+        }).collect();
+
+        format!("({})", parts.join("; "))
+    }
+
+    /// Format a reference list entry as HTML
+    pub fn format_reference(&self, entry: &BibEntry, style: CitationStyle) -> String {
+        let title = entry.title.as_deref().map(escape).unwrap_or_default();
+        let container = entry.container.as_deref().map(escape);
+        let publisher = entry.publisher.as_deref().map(escape);
+        let link = entry.doi.as_ref()
+            .map(|doi| format!("https://doi.org/{}", doi.trim_start_matches("https://doi.org/")))
+            .or_else(|| entry.url.clone())
+            .map(|url| format!(r#" <a href="{0}">{0}</a>"#, escape(&url)))
+            .unwrap_or_default();
+
+        let mut reference = match style {
+            CitationStyle::Apa => {
+                let mut parts = format!("{} ({}). ", escape(&full_author_list(entry, style)), entry.year_or_nd());
+                if entry.is_book() {
+                    parts.push_str(&format!("<em>{title}</em>. "));
+                } else {
+                    parts.push_str(&format!("{title}. "));
+                }
+                if let Some(container) = &container {
+                    parts.push_str(&format!("<em>{container}</em>"));
+                    if let Some(volume) = &entry.volume {
+                        parts.push_str(&format!(", <em>{}</em>", escape(volume)));
+                    }
+                    if let Some(issue) = &entry.issue {
+                        parts.push_str(&format!("({})", escape(issue)));
+                    }
+                    if let Some(pages) = &entry.pages {
+                        parts.push_str(&format!(", {}", escape(pages)));
+                    }
+                    parts.push_str(". ");
+                }
+                if let Some(publisher) = &publisher {
+                    parts.push_str(&format!("{publisher}. "));
+                }
+                parts
+            }
+            CitationStyle::Mla => {
+                let mut parts = format!("{}. ", escape(&full_author_list(entry, style)));
+                parts.push_str(&quoted_or_italic_title(entry, &title));
+                let mut details = Vec::new();
+                if let Some(container) = &container {
+                    details.push(format!("<em>{container}</em>"));
+                }
+                if let Some(volume) = &entry.volume {
+                    details.push(format!("vol. {}", escape(volume)));
+                }
+                if let Some(issue) = &entry.issue {
+                    details.push(format!("no. {}", escape(issue)));
+                }
+                if let Some(publisher) = &publisher {
+                    details.push(publisher.clone());
+                }
+                details.push(entry.year_or_nd().to_string());
+                if let Some(pages) = &entry.pages {
+                    details.push(format!("pp. {}", escape(pages)));
+                }
+                parts.push_str(&format!(" {}.", details.join(", ")));
+                parts
+            }
+            CitationStyle::Chicago => {
+                let mut parts = format!("{}. {}. ", escape(&full_author_list(entry, style)), entry.year_or_nd());
+                parts.push_str(&quoted_or_italic_title(entry, &title));
+                if let Some(container) = &container {
+                    parts.push_str(&format!(" <em>{container}</em>"));
+                    if let Some(volume) = &entry.volume {
+                        parts.push_str(&format!(" {}", escape(volume)));
+                    }
+                    if let Some(issue) = &entry.issue {
+                        parts.push_str(&format!(" ({})", escape(issue)));
+                    }
+                    if let Some(pages) = &entry.pages {
+                        parts.push_str(&format!(": {}", escape(pages)));
+                    }
+                    parts.push('.');
+                }
+                if let Some(publisher) = &publisher {
+                    parts.push_str(&format!(" {publisher}."));
+                }
+                parts
+            }
+            CitationStyle::Ieee => {
+                let mut parts = format!("{}, ", escape(&full_author_list(entry, style)));
+                let mut details = Vec::new();
+                if entry.is_book() {
+                    details.push(format!("<em>{title}</em>"));
+                } else {
+                    details.push(format!("&ldquo;{title},&rdquo;"));
+                }
+                if let Some(container) = &container {
+                    details.push(format!("<em>{container}</em>"));
+                }
+                if let Some(volume) = &entry.volume {
+                    details.push(format!("vol. {}", escape(volume)));
+                }
+                if let Some(issue) = &entry.issue {
+                    details.push(format!("no. {}", escape(issue)));
+                }
+                if let Some(pages) = &entry.pages {
+                    details.push(format!("pp. {}", escape(pages)));
+                }
+                if let Some(publisher) = &publisher {
+                    details.push(publisher.clone());
+                }
+                details.push(entry.year_or_nd().to_string());
+                // The quoted title already carries its trailing comma
+                parts.push_str(&details.join(", ").replace("&rdquo;,", "&rdquo;"));
+                parts.push('.');
+                parts
+            }
+        };
+
+        reference.push_str(&link);
+        reference.trim().to_string()
+    }
+}
+
+fn quoted_or_italic_title(entry: &BibEntry, title: &str) -> String {
+    if entry.is_book() {
+        format!("<em>{title}</em>.")
+    } else {
+        format!("&ldquo;{title}.&rdquo;")
+    }
+}
+
+/// Author names as they appear in citations, e.g. "Smith & Jones" or "Smith et al."
+fn short_author_list(entry: &BibEntry, style: CitationStyle) -> String {
+    let families: Vec<&str> = entry.authors.iter().map(|a| a.family.as_str()).collect();
+    let conjunction = if style == CitationStyle::Apa { "&" } else { "and" };
+    // Chicago lists up to three authors in citations; APA and MLA only two
+    let max_listed = if style == CitationStyle::Chicago { 3 } else { 2 };
+
+    match families.as_slice() {
+        [] => entry.title.clone().unwrap_or_else(|| entry.key.clone()),
+        [one] => one.to_string(),
+        [first, second] => format!("{first} {conjunction} {second}"),
+        [first, ..] if families.len() > max_listed => format!("{first} et al."),
+        [init @ .., last] => format!("{}, {conjunction} {last}", init.join(", ")),
+    }
+}
+
+/// Author names as they appear in the reference list
+fn full_author_list(entry: &BibEntry, style: CitationStyle) -> String {
+    if entry.authors.is_empty() {
+        return entry.publisher.clone().unwrap_or_else(|| "Anonymous".to_string());
+    }
+
+    let names: Vec<String> = entry.authors.iter().enumerate().map(|(i, name)| match style {
+        CitationStyle::Apa => name.family_initials(),
+        CitationStyle::Ieee => name.initials_family(),
+        // MLA and Chicago invert only the first author
+        _ if i == 0 => name.family_given(),
+        _ => name.given_family(),
+    }).collect();
+
+    match style {
+        CitationStyle::Mla if names.len() > 2 => format!("{}, et al", names[0]),
+        _ => {
+            let conjunction = if style == CitationStyle::Apa { "&" } else { "and" };
+            match names.as_slice() {
+                [one] => one.clone(),
+                [first, second] if style == CitationStyle::Apa => format!("{first}, {conjunction} {second}"),
+                [first, second] => format!("{first} {conjunction} {second}"),
+                [init @ .., last] => format!("{}, {conjunction} {last}", init.join(", ")),
+                [] => unreachable!(),
+            }
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    html_escape::encode_text(text).to_string()
+}
+
+/// Minimal BibTeX reader: `@type{key, field = {value} | "value" | number, ...}`.
+/// `@string`, `@preamble` and `@comment` blocks are skipped.
+fn parse_bibtex(content: &str) -> Vec<BibEntry> {
+    let mut entries = Vec::new();
+    let mut rest = content;
+
+    while let Some(at) = rest.find('@') {
+        rest = &rest[at + 1..];
+        let Some(open) = rest.find(['{', '(']) else { break };
+        let entry_type = rest[..open].trim().to_lowercase();
+        let Some(body_len) = matching_close(&rest[open..]) else { break };
+        let body = &rest[open + 1..open + body_len];
+        rest = &rest[open + body_len + 1..];
+
+        if matches!(entry_type.as_str(), "string" | "preamble" | "comment") {
+            continue;
+        }
+
+        let Some((key, fields)) = body.split_once(',') else { continue };
+        let fields = parse_bibtex_fields(fields);
+        let field = |name: &str| fields.get(name).map(|value| clean_bibtex_value(value));
+
+        entries.push(BibEntry {
+            key: key.trim().to_string(),
+            // Names keep their braces until split, so `{Barnes and Noble}` stays one author
+            authors: fields.get("author")
+                .or_else(|| fields.get("editor"))
+                .map(|authors| parse_bibtex_names(authors))
+                .unwrap_or_default(),
+            title: field("title"),
+            year: field("year").or_else(|| field("date").map(|d| d.chars().take(4).collect())),
+            container: field("journal").or_else(|| field("journaltitle")).or_else(|| field("booktitle")),
+            publisher: field("publisher").or_else(|| field("institution")).or_else(|| field("school")),
+            volume: field("volume"),
+            issue: field("number").or_else(|| field("issue")),
+            pages: field("pages").map(|pages| pages.replace("--", "–")),
+            doi: field("doi"),
+            url: field("url"),
+            entry_type,
+        });
+    }
+
+    entries
+}
+
+/// Offset of the bracket closing the `{` or `(` at the start of `text`
+fn matching_close(text: &str) -> Option<usize> {
+    let open = text.chars().next()?;
+    let close = if open == '(' { ')' } else { '}' };
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Raw field values, braces and escapes included
+fn parse_bibtex_fields(body: &str) -> HashMap<String, &str> {
+    let mut fields = HashMap::new();
+    let mut rest = body;
+
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim().trim_start_matches(',').trim().to_lowercase();
+        let value_part = rest[eq + 1..].trim_start();
+
+        let (value, consumed) = match value_part.chars().next() {
+            Some('{') => match matching_close(value_part) {
+                Some(end) => (&value_part[1..end], end + 1),
+                None => break,
+            },
+            Some('"') => match value_part[1..].find('"') {
+                Some(end) => (&value_part[1..end + 1], end + 2),
+                None => break,
+            },
+            _ => {
+                let end = value_part.find(',').unwrap_or(value_part.len());
+                (value_part[..end].trim(), end)
+            }
+        };
+
+        fields.insert(name, value);
+        rest = &value_part[consumed..];
+    }
+
+    fields
+}
+
+/// Strip protective braces and common LaTeX escapes
+fn clean_bibtex_value(value: &str) -> String {
+    let value = value
+        .replace("\\&", "&")
+        .replace("\\%", "%")
+        .replace("\\_", "_")
+        .replace(['{', '}'], "");
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn parse_bibtex_names(authors: &str) -> Vec<Name> {
+    split_names(authors).into_iter()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            // A fully braced name is a corporate author, kept whole
+            if name.starts_with('{') && matching_close(name) == Some(name.len() - 1) {
+                return Name { family: clean_bibtex_value(name), given: None };
+            }
+            let name = clean_bibtex_value(name);
+            match name.split_once(',') {
+                // "Family, Given"
+                Some((family, given)) => Name {
+                    family: family.trim().to_string(),
+                    given: Some(given.trim().to_string()).filter(|g| !g.is_empty()),
+                },
+                // "Given Family"
+                None => match name.rsplit_once(' ') {
+                    Some((given, family)) => Name {
+                        family: family.to_string(),
+                        given: Some(given.to_string()),
+                    },
+                    None => Name { family: name.clone(), given: None },
+                },
+            }
+        })
+        .collect()
+}
+
+/// Split a BibTeX name list at each ` and ` outside braces
+fn split_names(authors: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in authors.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if depth == 0 && authors[i..].starts_with(" and ") && i >= start => {
+                names.push(&authors[start..i]);
+                start = i + " and ".len();
+            }
+            _ => {}
+        }
+    }
+    names.push(&authors[start..]);
+    names
+}
+
+#[derive(Debug, Deserialize)]
+struct CslItem {
+    id: serde_json::Value,
+    #[serde(rename = "type", default)]
+    item_type: String,
+    #[serde(default)]
+    author: Vec<CslName>,
+    #[serde(default)]
+    editor: Vec<CslName>,
+    title: Option<String>,
+    issued: Option<CslDate>,
+    #[serde(rename = "container-title")]
+    container_title: Option<String>,
+    publisher: Option<String>,
+    volume: Option<serde_json::Value>,
+    issue: Option<serde_json::Value>,
+    page: Option<String>,
+    #[serde(rename = "DOI")]
+    doi: Option<String>,
+    #[serde(rename = "URL")]
+    url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CslName {
+    family: Option<String>,
+    given: Option<String>,
+    literal: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CslDate {
+    #[serde(rename = "date-parts")]
+    date_parts: Option<Vec<Vec<serde_json::Value>>>,
+    literal: Option<String>,
+    raw: Option<String>,
+}
+
+fn parse_csl_json(content: &str) -> Result<Vec<BibEntry>> {
+    let items: Vec<CslItem> = serde_json::from_str(content)?;
+
+    Ok(items.into_iter().map(|item| {
+        let names = if item.author.is_empty() { item.editor } else { item.author };
+
+        BibEntry {
+            key: json_to_string(&item.id),
+            entry_type: match item.item_type.as_str() {
+                "article-journal" | "article-magazine" | "article-newspaper" => "article".to_string(),
+                "chapter" | "paper-conference" => "inproceedings".to_string(),
+                other => other.to_string(),
+            },
+            authors: names.into_iter().map(|name| Name {
+                family: name.family.or(name.literal).unwrap_or_default(),
+                given: name.given,
+            }).collect(),
+            title: item.title,
+            year: item.issued.and_then(|date| {
+                date.date_parts
+                    .and_then(|parts| parts.first().and_then(|part| part.first()).map(json_to_string))
+                    .or(date.literal)
+                    .or(date.raw)
+            }),
+            container: item.container_title,
+            publisher: item.publisher,
+            volume: item.volume.as_ref().map(json_to_string),
+            issue: item.issue.as_ref().map(json_to_string),
+            pages: item.page.map(|pages| pages.replace('-', "–")),
+            doi: item.doi,
+            url: item.url,
+        }
+    }).collect())
+}
+
+fn json_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIBTEX: &str = r#"
+@string{acm = "ACM"}
+@article{knuth1984,
+  author = {Knuth, Donald E.},
+  title = {Literate {Programming}},
+  journal = {The Computer Journal},
+  volume = 27, number = {2},
+  pages = {97--111},
+  year = 1984
+}
+@book{kr1988,
+  author = "Brian W. Kernighan and Dennis M. Ritchie",
+  title = {The C Programming Language},
+  publisher = {Prentice Hall},
+  year = {1988}
+}
+"#;
+
+    fn bibliography() -> Bibliography {
+        Bibliography {
+            entries: parse_bibtex(BIBTEX).into_iter().map(|e| (e.key.clone(), e)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_bibtex_and_cite_per_style() {
+        let bib = bibliography();
+        let knuth = bib.get("knuth1984").unwrap();
+        assert_eq!(knuth.title.as_deref(), Some("Literate Programming"));
+        assert_eq!(knuth.pages.as_deref(), Some("97–111"));
+
+        let kr = bib.get("kr1988").unwrap();
+        assert_eq!(kr.authors[1], Name { family: "Ritchie".into(), given: Some("Dennis M.".into()) });
+        assert_eq!(
+            parse_bibtex_names("{Barnes and Noble} and Knuth, Donald"),
+            vec![Name { family: "Barnes and Noble".into(), given: None }, Name { family: "Knuth".into(), given: Some("Donald".into()) }]
+        );
+
+        // Knuth is cited first, so IEEE numbers it 1 even though the list sorts by author
+        let keys = vec!["knuth1984".to_string(), "kr1988".to_string()];
+        let numbers: HashMap<String, usize> = bib.sorted_entries(&keys, &BibliographySort::Author, CitationStyle::Ieee).iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.key.clone(), i + 1))
+            .collect();
+        let cited = [knuth, kr];
+        assert_eq!(bib.format_citation(&cited, CitationStyle::Apa, &numbers), "(Knuth, 1984; Kernighan & Ritchie, 1988)");
+        assert_eq!(bib.format_citation(&cited, CitationStyle::Mla, &numbers), "(Knuth; Kernighan and Ritchie)");
+        assert_eq!(bib.format_citation(&cited, CitationStyle::Chicago, &numbers), "(Knuth 1984; Kernighan and Ritchie 1988)");
+        assert_eq!(bib.format_citation(&cited, CitationStyle::Ieee, &numbers), "[1], [2]");
+
+        assert_eq!(
+            bib.format_reference(kr, CitationStyle::Apa),
+            "Kernighan, B. W., &amp; Ritchie, D. M. (1988). <em>The C Programming Language</em>. Prentice Hall."
+        );
+    }
+
+    #[test]
+    fn test_csl_json_and_sorting() {
+        let json = r#"[{"id": "doe", "type": "article-journal", "title": "Zebra",
+            "author": [{"family": "Doe", "given": "Jane"}], "issued": {"date-parts": [[2001, 5]]}}]"#;
+        let entry = &parse_csl_json(json).unwrap()[0];
+        assert_eq!(entry.year.as_deref(), Some("2001"));
+        assert_eq!(entry.entry_type, "article");
+
+        let bib = bibliography();
+        let cited = vec!["knuth1984".to_string(), "kr1988".to_string()];
+        let by_author: Vec<&str> = bib.sorted_entries(&cited, &BibliographySort::Author, CitationStyle::Apa).iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(by_author, vec!["kr1988", "knuth1984"]);
+        let by_citation: Vec<&str> = bib.sorted_entries(&cited, &BibliographySort::Citation, CitationStyle::Apa).iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(by_citation, vec!["knuth1984", "kr1988"]);
+    }
+}
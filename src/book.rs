@@ -0,0 +1,510 @@
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::front_matter::FrontMatter;
+use crate::logger::Logger;
+
+lazy_static! {
+    static ref SUMMARY_LINK_REGEX: Regex = Regex::new(r"^\s*(?:[-*+]\s+)?\[([^\]]*)\]\(([^)]+)\)\s*$").expect("Invalid summary link regex");
+    static ref SUMMARY_PART_REGEX: Regex = Regex::new(r"^#{1,6}\s+(.+)$").expect("Invalid summary part regex");
+    static ref HEADING_ID_REGEX: Regex = Regex::new(r#"id="(user-content-[^"]+)""#).expect("Invalid heading id regex");
+    static ref FRAGMENT_LINK_REGEX: Regex = Regex::new(r##"href="#([^"]+)""##).expect("Invalid fragment link regex");
+    static ref MD_LINK_REGEX: Regex = Regex::new(r##"href="([^"#:]+\.md)(#[^"]*)?""##).expect("Invalid markdown link regex");
+    static ref FOOTNOTE_LABEL_REGEX: Regex = Regex::new(r"\[\^([^\]\s]+)\]").expect("Invalid footnote label regex");
+    static ref MD_IMAGE_REGEX: Regex = Regex::new(r"(!\[[^\]]*\]\(\s*<?)([^)\s>]+)").expect("Invalid markdown image regex");
+}
+
+pub const MANIFEST_NAMES: [&str; 2] = ["book.toml", "SUMMARY.md"];
+
+/// On-disk `book.toml` layout
+#[derive(Debug, Deserialize)]
+struct BookToml {
+    title: Option<String>,
+    #[serde(default)]
+    front_matter: Vec<PathBuf>,
+    #[serde(default)]
+    chapters: Vec<PathBuf>,
+    #[serde(default)]
+    parts: Vec<BookTomlPart>,
+    #[serde(default)]
+    appendices: Vec<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BookTomlPart {
+    title: String,
+    chapters: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookEntry {
+    FrontMatter(PathBuf),
+    Part(String),
+    Chapter(PathBuf),
+    Appendix(PathBuf),
+}
+
+/// Ordered list of chapter files that are assembled into a single document
+#[derive(Debug)]
+pub struct BookManifest {
+    pub title: Option<String>,
+    pub root: PathBuf,
+    pub entries: Vec<BookEntry>,
+}
+
+impl BookManifest {
+    /// Load a `book.toml` or mdBook-style `SUMMARY.md`, or look for either
+    /// inside `path` when it is a directory
+    pub fn load(path: &Path) -> Result<Self> {
+        let manifest_path = if path.is_dir() {
+            Self::find_in_dir(path)
+                .ok_or_else(|| anyhow::anyhow!("No book.toml or SUMMARY.md found in {}", path.display()))?
+        } else {
+            path.to_path_buf()
+        };
+
+        let content = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read book manifest: {}", manifest_path.display()))?;
+        let root = manifest_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let mut manifest = match manifest_path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::parse_toml(&content, root)
+                .with_context(|| format!("Failed to parse book manifest: {}", manifest_path.display()))?,
+            Some("md") => Self::parse_summary(&content, root),
+            _ => anyhow::bail!(
+                "Unsupported book manifest. Use book.toml or SUMMARY.md: {}",
+                manifest_path.display()
+            ),
+        };
+
+        // A SUMMARY.md next to a book.toml only supplies the chapter list
+        if manifest.title.is_none() {
+            manifest.title = Self::read_title(&manifest.root);
+        }
+
+        if manifest.chapter_paths().next().is_none() {
+            anyhow::bail!("Book manifest lists no chapters: {}", manifest_path.display());
+        }
+
+        for chapter in manifest.chapter_paths() {
+            if !chapter.is_file() {
+                anyhow::bail!("Chapter listed in book manifest does not exist: {}", chapter.display());
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Whether a path names a book manifest rather than a single chapter
+    pub fn is_manifest(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| MANIFEST_NAMES.iter().any(|m| m.eq_ignore_ascii_case(name)))
+    }
+
+    fn find_in_dir(dir: &Path) -> Option<PathBuf> {
+        MANIFEST_NAMES.iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    fn read_title(root: &Path) -> Option<String> {
+        let content = fs::read_to_string(root.join("book.toml")).ok()?;
+        toml::from_str::<BookToml>(&content).ok()?.title
+    }
+
+    fn parse_toml(content: &str, root: PathBuf) -> Result<Self> {
+        let book: BookToml = toml::from_str(content)?;
+        let mut entries = Vec::new();
+
+        entries.extend(book.front_matter.into_iter().map(|p| BookEntry::FrontMatter(root.join(p))));
+        entries.extend(book.chapters.into_iter().map(|p| BookEntry::Chapter(root.join(p))));
+        for part in book.parts {
+            entries.push(BookEntry::Part(part.title));
+            entries.extend(part.chapters.into_iter().map(|p| BookEntry::Chapter(root.join(p))));
+        }
+        entries.extend(book.appendices.into_iter().map(|p| BookEntry::Appendix(root.join(p))));
+
+        // A book.toml without chapters defers to a SUMMARY.md alongside it
+        if !entries.iter().any(|e| matches!(e, BookEntry::Chapter(_))) {
+            if let Ok(summary) = fs::read_to_string(root.join("SUMMARY.md")) {
+                let mut manifest = Self::parse_summary(&summary, root);
+                manifest.title = book.title;
+                return Ok(manifest);
+            }
+        }
+
+        Ok(Self { title: book.title, root, entries })
+    }
+
+    /// Parse mdBook's SUMMARY.md conventions: plain links before the first
+    /// list item are front matter, list items are chapters, headings start a
+    /// new part and plain links after the chapter list are appendices
+    fn parse_summary(content: &str, root: PathBuf) -> Self {
+        let mut entries = Vec::new();
+        let mut seen_chapter = false;
+
+        for line in content.lines() {
+            if let Some(caps) = SUMMARY_LINK_REGEX.captures(line) {
+                let target = caps[2].trim();
+                if target.is_empty() || target.starts_with("http://") || target.starts_with("https://") {
+                    continue;
+                }
+                let path = root.join(target.split('#').next().unwrap_or(target));
+                let is_list_item = line.trim_start().starts_with(['-', '*', '+']);
+
+                entries.push(match (is_list_item, seen_chapter) {
+                    (true, _) => {
+                        seen_chapter = true;
+                        BookEntry::Chapter(path)
+                    }
+                    (false, false) => BookEntry::FrontMatter(path),
+                    (false, true) => BookEntry::Appendix(path),
+                });
+            } else if let Some(caps) = SUMMARY_PART_REGEX.captures(line.trim()) {
+                let title = caps[1].trim();
+                // The conventional "# Summary" title is not a part
+                if !title.eq_ignore_ascii_case("summary") {
+                    entries.push(BookEntry::Part(title.to_string()));
+                }
+            }
+        }
+
+        Self { title: None, root, entries }
+    }
+
+    /// Every Markdown file in reading order
+    pub fn chapter_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.entries.iter().filter_map(|entry| match entry {
+            BookEntry::FrontMatter(path) | BookEntry::Chapter(path) | BookEntry::Appendix(path) => Some(path),
+            BookEntry::Part(_) => None,
+        })
+    }
+
+    /// Concatenate the book as Markdown, for backends that work on source text
+    pub fn assemble_markdown(&self) -> Result<String> {
+        let mut markdown = String::new();
+        let mut part_number = 0;
+        let mut chapter_index = 0;
+
+        for entry in &self.entries {
+            match entry {
+                BookEntry::Part(title) => {
+                    part_number += 1;
+                    markdown.push_str(&format!("# Part {}: {}\n\n", to_roman(part_number), title));
+                }
+                BookEntry::FrontMatter(path) | BookEntry::Chapter(path) | BookEntry::Appendix(path) => {
+                    chapter_index += 1;
+                    let content = prefix_footnote_labels(&Self::read_chapter(path)?, chapter_index);
+                    let content = rebase_image_paths(&content, path, &self.root);
+                    markdown.push_str(content.trim_end());
+                    markdown.push_str("\n\n");
+                }
+            }
+        }
+
+        Ok(markdown)
+    }
+
+    /// Read a chapter's Markdown without its front matter. Layout overrides
+    /// cannot change part-way through a book, so they are ignored.
+    pub fn read_chapter(path: &Path) -> Result<String> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read chapter: {}", path.display()))?;
+        let (front_matter, body) = FrontMatter::extract(&content)
+            .with_context(|| format!("Failed to read chapter: {}", path.display()))?;
+
+        if front_matter.has_config_overrides() {
+            Logger::warning(format!(
+                "Ignoring config overrides in front matter of chapter {}; set them in the config file instead",
+                path.display()
+            ));
+        }
+
+        Ok(body.to_string())
+    }
+
+    /// Wrap per-chapter HTML fragments (in `chapter_paths` order) into book
+    /// sections with unique heading and footnote ids, and rewrite links
+    /// between chapter files to in-document anchors
+    pub fn assemble_html(&self, chapters: Vec<String>, toc_enabled: bool) -> String {
+        // Respect a [TOC] marker the author already placed in a chapter
+        let mut toc_placed = !toc_enabled || chapters.iter().any(|c| c.contains("[TOC]"));
+
+        // Make ids unique across the whole book first, so links into a later
+        // chapter can follow that chapter's renamed headings
+        let mut seen_ids = HashSet::new();
+        let mut chapters: Vec<(String, HashMap<String, String>)> = chapters.iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let body = prefix_footnote_ids(body, i + 1);
+                dedupe_heading_ids(&body, &mut seen_ids)
+            })
+            .collect();
+
+        let targets: HashMap<PathBuf, ChapterTarget> = self.chapter_paths()
+            .zip(chapters.iter())
+            .enumerate()
+            .map(|(i, (path, (_, renamed)))| (normalize(path), ChapterTarget {
+                section_id: format!("chapter-{}", i + 1),
+                renamed: renamed.clone(),
+            }))
+            .collect();
+
+        let mut html = String::new();
+        let mut part_number = 0;
+        let mut appendix_number = 0;
+        let mut chapter_index = 0;
+
+        for entry in &self.entries {
+            // The combined TOC follows the front matter
+            if !toc_placed && !matches!(entry, BookEntry::FrontMatter(_)) {
+                html.push_str("<p>[TOC]</p>\n");
+                toc_placed = true;
+            }
+
+            match entry {
+                BookEntry::Part(title) => {
+                    part_number += 1;
+                    html.push_str(&format!(
+                        r#"<section class="book-part" id="part-{part_number}"><h1 id="part-{part_number}-title" class="part-title">Part {}: {}</h1></section>"#,
+                        to_roman(part_number),
+                        html_escape::encode_text(title)
+                    ));
+                    html.push('\n');
+                }
+                BookEntry::FrontMatter(path) | BookEntry::Chapter(path) | BookEntry::Appendix(path) => {
+                    let body = std::mem::take(&mut chapters[chapter_index].0);
+                    chapter_index += 1;
+                    let body = rewrite_chapter_links(&body, path, &targets);
+
+                    let (class, extra) = match entry {
+                        BookEntry::FrontMatter(_) => ("book-front-matter", String::new()),
+                        BookEntry::Appendix(_) => {
+                            appendix_number += 1;
+                            ("book-appendix", format!(r#" data-appendix="{}""#, appendix_letter(appendix_number)))
+                        }
+                        _ => ("book-chapter", String::new()),
+                    };
+
+                    html.push_str(&format!(
+                        "<section class=\"{class}\" id=\"chapter-{chapter_index}\"{extra}>\n{body}\n</section>\n"
+                    ));
+                }
+            }
+        }
+
+        // Books consisting only of front matter still get their TOC
+        if !toc_placed {
+            html.push_str("<p>[TOC]</p>\n");
+        }
+
+        html
+    }
+}
+
+/// Where links to a chapter file should point once the book is merged
+struct ChapterTarget {
+    section_id: String,
+    renamed: HashMap<String, String>,
+}
+
+/// comrak numbers footnotes per document, so scope their ids to the chapter
+fn prefix_footnote_ids(html: &str, chapter: usize) -> String {
+    html.replace("href=\"#fn-", &format!("href=\"#ch{chapter}-fn-"))
+        .replace("id=\"fn-", &format!("id=\"ch{chapter}-fn-"))
+        .replace("href=\"#fnref-", &format!("href=\"#ch{chapter}-fnref-"))
+        .replace("id=\"fnref-", &format!("id=\"ch{chapter}-fnref-"))
+}
+
+/// Footnote labels are only unique within a chapter, so `[^1]` in two
+/// chapters would merge into one note once the Markdown is concatenated
+fn prefix_footnote_labels(markdown: &str, chapter: usize) -> String {
+    map_outside_code_fences(markdown, |line| {
+        FOOTNOTE_LABEL_REGEX.replace_all(line, |caps: &regex::Captures| {
+            format!("[^ch{chapter}-{}]", &caps[1])
+        }).into_owned()
+    })
+}
+
+/// Images are written relative to their chapter, but the assembled book is
+/// resolved against the manifest root, so point them there instead
+fn rebase_image_paths(markdown: &str, chapter: &Path, root: &Path) -> String {
+    let chapter_dir = chapter.parent().unwrap_or(Path::new("."));
+    let Ok(relative_dir) = chapter_dir.strip_prefix(root) else {
+        return markdown.to_string();
+    };
+    if relative_dir.as_os_str().is_empty() {
+        return markdown.to_string();
+    }
+
+    map_outside_code_fences(markdown, |line| {
+        MD_IMAGE_REGEX.replace_all(line, |caps: &regex::Captures| {
+            let target = &caps[2];
+            if target.contains(':') || target.starts_with('/') || target.starts_with('#') {
+                return caps[0].to_string();
+            }
+            format!("{}{}", &caps[1], relative_dir.join(target).display())
+        }).into_owned()
+    })
+}
+
+/// Apply `rewrite` to every line that is not inside a fenced code block
+fn map_outside_code_fences(markdown: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    let mut in_fence = false;
+
+    markdown.lines()
+        .map(|line| {
+            if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+                in_fence = !in_fence;
+            }
+            if in_fence {
+                line.to_string()
+            } else {
+                rewrite(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Heading ids are generated per chapter, so identical headings in different
+/// chapters would collide; later ones get a numeric suffix like comrak uses.
+/// Returns the rewritten HTML and a map of original to renamed ids.
+fn dedupe_heading_ids(html: &str, seen: &mut HashSet<String>) -> (String, HashMap<String, String>) {
+    let mut renamed = HashMap::new();
+    let html = HEADING_ID_REGEX.replace_all(html, |caps: &regex::Captures| {
+        let id = caps[1].to_string();
+        let mut unique = id.clone();
+        let mut suffix = 1;
+        while seen.contains(&unique) {
+            unique = format!("{id}-{suffix}");
+            suffix += 1;
+        }
+        seen.insert(unique.clone());
+        if unique != id {
+            renamed.insert(id, unique.clone());
+        }
+        format!("id=\"{unique}\"")
+    }).into_owned();
+
+    // Keep the chapter's own anchors pointing at the renamed headings. One
+    // pass, since a new id can be another heading's old one (intro → intro-1
+    // while intro-1 → intro-1-1).
+    let html = FRAGMENT_LINK_REGEX.replace_all(&html, |caps: &regex::Captures| {
+        match renamed.get(&format!("user-content-{}", &caps[1])) {
+            Some(new) => format!("href=\"#{}\"", new.trim_start_matches("user-content-")),
+            None => caps[0].to_string(),
+        }
+    }).into_owned();
+
+    (html, renamed)
+}
+
+/// Turn `other-chapter.md#heading` links into anchors within the book
+fn rewrite_chapter_links(html: &str, chapter: &Path, targets: &HashMap<PathBuf, ChapterTarget>) -> String {
+    let chapter_dir = chapter.parent().unwrap_or(Path::new("."));
+
+    MD_LINK_REGEX.replace_all(html, |caps: &regex::Captures| {
+        let Some(target) = targets.get(&normalize(&chapter_dir.join(&caps[1]))) else {
+            return caps[0].to_string();
+        };
+
+        match caps.get(2) {
+            Some(fragment) => {
+                let id = format!("user-content-{}", &fragment.as_str()[1..]);
+                let id = target.renamed.get(&id).cloned().unwrap_or(id);
+                format!("href=\"#{id}\"")
+            }
+            None => format!("href=\"#{}\"", target.section_id),
+        }
+    }).into_owned()
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn to_roman(mut num: u32) -> String {
+    let values = [(1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+                  (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I")];
+    let mut result = String::new();
+    for (value, numeral) in values {
+        while num >= value {
+            result.push_str(numeral);
+            num -= value;
+        }
+    }
+    result
+}
+
+fn appendix_letter(num: u32) -> char {
+    (b'A' + ((num - 1) % 26) as u8) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary_sections() {
+        let summary = "# Summary\n\n[Preface](preface.md)\n\n# Basics\n\n- [Intro](intro.md)\n    - [Setup](setup.md)\n\n[Glossary](glossary.md)\n";
+        let manifest = BookManifest::parse_summary(summary, PathBuf::from("docs"));
+
+        assert_eq!(manifest.entries, vec![
+            BookEntry::FrontMatter(PathBuf::from("docs/preface.md")),
+            BookEntry::Part("Basics".to_string()),
+            BookEntry::Chapter(PathBuf::from("docs/intro.md")),
+            BookEntry::Chapter(PathBuf::from("docs/setup.md")),
+            BookEntry::Appendix(PathBuf::from("docs/glossary.md")),
+        ]);
+    }
+
+    #[test]
+    fn test_duplicate_heading_ids_are_suffixed() {
+        let mut seen = HashSet::new();
+        let (first, _) = dedupe_heading_ids(r##"<a href="#setup" id="user-content-setup"></a>"##, &mut seen);
+        let (second, renamed) = dedupe_heading_ids(r##"<a href="#setup" id="user-content-setup"></a>"##, &mut seen);
+
+        assert_eq!(first, r##"<a href="#setup" id="user-content-setup"></a>"##);
+        assert_eq!(second, r##"<a href="#setup-1" id="user-content-setup-1"></a>"##);
+        assert_eq!(renamed.get("user-content-setup").map(String::as_str), Some("user-content-setup-1"));
+    }
+
+    #[test]
+    fn test_renamed_heading_links_are_rewritten_once() {
+        let heading = |slug: &str| format!(r##"<a href="#{slug}" id="user-content-{slug}"></a>"##);
+        let mut seen = HashSet::new();
+        dedupe_heading_ids(&heading("intro"), &mut seen);
+        dedupe_heading_ids(&heading("intro"), &mut seen);
+
+        // "Intro" becomes intro-2, which the chapter's own "Intro 2" heading already uses
+        let (third, renamed) = dedupe_heading_ids(&format!("{}{}", heading("intro"), heading("intro-2")), &mut seen);
+        assert_eq!(third, r##"<a href="#intro-2" id="user-content-intro-2"></a><a href="#intro-2-1" id="user-content-intro-2-1"></a>"##);
+        assert_eq!(renamed.len(), 2);
+    }
+
+    #[test]
+    fn test_footnote_labels_are_scoped_to_chapter() {
+        let chapter = "Claim.[^1]\n\n```\nlet x = a[^1];\n```\n\n[^1]: Source.";
+
+        assert_eq!(
+            prefix_footnote_labels(chapter, 2),
+            "Claim.[^ch2-1]\n\n```\nlet x = a[^1];\n```\n\n[^ch2-1]: Source."
+        );
+    }
+
+    #[test]
+    fn test_image_paths_are_rebased_to_book_root() {
+        let chapter = "![Chart](img/chart.png \"Sales\")\n![Logo](https://example.com/logo.png)";
+
+        assert_eq!(
+            rebase_image_paths(chapter, Path::new("book/part1/intro.md"), Path::new("book")),
+            "![Chart](part1/img/chart.png \"Sales\")\n![Logo](https://example.com/logo.png)"
+        );
+    }
+}
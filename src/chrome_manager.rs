@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
+use headless_chrome::{Browser, LaunchOptions};
+use parking_lot::{Condvar, Mutex};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 use zip::ZipArchive;
 
 const CHROME_FOR_TESTING_VERSION: &str = "131.0.6778.108"; // Latest stable with headless shell support
@@ -18,9 +21,13 @@ const CHROME_EXECUTABLE: &str = "chrome-headless-shell.exe";
 #[cfg(not(target_os = "windows"))]
 const CHROME_EXECUTABLE: &str = "chrome-headless-shell";
 
+pub const DEFAULT_RECYCLE_AFTER: usize = 50; // Documents rendered before a browser is replaced
+const BROWSER_IDLE_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60); // Pooled browsers outlive watch-mode pauses
+
 pub struct ChromeManager {
     chrome_dir: PathBuf,
     chrome_path: PathBuf,
+    pool: BrowserPool,
 }
 
 impl ChromeManager {
@@ -31,13 +38,44 @@ impl ChromeManager {
         Ok(Self {
             chrome_dir,
             chrome_path,
+            pool: BrowserPool::new(1, DEFAULT_RECYCLE_AFTER),
         })
     }
 
+    /// Size the browser pool, typically from `--jobs`, and set how many
+    /// documents a browser renders before it is recycled
+    pub fn with_pool(mut self, size: usize, recycle_after: usize) -> Self {
+        self.pool = BrowserPool::new(size, recycle_after);
+        self
+    }
+
+    /// Borrow a healthy browser from the pool, launching one if needed.
+    /// The browser is returned to the pool when the lease is dropped.
+    pub fn acquire_browser(&self) -> Result<BrowserLease<'_>> {
+        self.pool.acquire(|| self.launch_browser())
+    }
+
+    fn launch_browser(&self) -> Result<Browser> {
+        // Ensure Chrome is available (download if necessary)
+        let chrome_path = self.ensure_chrome()
+            .context("Failed to ensure Chrome availability")?;
+
+        let launch_options = LaunchOptions::default_builder()
+            .path(Some(chrome_path))
+            .headless(true)
+            .idle_browser_timeout(BROWSER_IDLE_TIMEOUT)
+            .build()
+            .context("Failed to build Chrome launch options")?;
+
+        crate::logger::Logger::verbose("🚀 Launching headless Chrome");
+        Browser::new(launch_options)
+            .context("Failed to launch headless Chrome")
+    }
+
     /// Get the directory where Chrome should be stored
     fn get_chrome_dir() -> Result<PathBuf> {
         let app_dir = dirs::data_local_dir()
-            .or_else(|| dirs::data_dir())
+            .or_else(dirs::data_dir)
             .context("Failed to get application data directory")?;
         
         Ok(app_dir.join("papercraft").join("chrome"))
@@ -58,6 +96,7 @@ impl ChromeManager {
     }
 
     /// Get the path to the Chrome executable
+    #[allow(dead_code)]
     pub fn get_chrome_path(&self) -> &Path {
         &self.chrome_path
     }
@@ -213,9 +252,139 @@ impl ChromeManager {
     }
 }
 
+/// What the pool needs from a browser, so tests can pool stand-ins
+pub trait PoolBrowser {
+    fn is_healthy(&self) -> bool;
+}
+
+impl PoolBrowser for Browser {
+    fn is_healthy(&self) -> bool {
+        self.get_version().is_ok()
+    }
+}
+
+/// A fixed-size set of headless browsers shared across conversions.
+/// Browsers are launched lazily, health-checked before reuse, relaunched
+/// after a crash and recycled after `recycle_after` documents to bound memory.
+struct BrowserPool<B = Browser> {
+    size: usize,
+    recycle_after: usize,
+    state: Mutex<PoolState<B>>,
+    available: Condvar,
+}
+
+struct PoolState<B> {
+    idle: Vec<PooledBrowser<B>>,
+    live: usize, // Browsers currently launched, idle or leased
+}
+
+struct PooledBrowser<B> {
+    browser: B,
+    documents: usize, // Completed conversions
+}
+
+impl<B: PoolBrowser> BrowserPool<B> {
+    fn new(size: usize, recycle_after: usize) -> Self {
+        Self {
+            size: size.max(1),
+            recycle_after: recycle_after.max(1),
+            state: Mutex::new(PoolState { idle: Vec::new(), live: 0 }),
+            available: Condvar::new(),
+        }
+    }
+
+    fn acquire<F>(&self, launch: F) -> Result<BrowserLease<'_, B>>
+    where
+        F: Fn() -> Result<B>,
+    {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(pooled) = state.idle.pop() {
+                drop(state);
+                if pooled.browser.is_healthy() {
+                    return Ok(BrowserLease { pool: self, pooled: Some(pooled), completed: false });
+                }
+
+                // The browser crashed or its connection dropped; replace it
+                crate::logger::Logger::warning("Headless Chrome became unresponsive, relaunching");
+                drop(pooled);
+                state = self.state.lock();
+                state.live -= 1;
+                continue;
+            }
+
+            if state.live < self.size {
+                state.live += 1;
+                drop(state);
+                return match launch() {
+                    Ok(browser) => Ok(BrowserLease {
+                        pool: self,
+                        pooled: Some(PooledBrowser { browser, documents: 0 }),
+                        completed: false,
+                    }),
+                    Err(e) => {
+                        self.state.lock().live -= 1;
+                        self.available.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+
+            self.available.wait(&mut state);
+        }
+    }
+
+    /// Return a browser; only `completed` conversions count towards recycling
+    fn release(&self, mut pooled: PooledBrowser<B>, completed: bool) {
+        if completed {
+            pooled.documents += 1;
+        }
+        let keep = pooled.documents < self.recycle_after && pooled.browser.is_healthy();
+
+        let mut state = self.state.lock();
+        if keep {
+            state.idle.push(pooled);
+        } else {
+            // Dropping the browser terminates the Chrome process
+            state.live -= 1;
+            drop(state);
+            drop(pooled);
+        }
+        self.available.notify_one();
+    }
+}
+
+/// Exclusive use of a pooled browser for the duration of one conversion
+pub struct BrowserLease<'a, B: PoolBrowser = Browser> {
+    pool: &'a BrowserPool<B>,
+    pooled: Option<PooledBrowser<B>>,
+    completed: bool,
+}
+
+impl<B: PoolBrowser> BrowserLease<'_, B> {
+    pub fn browser(&self) -> &B {
+        &self.pooled.as_ref().expect("browser lease already released").browser
+    }
+
+    /// Count this conversion towards the browser's `recycle_after` limit
+    pub fn complete(&mut self) {
+        self.completed = true;
+    }
+}
+
+impl<B: PoolBrowser> Drop for BrowserLease<'_, B> {
+    fn drop(&mut self) {
+        if let Some(pooled) = self.pooled.take() {
+            self.pool.release(pooled, self.completed);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     #[test]
     fn test_chrome_manager_creation() {
@@ -230,4 +399,49 @@ mod tests {
         assert!(url.contains("chrome-for-testing-public"));
         assert!(url.contains(CHROME_FOR_TESTING_VERSION));
     }
+
+    struct FakeBrowser {
+        id: usize,
+        healthy: Arc<AtomicBool>,
+    }
+
+    impl PoolBrowser for FakeBrowser {
+        fn is_healthy(&self) -> bool {
+            self.healthy.load(Ordering::SeqCst)
+        }
+    }
+
+    fn launcher(launched: &AtomicUsize) -> impl Fn() -> Result<FakeBrowser> + Copy + '_ {
+        move || Ok(FakeBrowser { id: launched.fetch_add(1, Ordering::SeqCst), healthy: Arc::new(AtomicBool::new(true)) })
+    }
+
+    #[test]
+    fn test_pool_recycles_after_completed_documents() {
+        let launched = AtomicUsize::new(0);
+        let launch = launcher(&launched);
+        let pool = BrowserPool::new(1, 2);
+
+        // A failed conversion does not count towards recycling
+        drop(pool.acquire(launch).unwrap());
+        for _ in 0..2 {
+            let mut lease = pool.acquire(launch).unwrap();
+            assert_eq!(lease.browser().id, 0);
+            lease.complete();
+        }
+        assert_eq!(pool.acquire(launch).unwrap().browser().id, 1);
+    }
+
+    #[test]
+    fn test_pool_replaces_unhealthy_browser() {
+        let launched = AtomicUsize::new(0);
+        let launch = launcher(&launched);
+        let pool = BrowserPool::new(1, 10);
+
+        let healthy = pool.acquire(launch).unwrap().browser().healthy.clone();
+        healthy.store(false, Ordering::SeqCst);
+
+        let lease = pool.acquire(launch).unwrap();
+        assert_eq!(lease.browser().id, 1);
+        assert_eq!(pool.state.lock().live, 1);
+    }
 }
\ No newline at end of file
@@ -1,9 +1,16 @@
 use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+lazy_static! {
+    // `{chapter}` or a Tera `{{ chapter }}`, with or without spaces and trim markers
+    static ref CHAPTER_PLACEHOLDER_REGEX: Regex = Regex::new(r"\{\{-?\s*chapter\b|\{chapter\}").expect("Invalid chapter placeholder regex");
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub output: OutputConfig,
@@ -14,6 +21,18 @@ pub struct Config {
     pub images: ImageConfig,
     pub code: CodeConfig,
     pub references: ReferenceConfig,
+    #[serde(default)]
+    pub assets: AssetConfig,
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+    #[serde(default)]
+    pub docx: DocxConfig,
+    #[serde(default)]
+    pub latex: LatexConfig,
+    #[serde(default)]
+    pub templates: TemplateConfig,
+    #[serde(default)]
+    pub cover: CoverConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +40,17 @@ pub struct OutputConfig {
     pub format: String,
     pub quality: Option<f64>,
     pub compression: Option<bool>,
+    #[serde(default)]
+    pub engine: PdfEngine,
+}
+
+/// Renderer for PDF output: headless Chrome, or printpdf without a browser
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfEngine {
+    #[default]
+    Chrome,
+    Native,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,12 +85,93 @@ pub enum Orientation {
     Landscape,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HeaderFooterConfig {
     pub enabled: bool,
+    #[serde(default)]
     pub template: String,
     pub height: Option<String>,
     pub font_size: Option<String>,
+    // Left/center/right text for every page, instead of `template`
+    #[serde(flatten)]
+    pub slots: HeaderFooterSlots,
+    #[serde(default)]
+    pub first: Option<HeaderFooterSlots>, // First page
+    #[serde(default)]
+    pub even: Option<HeaderFooterSlots>,  // Left-hand pages
+    #[serde(default)]
+    pub odd: Option<HeaderFooterSlots>,   // Right-hand pages
+    #[serde(default)]
+    pub show_on_cover: bool,
+    #[serde(default)]
+    pub skip_chapter_openings: bool, // Leave pages where a top-level heading starts bare
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderFooterSlots {
+    pub left: String,
+    pub center: String,
+    pub right: String,
+}
+
+impl PageConfig {
+    /// The enabled header or footer, otherwise the page number format as its
+    /// centre slot when page numbers are placed there
+    pub fn header_footer(&self, position: PageNumberPosition) -> Option<HeaderFooterConfig> {
+        let configured = match position {
+            PageNumberPosition::Header => &self.header,
+            PageNumberPosition::Footer => &self.footer,
+        };
+        if let Some(configured) = configured.as_ref().filter(|configured| configured.enabled) {
+            return Some(configured.clone());
+        }
+
+        let page_numbers = self.page_numbers.as_ref()
+            .filter(|page_numbers| page_numbers.enabled && page_numbers.position == position)?;
+        Some(HeaderFooterConfig {
+            enabled: true,
+            template: page_numbers.format.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+impl HeaderFooterSlots {
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty() && self.center.is_empty() && self.right.is_empty()
+    }
+}
+
+impl HeaderFooterConfig {
+    /// Whether any page differs from the rest, which Chrome's single
+    /// header/footer template cannot express
+    pub fn varies_by_page(&self) -> bool {
+        !self.slots.is_empty()
+            || self.first.is_some()
+            || self.even.is_some()
+            || self.odd.is_some()
+            || self.skip_chapter_openings
+            || CHAPTER_PLACEHOLDER_REGEX.is_match(&self.template)
+    }
+
+    /// Slots for a 1-based page: the first-page set, then the even/odd set,
+    /// then the defaults, with `template` as the centre slot
+    pub fn slots_for_page(&self, page: u32) -> HeaderFooterSlots {
+        let sided = if page.is_multiple_of(2) { &self.even } else { &self.odd };
+        let specific = if page == 1 { self.first.as_ref().or(sided.as_ref()) } else { sided.as_ref() };
+
+        specific.cloned().unwrap_or_else(|| self.default_slots())
+    }
+
+    /// Slots for pages without a specific set, with `template` as the centre slot
+    pub fn default_slots(&self) -> HeaderFooterSlots {
+        if self.slots.is_empty() {
+            HeaderFooterSlots { center: self.template.clone(), ..Default::default() }
+        } else {
+            self.slots.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,7 +182,7 @@ pub struct PageNumberConfig {
     pub start_from: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PageNumberPosition {
     Header,
@@ -85,7 +196,7 @@ pub struct ThemeConfig {
     pub custom_styles: Option<HashMap<String, String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FontConfig {
     pub family: Option<String>,
     pub size: Option<String>,
@@ -142,6 +253,13 @@ pub struct ReferenceConfig {
     pub footnotes: FootnoteConfig,
     pub bibliography: BibliographyConfig,
     pub cross_references: bool,
+    #[serde(default)]
+    pub numbering: NumberingConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NumberingConfig {
+    pub per_chapter: bool, // Number figures, tables and equations as "2.3" within each top-level heading
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -174,6 +292,8 @@ pub struct BibliographyConfig {
     pub style: String, // "apa", "mla", "chicago", "ieee"
     pub title: String,
     pub sort_by: BibliographySort,
+    #[serde(default)]
+    pub file: Option<PathBuf>, // BibTeX (.bib) or CSL-JSON (.json) source
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -185,6 +305,115 @@ pub enum BibliographySort {
     Citation,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocxConfig {
+    pub reference_doc: Option<PathBuf>, // .docx/.dotx whose styles replace the generated ones
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatexConfig {
+    pub template: Option<PathBuf>, // Tera preamble template; defaults to the theme's built-in one
+}
+
+/// Tera templates replacing the built-in HTML shell, header and footer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+    pub shell: Option<PathBuf>,      // Whole HTML document around the body
+    pub title_page: Option<PathBuf>, // Rendered before the body when set
+    pub header: Option<PathBuf>,     // PDF header wrapper
+    pub footer: Option<PathBuf>,     // PDF footer wrapper
+}
+
+/// Generated cover page; title, authors and date come from the metadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoverConfig {
+    pub enabled: bool,
+    pub layout: Option<String>, // "classic", "modern", "academic"; defaults to one matching the theme
+    pub subtitle: Option<String>,
+    pub organization: Option<String>,
+    pub logo: Option<PathBuf>,
+    pub version: Option<String>,
+    pub confidentiality: Option<String>, // e.g. "Confidential", "Internal use only"
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetConfig {
+    pub mode: AssetMode,
+    pub path: Option<PathBuf>, // Local asset directory for `mode = "path"`
+    pub strict: bool,          // Fail the conversion when KaTeX/Mermaid cannot be loaded
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetMode {
+    Cdn,
+    Embedded,
+    Path,
+}
+
+/// Descriptive metadata written to the HTML head and the PDF Info dictionary
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    #[serde(alias = "author", deserialize_with = "one_or_many")]
+    pub authors: Vec<String>, // `author = "..."` is accepted for a single author
+    pub date: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    pub creator: Option<String>, // Application named as the PDF creator (default: PaperCraft)
+}
+
+impl DocumentMetadata {
+    /// Fill fields that are still unset from `other`
+    pub fn or(mut self, other: DocumentMetadata) -> Self {
+        self.title = self.title.or(other.title);
+        if self.authors.is_empty() {
+            self.authors = other.authors;
+        }
+        self.date = self.date.or(other.date);
+        self.subject = self.subject.or(other.subject);
+        if self.keywords.is_empty() {
+            self.keywords = other.keywords;
+        }
+        self.creator = self.creator.or(other.creator);
+        self
+    }
+
+    /// Authors joined with commas, for fields that hold a single string
+    pub fn author(&self) -> Option<String> {
+        (!self.authors.is_empty()).then(|| self.authors.join(", "))
+    }
+}
+
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            mode: AssetMode::Cdn,
+            path: None,
+            strict: false,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -192,6 +421,7 @@ impl Default for Config {
                 format: "pdf".to_string(),
                 quality: Some(1.0),
                 compression: Some(true),
+                engine: PdfEngine::Chrome,
             },
             page: PageConfig {
                 size: PageSize {
@@ -259,9 +489,17 @@ impl Default for Config {
                     style: "apa".to_string(),
                     title: "References".to_string(),
                     sort_by: BibliographySort::Author,
+                    file: None,
                 },
                 cross_references: true,
+                numbering: NumberingConfig::default(),
             },
+            assets: AssetConfig::default(),
+            metadata: DocumentMetadata::default(),
+            docx: DocxConfig::default(),
+            latex: LatexConfig::default(),
+            templates: TemplateConfig::default(),
+            cover: CoverConfig::default(),
         }
     }
 }
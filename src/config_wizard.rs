@@ -58,7 +58,7 @@ impl ConfigWizard {
             if let Ok(num) = choice.parse::<usize>() {
                 if num >= 1 && num <= themes.len() {
                     config.theme.built_in = Some(themes[num - 1].clone());
-                    Logger::success(&format!("Selected theme: {}", themes[num - 1]));
+                    Logger::success(format!("Selected theme: {}", themes[num - 1]));
                     break;
                 } else if num == themes.len() + 1 {
                     let css_path = self.prompt_input("Enter path to custom CSS file:")?;
@@ -98,7 +98,7 @@ impl ConfigWizard {
             if let Ok(num) = choice.parse::<usize>() {
                 if num >= 1 && num <= sizes.len() {
                     config.page.size.preset = Some(sizes[num - 1].to_string());
-                    Logger::success(&format!("Selected paper size: {}", sizes[num - 1]));
+                    Logger::success(format!("Selected paper size: {}", sizes[num - 1]));
                     break;
                 } else {
                     Logger::warning("Invalid choice. Please try again.");
@@ -134,13 +134,13 @@ impl ConfigWizard {
         let font_family = self.prompt_input("Enter font family [default: system default]:")?;
         if !font_family.is_empty() {
             config.fonts.family = Some(font_family.clone());
-            Logger::success(&format!("Set font family: {}", font_family));
+            Logger::success(format!("Set font family: {}", font_family));
         }
         
         let font_size = self.prompt_input("Enter font size (e.g., '12pt', '14px') [default: system default]:")?;
         if !font_size.is_empty() {
             config.fonts.size = Some(font_size.clone());
-            Logger::success(&format!("Set font size: {}", font_size));
+            Logger::success(format!("Set font size: {}", font_size));
         }
         
         println!();
@@ -197,6 +197,7 @@ impl ConfigWizard {
                     template: header,
                     height: Some("1cm".to_string()),
                     font_size: Some("10px".to_string()),
+                    ..Default::default()
                 });
                 Logger::success("Custom header configured");
             }
@@ -210,6 +211,7 @@ impl ConfigWizard {
                     template: footer,
                     height: Some("1cm".to_string()),
                     font_size: Some("10px".to_string()),
+                    ..Default::default()
                 });
                 Logger::success("Custom footer configured");
             }
@@ -272,11 +274,11 @@ impl ConfigWizard {
             config.save_to_file(&config_path)
                 .with_context(|| format!("Failed to save configuration to {}", config_path.display()))?;
             
-            Logger::success(&format!("Configuration saved to: {}", config_path.display()));
+            Logger::success(format!("Configuration saved to: {}", config_path.display()));
             
             // Offer to create a sample document
             if self.prompt_yes_no("Create a sample markdown document to test? [Y/n]:", true)? {
-                self.create_sample_document(&config_path.parent().unwrap_or(&PathBuf::from(".")))?;
+                self.create_sample_document(config_path.parent().unwrap_or(&PathBuf::from(".")))?;
             }
             
             Ok(config_path)
@@ -304,7 +306,7 @@ impl ConfigWizard {
         
         println!("📚 Table of Contents: {}", if config.toc.enabled { "✓" } else { "✗" });
         println!("📝 Page Numbers: {}", 
-            if config.page.page_numbers.as_ref().map_or(false, |p| p.enabled) { "✓" } else { "✗" });
+            if config.page.page_numbers.as_ref().is_some_and(|p| p.enabled) { "✓" } else { "✗" });
         println!("💻 Code Line Numbers: {}", if config.code.line_numbers { "✓" } else { "✗" });
         println!("🖼️  Image Optimization: {}", if config.images.optimization { "✓" } else { "✗" });
     }
@@ -354,7 +356,7 @@ papercraft -i sample-document.md -o sample-document.pdf -c your-config.toml
         std::fs::write(&sample_path, sample_content)
             .with_context(|| format!("Failed to create sample document: {}", sample_path.display()))?;
         
-        Logger::success(&format!("Sample document created: {}", sample_path.display()));
+        Logger::success(format!("Sample document created: {}", sample_path.display()));
         Ok(())
     }
 }
@@ -373,11 +375,11 @@ pub fn run_first_time_setup() -> Result<()> {
         
         Logger::info("");
         Logger::success("🎉 Setup complete!");
-        Logger::info(&format!("Your configuration is saved at: {}", config_path.display()));
+        Logger::info(format!("Your configuration is saved at: {}", config_path.display()));
         Logger::info("You can now convert markdown files to PDF using PaperCraft.");
         Logger::info("");
         Logger::info("Quick start:");
-        Logger::info(&format!("  papercraft -i input.md -o output.pdf -c {}", config_path.display()));
+        Logger::info(format!("  papercraft -i input.md -o output.pdf -c {}", config_path.display()));
         Logger::info("");
         Logger::info("For more options, run: papercraft --help");
     } else {
@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::backends::{Backend, BackendRegistry};
+use crate::config::{Config, DocumentMetadata};
+use crate::error_handler::{self, PapercraftError};
+use crate::html_converter::HtmlToPdfConverter;
+use crate::logger::Logger;
+
+/// Output formats built into PaperCraft
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pdf,
+    Docx,
+    Html,
+    Epub,
+    Latex,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [Format::Pdf, Format::Docx, Format::Html, Format::Epub, Format::Latex];
+
+    /// The name `--format` accepts for this format
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Pdf => "pdf",
+            Format::Docx => "docx",
+            Format::Html => "html",
+            Format::Epub => "epub",
+            Format::Latex => "latex",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = PapercraftError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter()
+            .find(|candidate| candidate.name().eq_ignore_ascii_case(format))
+            .ok_or_else(|| PapercraftError::UnknownFormat {
+                format: format.to_string(),
+                available: Self::ALL.map(Format::name).join(", "),
+            })
+    }
+}
+
+/// Converts Markdown to one output format. PDF output uses the engine set in
+/// `config.output.engine`; Chrome is only launched when a document needs it.
+pub struct Converter {
+    config: Config,
+    format: Format,
+    metadata: DocumentMetadata,
+    base_path: PathBuf,
+    html_converter: HtmlToPdfConverter,
+}
+
+impl Converter {
+    pub fn new(config: Config) -> Result<Self, PapercraftError> {
+        // Stdout belongs to the host program; progress and warnings go to stderr
+        Logger::redirect_to_stderr();
+
+        let html_converter = HtmlToPdfConverter::new()
+            .map_err(|e| PapercraftError::BrowserInitFailed { reason: format!("{e:#}") })?;
+
+        Ok(Self {
+            config,
+            format: Format::Pdf,
+            metadata: DocumentMetadata::default(),
+            base_path: PathBuf::from("."),
+            html_converter,
+        })
+    }
+
+    /// Output format, PDF by default
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Title, author and keywords that take precedence over front matter
+    pub fn with_metadata(mut self, metadata: DocumentMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Directory that relative image paths in `convert_str` input are resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
+    }
+
+    /// Convert a Markdown document, front matter included, to the bytes of the output file
+    pub fn convert_str(&self, markdown: &str) -> Result<Vec<u8>, PapercraftError> {
+        self.with_backend(|backend| backend.convert_str(markdown, &self.base_path, &self.config))
+            .map_err(|e| library_error(e, |reason| PapercraftError::RenderFailed { reason }))
+    }
+
+    /// Convert a Markdown file, resolving its images relative to the file
+    pub fn convert_file(&self, input: &Path, output: &Path) -> Result<(), PapercraftError> {
+        error_handler::validate_input_file(input)?;
+
+        self.with_backend(|backend| backend.convert(input, output, &self.config))
+            .map_err(|e| library_error(e, |reason| PapercraftError::ConversionFailed {
+                input: input.to_path_buf(),
+                output: output.to_path_buf(),
+                reason,
+            }))
+    }
+
+    fn with_backend<T>(&self, convert: impl FnOnce(&dyn Backend) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let registry = BackendRegistry::builtin(&self.html_converter, self.config.output.engine, self.metadata.clone());
+        convert(registry.get(self.format.name())?)
+    }
+}
+
+/// Surface the specific failure (front matter, bibliography, template,
+/// browser) raised somewhere in the chain, or fall back to `otherwise`
+fn library_error(error: anyhow::Error, otherwise: impl FnOnce(String) -> PapercraftError) -> PapercraftError {
+    let reason = format!("{error:#}");
+    error.downcast::<PapercraftError>().unwrap_or_else(|_| otherwise(reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_str_renders_each_format_in_memory() {
+        let markdown = "---\ntitle: Notes\n---\n# Release notes\n\nAll tests pass.\n";
+        let converter = |format| Converter::new(Config::default()).unwrap().with_format(format);
+
+        let html = String::from_utf8(converter(Format::Html).convert_str(markdown).unwrap()).unwrap();
+        assert!(html.contains("<title>Notes</title>"));
+        assert!(converter(Format::Docx).convert_str(markdown).unwrap().starts_with(b"PK"));
+        let latex = String::from_utf8(converter(Format::Latex).convert_str(markdown).unwrap()).unwrap();
+        assert!(latex.contains("\\section{Release notes}"));
+
+        assert!(matches!(
+            converter(Format::Docx).convert_str("+++\ntitle = [broken\n+++\nBody"),
+            Err(PapercraftError::FrontMatterError { .. })
+        ));
+        assert!(matches!(
+            converter(Format::Html).convert_str("---\nbibliography: missing.bib\n---\nSee [@cite:knuth84]."),
+            Err(PapercraftError::BibliographyError { .. })
+        ));
+        assert!(matches!("odt".parse::<Format>(), Err(PapercraftError::UnknownFormat { .. })));
+        assert_eq!("LaTeX".parse::<Format>().unwrap(), Format::Latex);
+    }
+}
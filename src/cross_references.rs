@@ -0,0 +1,247 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use crate::logger::Logger;
+
+lazy_static! {
+    // Everything that advances a counter, in document order. Captions and
+    // equations stop at their own closing tag, so an unlabeled element can't
+    // swallow the ones after it on the way to a later label.
+    static ref NUMBERED_REGEX: Regex = Regex::new(concat!(
+        r#"(?s)<h(?P<level>[1-6])(?P<attrs>[^>]*)>(?P<heading>.*?)</h[1-6]>"#,
+        r#"|<p>(?P<img><img [^>]*>)\s*\{#fig:(?P<fig>[\w-]+)\}</p>"#,
+        r#"|<p>Table:\s*(?P<table_caption>(?:[^<]|<[^/]|</[^p])*?)\s*\{#table:(?P<table>[\w-]+)\}</p>\s*<table>"#,
+        r#"|<div class="math-display">(?P<math>(?:[^<]|<[^/]|</[^d])*?)</div>\s*\{#eq:(?P<eq>[\w-]+)\}"#,
+    )).expect("Invalid numbered element regex");
+    static ref SECTION_LABEL_REGEX: Regex = Regex::new(r"\s*\{#sec:([\w-]+)\}\s*$").expect("Invalid section label regex");
+    static ref ALT_REGEX: Regex = Regex::new(r#"alt="([^"]*)""#).expect("Invalid alt regex");
+    static ref TAG_REGEX: Regex = Regex::new(r"<[^>]+>").expect("Invalid tag regex");
+    static ref XREF_REGEX: Regex = Regex::new(r"\[@ref:(\w+):([^\]]+)\]").expect("Invalid cross-reference regex");
+}
+
+/// A numbered element that references can point at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub number: String,
+    pub caption: String,
+}
+
+/// Assigns ids and sequential numbers to labeled figures, tables, equations
+/// and sections, then resolves `[@ref:type:label]` references to them.
+pub struct CrossReferencer {
+    per_chapter: bool,
+    labels: HashMap<String, Label>,
+}
+
+impl CrossReferencer {
+    pub fn new(per_chapter: bool) -> Self {
+        Self {
+            per_chapter,
+            labels: HashMap::new(),
+        }
+    }
+
+    pub fn process(&mut self, html: &str) -> String {
+        let numbered = self.number_elements(html);
+        self.resolve_references(&numbered)
+    }
+
+    fn number_elements(&mut self, html: &str) -> String {
+        let mut chapter = 0u32;
+        let mut figures = 0u32;
+        let mut tables = 0u32;
+        let mut equations = 0u32;
+        let mut sections = [0u32; 6];
+
+        NUMBERED_REGEX.replace_all(html, |caps: &regex::Captures| {
+            if let Some(level) = caps.name("level") {
+                let level: usize = level.as_str().parse().unwrap_or(1);
+                let attrs = &caps["attrs"];
+                let heading = &caps["heading"];
+
+                // Part title pages are not chapters or sections
+                if attrs.contains("part-title") {
+                    return caps[0].to_string();
+                }
+
+                if level == 1 {
+                    chapter += 1;
+                    if self.per_chapter {
+                        figures = 0;
+                        tables = 0;
+                        equations = 0;
+                    }
+                }
+                sections[level - 1] += 1;
+                for counter in sections.iter_mut().skip(level) {
+                    *counter = 0;
+                }
+
+                let Some(label) = SECTION_LABEL_REGEX.captures(heading) else {
+                    return caps[0].to_string();
+                };
+
+                let text = SECTION_LABEL_REGEX.replace(heading, "");
+                let number = sections[..level].iter()
+                    .filter(|&&c| c > 0)
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let id = self.register("sec", &label[1], number, strip_tags(&text));
+
+                format!("<h{level}{attrs}{}>{text}</h{level}>", id_attr(id))
+            } else if let Some(fig) = caps.name("fig") {
+                figures += 1;
+                let img = &caps["img"];
+                let caption = ALT_REGEX.captures(img)
+                    .map(|alt| html_escape::decode_html_entities(&alt[1]).to_string())
+                    .unwrap_or_default();
+                let number = self.format_number(chapter, figures);
+                let id = self.register("fig", fig.as_str(), number.clone(), caption.clone());
+
+                format!(
+                    r#"<figure class="figure"{}>{img}<figcaption class="figure-caption"><span class="figure-number">Figure {number}:</span> {}</figcaption></figure>"#,
+                    id_attr(id),
+                    html_escape::encode_text(&caption)
+                )
+            } else if let Some(table) = caps.name("table") {
+                tables += 1;
+                let caption = &caps["table_caption"];
+                let number = self.format_number(chapter, tables);
+                let id = self.register("table", table.as_str(), number.clone(), strip_tags(caption));
+
+                format!(
+                    r#"<table{}><caption class="table-caption"><span class="table-number">Table {number}:</span> {caption}</caption>"#,
+                    id_attr(id)
+                )
+            } else if let Some(eq) = caps.name("eq") {
+                equations += 1;
+                let number = self.format_number(chapter, equations);
+                let id = self.register("eq", eq.as_str(), number.clone(), String::new());
+
+                format!(
+                    r#"<div class="math-display equation"{}>{}<span class="equation-number">({number})</span></div>"#,
+                    id_attr(id),
+                    &caps["math"]
+                )
+            } else {
+                caps[0].to_string()
+            }
+        }).into_owned()
+    }
+
+    fn format_number(&self, chapter: u32, count: u32) -> String {
+        if self.per_chapter && chapter > 0 {
+            format!("{chapter}.{count}")
+        } else {
+            count.to_string()
+        }
+    }
+
+    /// Record a label, returning the element id or `None` when the label is a duplicate
+    fn register(&mut self, kind: &str, name: &str, number: String, caption: String) -> Option<String> {
+        let key = format!("{kind}:{name}");
+        if self.labels.contains_key(&key) {
+            Logger::warning(format!("Duplicate label {{#{key}}}; references point to its first definition"));
+            return None;
+        }
+
+        self.labels.insert(key, Label { number, caption });
+        Some(format!("{kind}-{name}"))
+    }
+
+    fn resolve_references(&self, html: &str) -> String {
+        XREF_REGEX.replace_all(html, |caps: &regex::Captures| {
+            let ref_type = &caps[1];
+            let ref_id = &caps[2];
+
+            let name = match ref_type {
+                "fig" => "Figure",
+                "table" => "Table",
+                "eq" => "Equation",
+                "sec" => "Section",
+                _ => return format!("<a href=\"#{ref_id}\" class=\"cross-ref\">{ref_id}</a>"),
+            };
+
+            match self.labels.get(&format!("{ref_type}:{ref_id}")) {
+                Some(label) => {
+                    let number = if ref_type == "eq" {
+                        format!("({})", label.number)
+                    } else {
+                        label.number.clone()
+                    };
+                    format!(
+                        "<a href=\"#{ref_type}-{ref_id}\" class=\"cross-ref cross-ref-{ref_type}\" title=\"{}\">{name} <span class=\"ref-number\">{number}</span></a>",
+                        html_escape::encode_double_quoted_attribute(&label.caption)
+                    )
+                }
+                None => {
+                    Logger::warning(format!("Unresolved cross-reference [@ref:{ref_type}:{ref_id}]"));
+                    format!("<span class=\"cross-ref cross-ref-missing\">{name} ??</span>")
+                }
+            }
+        }).into_owned()
+    }
+}
+
+fn id_attr(id: Option<String>) -> String {
+    id.map(|id| format!(" id=\"{id}\"")).unwrap_or_default()
+}
+
+fn strip_tags(html: &str) -> String {
+    html_escape::decode_html_entities(&TAG_REGEX.replace_all(html, "")).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers_and_resolves_labels_per_chapter() {
+        let html = concat!(
+            r#"<h1>One</h1><h2>Setup {#sec:setup}</h2>"#,
+            r#"<p><img src="a.png" alt="Overview" />{#fig:overview}</p>"#,
+            r#"<h1>Two</h1><p><img src="b.png" alt="Detail" />{#fig:detail}</p>"#,
+            r#"<p>Table: Results {#table:results}</p><table><tr><td>1</td></tr></table>"#,
+            r#"<p>[@ref:fig:detail], [@ref:sec:setup], [@ref:table:results], [@ref:fig:missing]</p>"#,
+        );
+
+        let output = CrossReferencer::new(true).process(html);
+
+        assert!(output.contains(r#"<h2 id="sec-setup">Setup</h2>"#));
+        assert!(output.contains(r#"<figure class="figure" id="fig-detail">"#));
+        assert!(output.contains(r#"Figure <span class="ref-number">2.1</span>"#));
+        assert!(output.contains(r#"Section <span class="ref-number">1.1</span>"#));
+        assert!(output.contains(r#"<table id="table-results"><caption class="table-caption"><span class="table-number">Table 2.1:</span> Results</caption>"#));
+        assert!(output.contains("Figure ??"));
+    }
+
+    #[test]
+    fn test_duplicate_labels_keep_first_definition() {
+        let html = r#"<div class="math-display">$$a$$</div> {#eq:x}<div class="math-display">$$b$$</div> {#eq:x}<p>[@ref:eq:x]</p>"#;
+
+        let output = CrossReferencer::new(false).process(html);
+
+        assert_eq!(output.matches(r#"id="eq-x""#).count(), 1);
+        assert!(output.contains(r#"<span class="equation-number">(2)</span>"#));
+        assert!(output.contains(r#"Equation <span class="ref-number">(1)</span>"#));
+    }
+
+    #[test]
+    fn test_unlabeled_elements_do_not_absorb_later_labels() {
+        let html = concat!(
+            r#"<div class="math-display">$$a = b$$</div>"#,
+            r#"<h2>Middle {#sec:mid}</h2>"#,
+            r#"<p>Table: Not labeled</p><p>Table: Totals {#table:totals}</p><table><tr><td>1</td></tr></table>"#,
+            r#"<div class="math-display">$$c = d$$</div> {#eq:second}"#,
+            r#"<p>[@ref:sec:mid], [@ref:eq:second]</p>"#,
+        );
+
+        let output = CrossReferencer::new(false).process(html);
+
+        assert!(output.starts_with(r#"<div class="math-display">$$a = b$$</div><h2 id="sec-mid">Middle</h2>"#));
+        assert!(output.contains(r#"<p>Table: Not labeled</p><table id="table-totals">"#));
+        assert!(output.contains(r#"<div class="math-display equation" id="eq-second">$$c = d$$<span class="equation-number">(1)</span></div>"#));
+        assert!(output.contains(r#"Section <span class="ref-number">1</span>"#));
+    }
+}
@@ -0,0 +1,362 @@
+use comrak::{nodes::{AstNode, ListType as NodeListType, NodeValue}, parse_document, Arena, ComrakOptions};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElementType {
+    Heading,
+    Paragraph,
+    ListItem,
+    TaskListItem,
+    BlockQuote,
+    Table,
+    CodeBlock,
+    Image,
+    HorizontalRule,
+    Footnote,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListType {
+    Bullet,
+    Ordered(u64), // Number of this item
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextFormat {
+    Bold,
+    Italic,
+    Strikethrough,
+    Code,
+}
+
+/// A run of inline text sharing one set of styles, and the link it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub formats: Vec<TextFormat>,
+    pub link: Option<String>,
+}
+
+impl TextSpan {
+    pub fn plain(text: impl Into<String>) -> Self {
+        Self { text: text.into(), formats: Vec::new(), link: None }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TableData {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// One block of the flattened document laid out by `PdfGenerator`.
+/// `content` is the plain text; paragraphs, list items, quotes and footnotes
+/// also carry it as styled `spans`.
+#[derive(Debug, Clone)]
+pub struct DocumentElement {
+    pub element_type: ElementType,
+    pub content: String,
+    pub spans: Vec<TextSpan>,
+    pub formatting: Vec<TextFormat>, // Applies to the whole element
+    pub level: Option<u8>,           // Heading level, or list nesting depth
+    pub list_type: Option<ListType>,
+    pub is_checked: Option<bool>,
+    pub table_data: Option<TableData>,
+    pub url: Option<String>,         // Image source, or footnote label
+}
+
+impl DocumentElement {
+    fn new(element_type: ElementType, content: String) -> Self {
+        Self {
+            element_type,
+            content,
+            spans: Vec::new(),
+            formatting: Vec::new(),
+            level: None,
+            list_type: None,
+            is_checked: None,
+            table_data: None,
+            url: None,
+        }
+    }
+
+    /// An element whose text comes from styled spans
+    fn with_spans(element_type: ElementType, spans: Vec<TextSpan>) -> Self {
+        let content = spans.iter().map(|span| span.text.as_str()).collect();
+        Self { spans, ..Self::new(element_type, content) }
+    }
+}
+
+/// Parse Markdown with comrak and flatten the tree into layout elements
+pub fn parse_markdown(markdown: &str) -> Vec<DocumentElement> {
+    let arena = Arena::new();
+    let mut options = ComrakOptions::default();
+    options.extension.strikethrough = true;
+    options.extension.table = true;
+    options.extension.autolink = true;
+    options.extension.tasklist = true;
+    options.extension.footnotes = true;
+
+    let root = parse_document(&arena, markdown, &options);
+    let mut builder = ElementBuilder::default();
+    builder.block(root, 0);
+    builder.elements
+}
+
+#[derive(Default)]
+struct ElementBuilder {
+    elements: Vec<DocumentElement>,
+    // Footnote numbers in order of first reference
+    footnotes: HashMap<String, usize>,
+}
+
+impl ElementBuilder {
+    fn block<'a>(&mut self, node: &'a AstNode<'a>, depth: u8) {
+        match &node.data.borrow().value {
+            NodeValue::Heading(heading) => {
+                let mut element = DocumentElement::new(ElementType::Heading, plain_text(node));
+                element.level = Some(heading.level);
+                self.elements.push(element);
+            }
+            // The native engine has no generated TOC; drop the marker like the LaTeX backend
+            NodeValue::Paragraph if plain_text(node).trim() == "[TOC]" => {}
+            NodeValue::Paragraph => self.paragraph(node, ElementType::Paragraph),
+            NodeValue::List(list) => {
+                for (number, item) in (list.start as u64..).zip(node.children()) {
+                    let list_type = match list.list_type {
+                        NodeListType::Bullet => ListType::Bullet,
+                        NodeListType::Ordered => ListType::Ordered(number),
+                    };
+                    self.list_item(item, list_type, depth);
+                }
+            }
+            NodeValue::CodeBlock(code_block) => {
+                let code = code_block.literal.trim_end_matches('\n').to_string();
+                self.elements.push(DocumentElement::new(ElementType::CodeBlock, code));
+            }
+            NodeValue::Table(_) => {
+                let mut table = TableData::default();
+                for row in node.children() {
+                    let cells: Vec<String> = row.children().map(plain_text).collect();
+                    match &row.data.borrow().value {
+                        NodeValue::TableRow(true) => table.headers = cells,
+                        _ => table.rows.push(cells),
+                    }
+                }
+                let mut element = DocumentElement::new(ElementType::Table, String::new());
+                element.table_data = Some(table);
+                self.elements.push(element);
+            }
+            NodeValue::ThematicBreak => {
+                self.elements.push(DocumentElement::new(ElementType::HorizontalRule, String::new()));
+            }
+            NodeValue::BlockQuote => {
+                for child in node.children() {
+                    if matches!(child.data.borrow().value, NodeValue::Paragraph) {
+                        self.paragraph(child, ElementType::BlockQuote);
+                    } else {
+                        self.block(child, depth);
+                    }
+                }
+            }
+            NodeValue::FootnoteDefinition(definition) => {
+                let label = self.footnotes.get(&definition.name)
+                    .map(|number| number.to_string())
+                    .unwrap_or_else(|| definition.name.clone());
+                let spans = self.inline_paragraphs(node.children());
+                let mut element = DocumentElement::with_spans(ElementType::Footnote, spans);
+                element.url = Some(label);
+                self.elements.push(element);
+            }
+            // Raw HTML has no native rendering
+            NodeValue::HtmlBlock(_) => {}
+            _ => {
+                for child in node.children() {
+                    self.block(child, depth);
+                }
+            }
+        }
+    }
+
+    /// A list item's paragraphs become one element; nested lists follow it one level deeper
+    fn list_item<'a>(&mut self, item: &'a AstNode<'a>, list_type: ListType, depth: u8) {
+        let checked = match &item.data.borrow().value {
+            NodeValue::TaskItem(mark) => Some(mark.is_some()),
+            _ => None,
+        };
+
+        let spans = self.inline_paragraphs(item.children()
+            .filter(|child| matches!(child.data.borrow().value, NodeValue::Paragraph)));
+
+        let element_type = if checked.is_some() { ElementType::TaskListItem } else { ElementType::ListItem };
+        let mut element = DocumentElement::with_spans(element_type, spans);
+        element.level = Some(depth);
+        element.list_type = Some(list_type);
+        element.is_checked = checked;
+        self.elements.push(element);
+
+        for child in item.children() {
+            if !matches!(child.data.borrow().value, NodeValue::Paragraph) {
+                self.block(child, depth + 1);
+            }
+        }
+    }
+
+    /// Paragraph text, with each image split out into its own element
+    fn paragraph<'a>(&mut self, node: &'a AstNode<'a>, element_type: ElementType) {
+        let mut spans = Vec::new();
+
+        for child in node.children() {
+            if let NodeValue::Image(image) = &child.data.borrow().value {
+                self.push_spans(element_type, std::mem::take(&mut spans));
+                let mut element = DocumentElement::new(ElementType::Image, plain_text(child));
+                element.url = Some(image.url.clone());
+                self.elements.push(element);
+            } else {
+                self.inline(child, &InlineStyle::default(), &mut spans);
+            }
+        }
+
+        self.push_spans(element_type, spans);
+    }
+
+    fn push_spans(&mut self, element_type: ElementType, spans: Vec<TextSpan>) {
+        let spans = trim_spans(spans);
+        if !spans.is_empty() {
+            self.elements.push(DocumentElement::with_spans(element_type, spans));
+        }
+    }
+
+    /// Inline content of several paragraphs, joined by spaces
+    fn inline_paragraphs<'a>(&mut self, paragraphs: impl Iterator<Item = &'a AstNode<'a>>) -> Vec<TextSpan> {
+        let mut spans = Vec::new();
+        for paragraph in paragraphs {
+            let mut text = Vec::new();
+            for child in paragraph.children() {
+                self.inline(child, &InlineStyle::default(), &mut text);
+            }
+            let text = trim_spans(text);
+            if !spans.is_empty() && !text.is_empty() {
+                push_span(&mut spans, " ", &InlineStyle::default());
+            }
+            spans.extend(text);
+        }
+        spans
+    }
+
+    fn inline<'a>(&mut self, node: &'a AstNode<'a>, style: &InlineStyle, out: &mut Vec<TextSpan>) {
+        let mut style = style.clone();
+        match &node.data.borrow().value {
+            NodeValue::Text(text) => return push_span(out, text, &style),
+            NodeValue::Code(code) => {
+                style.formats.push(TextFormat::Code);
+                return push_span(out, &code.literal, &style);
+            }
+            NodeValue::SoftBreak | NodeValue::LineBreak => return push_span(out, " ", &style),
+            NodeValue::FootnoteReference(reference) => {
+                let next = self.footnotes.len() + 1;
+                let number = *self.footnotes.entry(reference.name.clone()).or_insert(next);
+                return push_span(out, &format!("[{number}]"), &style);
+            }
+            NodeValue::HtmlInline(_) => return,
+            NodeValue::Link(link) => style.link = Some(link.url.clone()),
+            NodeValue::Strong => style.formats.push(TextFormat::Bold),
+            NodeValue::Emph => style.formats.push(TextFormat::Italic),
+            NodeValue::Strikethrough => style.formats.push(TextFormat::Strikethrough),
+            // Images inside lists and tables fall back to their alt text
+            _ => {}
+        }
+
+        for child in node.children() {
+            self.inline(child, &style, out);
+        }
+    }
+}
+
+/// Styles in effect while walking inline nodes
+#[derive(Clone, Default)]
+struct InlineStyle {
+    formats: Vec<TextFormat>,
+    link: Option<String>,
+}
+
+/// Append text, extending the last span when its style is the same
+fn push_span(spans: &mut Vec<TextSpan>, text: &str, style: &InlineStyle) {
+    match spans.last_mut() {
+        Some(last) if last.formats == style.formats && last.link == style.link => last.text.push_str(text),
+        _ => spans.push(TextSpan { text: text.to_string(), formats: style.formats.clone(), link: style.link.clone() }),
+    }
+}
+
+/// Drop leading and trailing whitespace, and any spans left empty
+fn trim_spans(mut spans: Vec<TextSpan>) -> Vec<TextSpan> {
+    if let Some(first) = spans.first_mut() {
+        first.text = first.text.trim_start().to_string();
+    }
+    if let Some(last) = spans.last_mut() {
+        last.text = last.text.trim_end().to_string();
+    }
+    spans.retain(|span| !span.text.is_empty());
+    spans
+}
+
+/// Text content of a node without any styling
+fn plain_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for descendant in node.descendants() {
+        match &descendant.data.borrow().value {
+            NodeValue::Text(literal) => text.push_str(literal),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_flattens_to_layout_elements() {
+        let markdown = "[TOC]\n\n# Guide\n\nSome **bold** and [docs](https://example.com).[^n]\n\n\
+            1. First\n   - [x] Nested task\n2. Second\n\n![Chart](chart.png)\n\n\
+            | A | B |\n|---|---|\n| 1 | 2 |\n\n[^n]: A note.\n";
+        let elements = parse_markdown(markdown);
+        let types: Vec<ElementType> = elements.iter().map(|element| element.element_type).collect();
+
+        assert_eq!(types, vec![
+            ElementType::Heading,
+            ElementType::Paragraph,
+            ElementType::ListItem,
+            ElementType::TaskListItem,
+            ElementType::ListItem,
+            ElementType::Image,
+            ElementType::Table,
+            ElementType::Footnote,
+        ]);
+        assert_eq!(elements[1].content, "Some bold and docs.[1]");
+        assert_eq!(elements[1].spans, vec![
+            TextSpan::plain("Some "),
+            TextSpan { text: "bold".into(), formats: vec![TextFormat::Bold], link: None },
+            TextSpan::plain(" and "),
+            TextSpan { text: "docs".into(), formats: Vec::new(), link: Some("https://example.com".into()) },
+            TextSpan::plain(".[1]"),
+        ]);
+        assert_eq!(elements[4].list_type, Some(ListType::Ordered(2)));
+        assert_eq!((elements[3].level, elements[3].is_checked), (Some(1), Some(true)));
+        assert_eq!(elements[5].url.as_deref(), Some("chart.png"));
+        assert_eq!(elements[6].table_data.as_ref().unwrap().rows, vec![vec!["1", "2"]]);
+        assert_eq!((elements[7].content.as_str(), elements[7].url.as_deref()), ("A note.", Some("1")));
+    }
+
+    #[test]
+    fn test_bracketed_text_and_urls_stay_literal() {
+        let elements = parse_markdown("Write `[BOLD_START]` or [*this*](https://example.com/a]b).\n");
+
+        assert_eq!(elements[0].content, "Write [BOLD_START] or this.");
+        assert_eq!(elements[0].spans[1], TextSpan { text: "[BOLD_START]".into(), formats: vec![TextFormat::Code], link: None });
+        assert_eq!(elements[0].spans[3].link.as_deref(), Some("https://example.com/a]b"));
+    }
+}
@@ -1,18 +1,126 @@
 use anyhow::{Context, Result};
-use comrak::{nodes::{AstNode, NodeValue}, parse_document, Arena, ComrakOptions};
+use comrak::{nodes::{AstNode, ListType, NodeList, NodeTable, NodeValue, TableAlignment}, parse_document, Anchorizer, Arena, ComrakOptions};
 use docx_rs::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::cell::RefCell;
 use std::fs;
-use std::path::Path;
-use crate::config::Config;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, FontStyle, Style as TokenStyle};
+use syntect::util::LinesWithEndings;
+use crate::config::{Config, DocumentMetadata, HeaderFooterConfig, HeaderFooterSlots, Orientation, PageNumberPosition};
+use crate::docx_styles::{self, DocxTheme};
+use crate::front_matter::FrontMatter;
+use crate::image_optimization::ImageOptimizer;
 use crate::logger::Logger;
+use crate::syntax_highlighting::{self, SYNTAX_SET};
+use crate::templates::{self, PageTemplates};
+
+const EMU_PER_PIXEL: u32 = 9525; // At 96 DPI
+const EMU_PER_TWIP: u32 = 635;
+const LIST_INDENT: i32 = 720; // Per nesting level, in twentieths of a point
+
+// Light syntect theme for code when `code.highlight_theme` is not one of its names
+const CODE_THEME: &str = "InspiredGitHub";
+
+// docx-rs reserves abstract numbering and instance 1 for its default list
+const BULLET_NUMBERING: usize = 2;
+const DECIMAL_NUMBERING: usize = 3;
+
+lazy_static! {
+    // Chrome's header/footer placeholders, as used in `PageConfig` templates
+    static ref TEMPLATE_SPAN_REGEX: Regex = Regex::new(r#"<span class="(pageNumber|totalPages|title|date)"></span>"#).expect("Invalid template span regex");
+    static ref TAG_REGEX: Regex = Regex::new(r"<[^>]+>").expect("Invalid tag regex");
+    static ref PAGE_FIELD_REGEX: Regex = Regex::new(r"\{(page|total|chapter)\}").expect("Invalid page field regex");
+}
+
+/// Top-level body content; docx-rs adds paragraphs, tables and the TOC separately
+enum Block {
+    Paragraph(Box<Paragraph>),
+    Table(Box<Table>),
+    TableOfContents(Box<TableOfContents>),
+}
+
+/// Per-document state, reset at the start of each conversion
+struct DocumentState {
+    // Heading slugs, matching the HTML ids so `#anchor` links resolve to bookmarks
+    anchorizer: Anchorizer,
+    bookmark_count: usize,
+    table_header_fill: String,
+    // One numbering instance per list, so each list restarts its count
+    numberings: Vec<Numbering>,
+}
+
+/// Character formatting inherited from enclosing inline nodes; each nested
+/// node adds to a copy, so `***x***` is both bold and italic
+#[derive(Debug, Clone, Copy, Default)]
+struct RunStyle {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    superscript: bool,
+    character_style: Option<&'static str>,
+}
+
+impl RunStyle {
+    fn run(&self, text: &str) -> Run {
+        self.apply(Run::new().add_text(text))
+    }
+
+    fn apply(&self, mut run: Run) -> Run {
+        if let Some(style) = self.character_style {
+            run = run.style(style);
+        }
+        if self.bold {
+            run = run.bold();
+        }
+        if self.italic {
+            run = run.italic();
+        }
+        if self.strike {
+            run = run.strike();
+        }
+        if self.superscript {
+            run.run_property = run.run_property.vert_align(VertAlignType::SuperScript);
+        }
+        run
+    }
+}
 
 pub struct DocxConverter {
     config: Config,
+    metadata: DocumentMetadata,
+    base_path: PathBuf,
+    state: RefCell<DocumentState>,
 }
 
 impl DocxConverter {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            metadata: DocumentMetadata::default(),
+            base_path: PathBuf::from("."),
+            state: RefCell::new(DocumentState {
+                anchorizer: Anchorizer::new(),
+                bookmark_count: 0,
+                table_header_fill: String::new(),
+                numberings: Vec::new(),
+            }),
+        }
+    }
+
+    /// Title and date shown by `title`/`date` placeholders in headers and footers
+    pub fn with_metadata(mut self, metadata: DocumentMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Directory that relative image paths are resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
     }
 
     pub fn convert_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
@@ -22,6 +130,44 @@ impl DocxConverter {
         let markdown_content = fs::read_to_string(input_path)
             .with_context(|| format!("Failed to read input file: {}", input_path.display()))?;
 
+        let docx_data = Self::new(self.config.clone())
+            .with_metadata(self.metadata.clone())
+            .with_base_path(input_path.parent().unwrap_or(Path::new(".")))
+            .convert_str(&markdown_content)?;
+        self.write_output(&docx_data, output_path)
+    }
+
+    /// Convert a whole document held in memory, applying its front matter
+    pub fn convert_str(&self, markdown_content: &str) -> Result<Vec<u8>> {
+        // Front matter may adjust the layout of this document only
+        let (front_matter, markdown_body) = FrontMatter::extract(markdown_content)?;
+        let config = front_matter.apply_to(&self.config)?;
+        let metadata = self.metadata.clone()
+            .or(front_matter.metadata)
+            .or(config.metadata.clone());
+
+        Self::new(config)
+            .with_metadata(metadata)
+            .with_base_path(&self.base_path)
+            .render(markdown_body)
+    }
+
+    /// Convert Markdown source that has already been loaded, e.g. an assembled book
+    pub fn convert_markdown(&self, markdown_content: &str, output_path: &Path) -> Result<()> {
+        let docx_data = self.render(markdown_content)?;
+        self.write_output(&docx_data, output_path)
+    }
+
+    fn write_output(&self, docx_data: &[u8], output_path: &Path) -> Result<()> {
+        fs::write(output_path, docx_data)
+            .with_context(|| format!("Failed to write DOCX file: {}", output_path.display()))?;
+
+        Logger::verbose(format!("Successfully converted to {}", output_path.display()));
+        Ok(())
+    }
+
+    /// The packed `.docx` archive for Markdown without front matter
+    fn render(&self, markdown_content: &str) -> Result<Vec<u8>> {
         // Parse markdown to AST
         let arena = Arena::new();
         let mut options = ComrakOptions::default();
@@ -36,47 +182,87 @@ impl DocxConverter {
         options.extension.description_lists = true;
         options.extension.front_matter_delimiter = Some("---".to_string());
 
-        let root = parse_document(&arena, &markdown_content, &options);
+        let root = parse_document(&arena, markdown_content, &options);
+
+        // Named styles derived from the theme, unless a reference document supplies them
+        let theme = DocxTheme::from_config(&self.config)?;
+        let mut styles = theme.styles();
+        if let Some(reference_doc) = &self.config.docx.reference_doc {
+            styles = docx_styles::reference_styles(reference_doc, styles)?;
+        }
+
+        *self.state.borrow_mut() = DocumentState {
+            anchorizer: Anchorizer::new(),
+            bookmark_count: 0,
+            table_header_fill: theme.table_header_background,
+            numberings: Vec::new(),
+        };
 
         // Create DOCX document
-        let mut docx = Docx::new();
+        let mut docx = Docx::new()
+            .styles(styles)
+            .add_abstract_numbering(Self::list_levels(BULLET_NUMBERING, ListType::Bullet))
+            .add_abstract_numbering(Self::list_levels(DECIMAL_NUMBERING, ListType::Ordered));
 
         // Apply document settings based on config
         self.apply_document_settings(&mut docx)?;
 
-        // Convert AST to DOCX by collecting paragraphs
-        let paragraphs = self.collect_paragraphs(root)?;
-        Logger::verbose(format!("Collected {} paragraphs for conversion", paragraphs.len()));
+        // Convert AST to DOCX by collecting paragraphs and tables
+        let blocks = self.collect_blocks(root)?;
+        Logger::verbose(format!("Collected {} blocks for conversion", blocks.len()));
         
-        // Add all paragraphs to the document
-        for paragraph in paragraphs {
-            docx = docx.add_paragraph(paragraph);
+        // Add all blocks to the document
+        for block in blocks {
+            docx = match block {
+                Block::Paragraph(paragraph) => docx.add_paragraph(*paragraph),
+                Block::Table(table) => docx.add_table(*table),
+                Block::TableOfContents(toc) => docx.add_table_of_contents(*toc),
+            };
+        }
+        for numbering in std::mem::take(&mut self.state.borrow_mut().numberings) {
+            docx = docx.add_numbering(numbering);
         }
         
         // Add a final empty paragraph for proper formatting
-        docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text("")));
+        docx = docx.add_paragraph(Paragraph::new().style(docx_styles::BODY).add_run(Run::new().add_text("")));
 
-        // Write to output file
-        let mut file = std::fs::File::create(output_path)
-            .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
-        
-        docx.build().pack(&mut file)
-            .with_context(|| format!("Failed to write DOCX file: {}", output_path.display()))?;
+        let mut docx_data = Cursor::new(Vec::new());
+        docx.build().pack(&mut docx_data)
+            .context("Failed to pack DOCX document")?;
 
-        Logger::verbose(format!("Successfully converted to {}", output_path.display()));
-        Ok(())
+        Ok(docx_data.into_inner())
+    }
+
+    fn page_size(&self) -> (u32, u32) {
+        let (width, height) = match self.config.page.size.preset.as_deref().map(str::to_uppercase).as_deref() {
+            Some("A4") => (11906, 16838), // 210 × 297 mm in twentieths of a point
+            Some("LETTER") => (12240, 15840), // 8.5 × 11 in
+            Some("LEGAL") => (12240, 20160), // 8.5 × 14 in
+            Some("A3") => (16838, 23811), // 297 × 420 mm
+            Some("A5") => (8391, 11906), // 148 × 210 mm
+            _ => (11906, 16838), // Default to A4
+        };
+        match self.config.page.orientation {
+            Orientation::Landscape => (height, width),
+            Orientation::Portrait => (width, height),
+        }
+    }
+
+    /// Width between the left and right margins, in twentieths of a point
+    fn content_width(&self) -> u32 {
+        let margins = self.parse_margin(&self.config.page.margins.left).unwrap_or(1440)
+            + self.parse_margin(&self.config.page.margins.right).unwrap_or(1440);
+        (self.page_size().0 as i32 - margins).max(1440) as u32
+    }
+
+    /// Width between the left and right margins, in EMU
+    fn content_width_emu(&self) -> u32 {
+        self.content_width() * EMU_PER_TWIP
     }
 
     fn apply_document_settings(&self, docx: &mut Docx) -> Result<()> {
         // Apply page settings
-        let page_size = match self.config.page.size.preset.as_deref() {
-            Some("A4") => (8267, 11693), // A4 in twentieths of a point
-            Some("Letter") => (7920, 10080), // Letter in twentieths of a point
-            Some("Legal") => (7920, 12240), // Legal in twentieths of a point
-            Some("A3") => (11693, 16535), // A3 in twentieths of a point
-            Some("A5") => (4961, 7016), // A5 in twentieths of a point
-            _ => (8267, 11693), // Default to A4
-        };
+        let page_size = self.page_size();
 
         // Convert margins from CSS units to twentieths of a point
         let top_margin = self.parse_margin(&self.config.page.margins.top)?;
@@ -92,16 +278,147 @@ impl DocxConverter {
                 .right(right_margin)
                 .bottom(bottom_margin)
                 .left(left_margin));
+        if matches!(self.config.page.orientation, Orientation::Landscape) {
+            *docx = docx.clone().page_orient(PageOrientationType::Landscape);
+        }
 
-        // Apply font settings if specified
-        if let Some(_font_family) = &self.config.fonts.family {
-            // Note: Font settings will be applied to individual runs
-            // as the current docx-rs version doesn't have DefaultFonts
+        // Word uses the default header for odd pages once even ones differ
+        if let Some(header) = self.config.page.header_footer(PageNumberPosition::Header) {
+            let paragraph = |slots: &HeaderFooterSlots| self.slots_paragraph(slots, docx_styles::HEADER, &header);
+            *docx = docx.clone().header(Header::new().add_paragraph(paragraph(header.odd.as_ref().unwrap_or(&header.default_slots()))));
+            if let Some(first) = &header.first {
+                *docx = docx.clone().first_header(Header::new().add_paragraph(paragraph(first)));
+            }
+            if let Some(even) = &header.even {
+                *docx = docx.clone().even_header(Header::new().add_paragraph(paragraph(even)));
+            }
+        }
+        if let Some(footer) = self.config.page.header_footer(PageNumberPosition::Footer) {
+            let paragraph = |slots: &HeaderFooterSlots| self.slots_paragraph(slots, docx_styles::FOOTER, &footer);
+            *docx = docx.clone().footer(Footer::new().add_paragraph(paragraph(footer.odd.as_ref().unwrap_or(&footer.default_slots()))));
+            if let Some(first) = &footer.first {
+                *docx = docx.clone().first_footer(Footer::new().add_paragraph(paragraph(first)));
+            }
+            if let Some(even) = &footer.even {
+                *docx = docx.clone().even_footer(Footer::new().add_paragraph(paragraph(even)));
+            }
+        }
+        if let Some(start) = self.config.page.page_numbers.as_ref().and_then(|p| p.start_from) {
+            *docx = docx.clone().page_num_type(PageNumType::new().start(start));
         }
 
         Ok(())
     }
 
+    /// Header or footer paragraph: a centred template, or left/centre/right
+    /// slots separated by tab stops across the text width
+    fn slots_paragraph(&self, slots: &HeaderFooterSlots, style: &str, configured: &HeaderFooterConfig) -> Paragraph {
+        let size = configured.font_size.as_deref().and_then(docx_styles::half_points);
+        if slots.left.is_empty() && slots.right.is_empty() {
+            return self.template_runs(&slots.center, size).into_iter()
+                .fold(Paragraph::new().style(style), Paragraph::add_run);
+        }
+
+        let width = self.content_width() as usize;
+        let mut paragraph = Paragraph::new()
+            .style(style)
+            .align(AlignmentType::Left)
+            .add_tab(Tab::new().val(TabValueType::Center).pos(width / 2))
+            .add_tab(Tab::new().val(TabValueType::Right).pos(width));
+        for (i, slot) in [&slots.left, &slots.center, &slots.right].into_iter().enumerate() {
+            if i > 0 {
+                paragraph = paragraph.add_run(Run::new().add_tab());
+            }
+            paragraph = self.template_runs(slot, size).into_iter().fold(paragraph, Paragraph::add_run);
+        }
+        paragraph
+    }
+
+    /// Turn an HTML header/footer template into text with PAGE, NUMPAGES and
+    /// STYLEREF fields; `{page}`, `{total}`, `{chapter}`, `{title}` and `{date}`
+    /// work as well as the spans
+    fn template_runs(&self, template: &str, size: Option<usize>) -> Vec<Run> {
+        let text = TEMPLATE_SPAN_REGEX.replace_all(template, |caps: &regex::Captures| match &caps[1] {
+            "pageNumber" => "{page}",
+            "totalPages" => "{total}",
+            "title" => "{title}",
+            _ => "{date}",
+        });
+        // Tera variables as in the PDF templates; page and chapter become Word fields
+        let mut context = templates::document_context(&self.metadata, None, None, &Default::default());
+        for field in ["page", "total", "chapter"] {
+            context.insert(field, &format!("{{{field}}}"));
+        }
+        let text = PageTemplates::render_str(&text, &context).unwrap_or_else(|e| {
+            Logger::warning(format!("{e}"));
+            text.into_owned()
+        });
+        let text = html_escape::decode_html_entities(&TAG_REGEX.replace_all(&text, "")).trim().to_string();
+        let date = self.metadata.date.clone()
+            .unwrap_or_else(|| chrono::Local::now().format("%-m/%-d/%Y").to_string());
+        let text = text
+            .replace("{title}", self.metadata.title.as_deref().unwrap_or_default())
+            .replace("{date}", &date);
+
+        let sized = |run: Run| match size {
+            Some(size) => run.size(size),
+            None => run,
+        };
+
+        let mut runs = Vec::new();
+        let mut last = 0;
+        for field in PAGE_FIELD_REGEX.find_iter(&text) {
+            if field.start() > last {
+                runs.push(sized(Run::new().add_text(&text[last..field.start()])));
+            }
+            let (instr, cached) = match field.as_str() {
+                "{page}" => (InstrText::PAGE(InstrPAGE::new()), "1"),
+                "{total}" => (InstrText::NUMPAGES(InstrNUMPAGES::new()), "1"),
+                // Running header: the nearest Heading 1 text on or before the page
+                _ => (InstrText::Unsupported(r#"STYLEREF "Heading 1" "#.to_string()), ""),
+            };
+            // Word recalculates the cached value when the document is laid out
+            runs.push(sized(Run::new()
+                .add_field_char(FieldCharType::Begin, false)
+                .add_instr_text(instr)
+                .add_field_char(FieldCharType::Separate, false)
+                .add_text(cached)
+                .add_field_char(FieldCharType::End, false)));
+            last = field.end();
+        }
+        if last < text.len() {
+            runs.push(sized(Run::new().add_text(&text[last..])));
+        }
+
+        runs
+    }
+
+    /// Word TOC field over Heading 1 to `max_depth`, filled with the current
+    /// headings and flagged dirty so Word recomputes page numbers on open
+    fn table_of_contents(&self) -> TableOfContents {
+        let toc_config = &self.config.toc;
+        let depth = toc_config.max_depth.clamp(1, 6) as usize;
+
+        let mut toc = TableOfContents::new()
+            .heading_styles_range(1, depth)
+            .alias("Table of contents")
+            .auto()
+            .dirty();
+        if toc_config.links {
+            toc = toc.hyperlink();
+        }
+        if !toc_config.page_numbers {
+            toc.instr = toc.instr.omit_page_numbers_level_range(1, depth);
+        }
+        if !toc_config.title.is_empty() {
+            toc = toc.add_before_paragraph(Paragraph::new()
+                .style(docx_styles::TOC_HEADING)
+                .add_run(Run::new().add_text(&toc_config.title)));
+        }
+
+        toc
+    }
+
     fn parse_margin(&self, margin_str: &str) -> Result<i32> {
         // Parse margin string (e.g., "1in", "20mm", "2cm") to twentieths of a point
         let margin_str = margin_str.trim().to_lowercase();
@@ -130,144 +447,333 @@ impl DocxConverter {
         }
     }
 
-    fn collect_paragraphs<'a>(&self, node: &'a AstNode<'a>) -> Result<Vec<Paragraph>> {
-        let mut paragraphs = Vec::new();
-        self.process_node_to_paragraphs(node, &mut paragraphs)?;
-        Ok(paragraphs)
+    fn collect_blocks<'a>(&self, node: &'a AstNode<'a>) -> Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        self.process_node_to_blocks(node, &mut blocks)?;
+        Ok(blocks)
     }
 
-    fn process_node_to_paragraphs<'a>(&self, node: &'a AstNode<'a>, paragraphs: &mut Vec<Paragraph>) -> Result<()> {
+    fn process_node_to_blocks<'a>(&self, node: &'a AstNode<'a>, blocks: &mut Vec<Block>) -> Result<()> {
         match &node.data.borrow().value {
             NodeValue::Document => {
                 // Process all children
                 for child in node.children() {
-                    self.process_node_to_paragraphs(child, paragraphs)?;
+                    self.process_node_to_blocks(child, blocks)?;
                 }
             }
             NodeValue::Heading(heading) => {
-                let level = heading.level;
                 let text = self.extract_text_from_node(node)?;
-                
-                let paragraph = match level {
-                    1 => Paragraph::new().add_run(Run::new().add_text(&text).size(32).bold()),
-                    2 => Paragraph::new().add_run(Run::new().add_text(&text).size(28).bold()),
-                    3 => Paragraph::new().add_run(Run::new().add_text(&text).size(24).bold()),
-                    4 => Paragraph::new().add_run(Run::new().add_text(&text).size(22).bold()),
-                    5 => Paragraph::new().add_run(Run::new().add_text(&text).size(20).bold()),
-                    _ => Paragraph::new().add_run(Run::new().add_text(&text).size(18).bold()),
-                };
-                
-                paragraphs.push(paragraph);
+                let paragraph = Paragraph::new().style(&docx_styles::heading(heading.level));
+                let paragraph = self.add_inline_children(paragraph, node, RunStyle::default())?;
+
+                blocks.push(Block::Paragraph(Box::new(self.bookmark_heading(paragraph, &text))));
             }
-            NodeValue::Paragraph => {
-                let mut paragraph = Paragraph::new();
-                for child in node.children() {
-                    self.process_inline_node(&mut paragraph, child)?;
+            NodeValue::Paragraph if self.extract_text_from_node(node)?.trim() == "[TOC]" => {
+                // Same marker the HTML path replaces; dropped when the TOC is disabled
+                if self.config.toc.enabled {
+                    blocks.push(Block::TableOfContents(Box::new(self.table_of_contents())));
                 }
-                paragraphs.push(paragraph);
             }
-            NodeValue::List(list_data) => {
-                for (i, child) in node.children().enumerate() {
-                    if let NodeValue::Item(_) = &child.data.borrow().value {
-                        let text = self.extract_text_from_node(child)?;
-                        let paragraph = if list_data.list_type == comrak::nodes::ListType::Ordered {
-                            Paragraph::new()
-                                .add_run(Run::new().add_text(&format!("{}. {}", i + 1, text)))
-                                .indent(Some(720), None, None, None) // Indent list items
-                        } else {
-                            Paragraph::new()
-                                .add_run(Run::new().add_text(&format!("• {}", text)))
-                                .indent(Some(720), None, None, None) // Indent list items
-                        };
-                        paragraphs.push(paragraph);
-                    }
-                }
+            NodeValue::Paragraph => {
+                let paragraph = Paragraph::new().style(docx_styles::BODY);
+                let paragraph = self.add_inline_children(paragraph, node, RunStyle::default())?;
+                blocks.push(Block::Paragraph(Box::new(paragraph)));
+            }
+            NodeValue::List(list) => {
+                self.process_list(node, list, 0, blocks)?;
             }
             NodeValue::CodeBlock(code_block) => {
-                let code_text = &code_block.literal;
-                let _language = &code_block.info;
-                
-                let code_paragraph = Paragraph::new()
-                    .add_run(
-                        Run::new()
-                            .add_text(code_text)
-                            .fonts(RunFonts::new().ascii("Consolas").hi_ansi("Consolas"))
-                            .size(20)
-                    );
-                    
-                paragraphs.push(code_paragraph);
+                let language = code_block.info.split_whitespace().next().unwrap_or("");
+                let code_paragraph = self.build_code_block(&code_block.literal, language);
+                blocks.push(Block::Paragraph(Box::new(code_paragraph)));
             }
-            NodeValue::Table(_) => {
-                // For now, convert table to simple text representation
-                let table_text = self.extract_text_from_node(node)?;
-                let table_paragraph = Paragraph::new()
-                    .add_run(Run::new().add_text(&table_text));
-                paragraphs.push(table_paragraph);
+            NodeValue::Table(table) => {
+                blocks.push(Block::Table(Box::new(self.build_table(node, table)?)));
+                // Keep consecutive tables from merging into one
+                blocks.push(Block::Paragraph(Box::new(Paragraph::new())));
             }
             NodeValue::ThematicBreak => {
                 // Add a horizontal rule as a simple line
                 let hr_paragraph = Paragraph::new()
+                    .style(docx_styles::BODY)
                     .add_run(Run::new().add_text("___________________________________"));
-                paragraphs.push(hr_paragraph);
+                blocks.push(Block::Paragraph(Box::new(hr_paragraph)));
             }
             NodeValue::BlockQuote => {
-                let quote_text = self.extract_text_from_node(node)?;
-                let quote_paragraph = Paragraph::new()
-                    .add_run(Run::new().add_text(&quote_text).italic())
-                    .indent(Some(720), None, None, None); // Indent blockquotes
-                paragraphs.push(quote_paragraph);
+                for child in node.children() {
+                    if let NodeValue::Paragraph = &child.data.borrow().value {
+                        let paragraph = Paragraph::new().style(docx_styles::QUOTE);
+                        let paragraph = self.add_inline_children(paragraph, child, RunStyle::default())?;
+                        blocks.push(Block::Paragraph(Box::new(paragraph)));
+                    } else {
+                        self.process_node_to_blocks(child, blocks)?;
+                    }
+                }
             }
             _ => {
                 // Process children for other node types
                 for child in node.children() {
-                    self.process_node_to_paragraphs(child, paragraphs)?;
+                    self.process_node_to_blocks(child, blocks)?;
                 }
             }
         }
         Ok(())
     }
 
-    fn process_inline_node<'a>(&self, paragraph: &mut Paragraph, node: &'a AstNode<'a>) -> Result<()> {
-        match &node.data.borrow().value {
-            NodeValue::Text(text) => {
-                *paragraph = paragraph.clone().add_run(Run::new().add_text(text));
+    /// Code block as a single Code paragraph with one coloured run per syntect
+    /// token, prefixed by line numbers when `code.line_numbers` is set
+    fn build_code_block(&self, code: &str, language: &str) -> Paragraph {
+        let theme = syntax_highlighting::find_theme(&self.config.code.highlight_theme, CODE_THEME);
+        // Light themes sit on the Code style's shading; dark ones bring their own background
+        let background = theme.settings.background
+            .map(hex_color)
+            .filter(|color| docx_styles::is_dark(&format!("#{color}")));
+        let gutter = theme.settings.gutter_foreground.map(hex_color).unwrap_or_else(|| "6A737D".to_string());
+        let mut highlighter = HighlightLines::new(syntax_highlighting::find_syntax(language), theme);
+
+        let code = code.trim_end_matches('\n');
+        let width = code.lines().count().max(1).to_string().len();
+        let shaded = |run: Run| match &background {
+            Some(fill) => run.shading(Shading::new().shd_type(ShdType::Clear).fill(fill)),
+            None => run,
+        };
+
+        let mut paragraph = Paragraph::new().style(docx_styles::CODE);
+        for (i, line) in LinesWithEndings::from(code).enumerate() {
+            // Word ignores newlines inside text, so break at each line
+            if i > 0 {
+                paragraph = paragraph.add_run(Run::new().add_break(BreakType::TextWrapping));
             }
-            NodeValue::Strong => {
-                let text = self.extract_text_from_node(node)?;
-                *paragraph = paragraph.clone().add_run(Run::new().add_text(&text).bold());
+            if self.config.code.line_numbers {
+                paragraph = paragraph.add_run(shaded(Run::new()
+                    .add_text(format!("{:>width$}  ", i + 1))
+                    .color(&gutter)));
             }
-            NodeValue::Emph => {
-                let text = self.extract_text_from_node(node)?;
-                *paragraph = paragraph.clone().add_run(Run::new().add_text(&text).italic());
+
+            let tokens = highlighter.highlight_line(line, &SYNTAX_SET).unwrap_or_else(|e| {
+                Logger::debug(format!("Highlighting failed for {language} code: {e}"));
+                vec![(TokenStyle::default(), line)]
+            });
+            for (style, text) in tokens {
+                let text = text.trim_end_matches(['\r', '\n']);
+                if text.is_empty() {
+                    continue;
+                }
+                let mut run = Run::new().add_text(text).color(hex_color(style.foreground));
+                if style.font_style.contains(FontStyle::BOLD) {
+                    run = run.bold();
+                }
+                if style.font_style.contains(FontStyle::ITALIC) {
+                    run = run.italic();
+                }
+                paragraph = paragraph.add_run(shaded(run));
             }
-            NodeValue::Code(code) => {
-                *paragraph = paragraph.clone().add_run(
-                    Run::new()
-                        .add_text(&code.literal)
-                        .fonts(RunFonts::new().ascii("Consolas").hi_ansi("Consolas"))
-                );
+        }
+
+        paragraph
+    }
+
+    /// Emit a list's items, numbering the first paragraph of each and
+    /// recursing into nested lists one level deeper
+    fn process_list<'a>(&self, node: &'a AstNode<'a>, list: &NodeList, level: usize, blocks: &mut Vec<Block>) -> Result<()> {
+        let numbering_id = self.start_numbering(list, level);
+        let indent = LIST_INDENT * (level as i32 + 1);
+
+        for item in node.children() {
+            let task = match &item.data.borrow().value {
+                NodeValue::TaskItem(mark) => Some(mark.is_some()),
+                _ => None,
+            };
+            let mut first_paragraph = true;
+
+            for child in item.children() {
+                match &child.data.borrow().value {
+                    NodeValue::List(nested) => self.process_list(child, nested, level + 1, blocks)?,
+                    NodeValue::Paragraph => {
+                        let mut paragraph = Paragraph::new().style(docx_styles::LIST);
+                        paragraph = match (first_paragraph, task) {
+                            (true, Some(checked)) => paragraph
+                                .indent(Some(indent), Some(SpecialIndentType::Hanging(360)), None, None)
+                                .add_run(Run::new().add_text(if checked { "☒" } else { "☐" }).add_tab()),
+                            (true, None) => paragraph
+                                .numbering(NumberingId::new(numbering_id), IndentLevel::new(level)),
+                            // Continuation paragraphs line up with the item text
+                            (false, _) => paragraph.indent(Some(indent), None, None, None),
+                        };
+                        first_paragraph = false;
+
+                        let paragraph = self.add_inline_children(paragraph, child, RunStyle::default())?;
+                        blocks.push(Block::Paragraph(Box::new(paragraph)));
+                    }
+                    _ => self.process_node_to_blocks(child, blocks)?,
+                }
             }
-            NodeValue::Link(link) => {
-                let link_text = self.extract_text_from_node(node)?;
-                // For now, just add as regular text with the URL in parentheses
-                *paragraph = paragraph.clone().add_run(
-                    Run::new().add_text(&format!("{} ({})", link_text, link.url))
-                        .color("0000FF") // Blue color for links
-                );
+        }
+
+        Ok(())
+    }
+
+    /// New numbering instance for a list, restarting ordered lists at their start number
+    fn start_numbering(&self, list: &NodeList, level: usize) -> usize {
+        let mut state = self.state.borrow_mut();
+        let id = state.numberings.len() + 2;
+
+        let numbering = match list.list_type {
+            ListType::Bullet => Numbering::new(id, BULLET_NUMBERING),
+            ListType::Ordered => Numbering::new(id, DECIMAL_NUMBERING)
+                .add_override(LevelOverride::new(level).start(list.start)),
+        };
+        state.numberings.push(numbering);
+        id
+    }
+
+    /// Nine indented levels, cycling bullet glyphs or decimal/letter/roman numbers like Word
+    fn list_levels(id: usize, list_type: ListType) -> AbstractNumbering {
+        let mut numbering = AbstractNumbering::new(id);
+
+        for level in 0..9 {
+            let (format, text) = match list_type {
+                ListType::Bullet => ("bullet", ["•", "◦", "▪"][level % 3].to_string()),
+                ListType::Ordered => (["decimal", "lowerLetter", "lowerRoman"][level % 3], format!("%{}.", level + 1)),
+            };
+            numbering = numbering.add_level(
+                Level::new(level, Start::new(1), NumberFormat::new(format), LevelText::new(text), LevelJc::new("left"))
+                    .indent(Some(LIST_INDENT * (level as i32 + 1)), Some(SpecialIndentType::Hanging(360)), None, None)
+            );
+        }
+
+        numbering
+    }
+
+    /// Wrap a heading in a bookmark named like its HTML id
+    fn bookmark_heading(&self, paragraph: Paragraph, text: &str) -> Paragraph {
+        let mut state = self.state.borrow_mut();
+        let name = state.anchorizer.anchorize(text.to_string());
+        state.bookmark_count += 1;
+        let id = state.bookmark_count;
+
+        // Children are public, so the bookmark can open before the existing runs
+        let mut paragraph = paragraph.add_bookmark_end(id);
+        paragraph.children.insert(0, ParagraphChild::BookmarkStart(BookmarkStart::new(id, name)));
+        paragraph
+    }
+
+    fn build_table<'a>(&self, node: &'a AstNode<'a>, table: &NodeTable) -> Result<Table> {
+        let mut rows = Vec::new();
+
+        for row in node.children() {
+            let is_header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+            let cell_style = RunStyle { bold: is_header, ..Default::default() };
+            let mut cells = Vec::new();
+
+            for (column, cell) in row.children().enumerate() {
+                let paragraph = Paragraph::new().style(docx_styles::BODY);
+                let mut paragraph = self.add_inline_children(paragraph, cell, cell_style)?;
+
+                paragraph = match table.alignments.get(column) {
+                    Some(TableAlignment::Left) => paragraph.align(AlignmentType::Left),
+                    Some(TableAlignment::Center) => paragraph.align(AlignmentType::Center),
+                    Some(TableAlignment::Right) => paragraph.align(AlignmentType::Right),
+                    _ => paragraph,
+                };
+
+                let mut table_cell = TableCell::new();
+                if is_header {
+                    table_cell = table_cell.shading(Shading::new().shd_type(ShdType::Clear).fill(&self.state.borrow().table_header_fill));
+                }
+                cells.push(table_cell.add_paragraph(paragraph));
             }
-            NodeValue::Strikethrough => {
-                let text = self.extract_text_from_node(node)?;
-                *paragraph = paragraph.clone().add_run(Run::new().add_text(&text).strike());
+
+            rows.push(TableRow::new(cells));
+        }
+
+        // Table::new draws single-line borders around every cell
+        Ok(Table::new(rows).width(5000, WidthType::Pct))
+    }
+
+    /// Embed a local image as an inline picture, scaled to fit the page
+    fn build_image(&self, url: &str) -> Result<Run> {
+        if url.starts_with("http://") || url.starts_with("https://") || url.starts_with("data:") {
+            anyhow::bail!("Only local images can be embedded in DOCX");
+        }
+
+        let image_path = if Path::new(url).is_absolute() {
+            PathBuf::from(url)
+        } else {
+            self.base_path.join(url)
+        };
+
+        let img = image::open(&image_path)
+            .with_context(|| format!("Failed to load image: {}", image_path.display()))?;
+        let img = ImageOptimizer::new(self.config.images.clone()).optimize_image(img)?;
+        let (width, height) = (img.width(), img.height());
+
+        let mut png = Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png)
+            .with_context(|| format!("Failed to encode image: {}", image_path.display()))?;
+
+        let max_width = self.content_width_emu();
+        let (mut width_emu, mut height_emu) = (width * EMU_PER_PIXEL, height * EMU_PER_PIXEL);
+        if width_emu > max_width {
+            height_emu = (height_emu as u64 * max_width as u64 / width_emu as u64) as u32;
+            width_emu = max_width;
+        }
+
+        let pic = Pic::new_with_dimensions(png.into_inner(), width, height).size(width_emu, height_emu);
+        Ok(Run::new().add_image(pic))
+    }
+
+    fn add_inline_children<'a>(&self, mut paragraph: Paragraph, node: &'a AstNode<'a>, style: RunStyle) -> Result<Paragraph> {
+        for child in node.children() {
+            self.collect_inline(child, style, &mut paragraph.children)?;
+        }
+        Ok(paragraph)
+    }
+
+    fn collect_inline<'a>(&self, node: &'a AstNode<'a>, style: RunStyle, out: &mut Vec<ParagraphChild>) -> Result<()> {
+        let run = |run: Run| ParagraphChild::Run(Box::new(run));
+
+        match &node.data.borrow().value {
+            NodeValue::Text(text) => out.push(run(style.run(text))),
+            NodeValue::Code(code) => {
+                let code_style = RunStyle { character_style: Some(docx_styles::CODE_CHAR), ..style };
+                out.push(run(code_style.run(&code.literal)));
             }
-            NodeValue::SoftBreak | NodeValue::LineBreak => {
-                *paragraph = paragraph.clone().add_run(Run::new().add_break(BreakType::TextWrapping));
+            NodeValue::Strong => return self.collect_inline_children(node, RunStyle { bold: true, ..style }, out),
+            NodeValue::Emph => return self.collect_inline_children(node, RunStyle { italic: true, ..style }, out),
+            NodeValue::Strikethrough => return self.collect_inline_children(node, RunStyle { strike: true, ..style }, out),
+            NodeValue::Superscript => return self.collect_inline_children(node, RunStyle { superscript: true, ..style }, out),
+            NodeValue::Link(link) => {
+                let mut hyperlink = match link.url.strip_prefix('#') {
+                    Some(anchor) => Hyperlink::new(anchor, HyperlinkType::Anchor),
+                    None => Hyperlink::new(&link.url, HyperlinkType::External),
+                };
+                let link_style = RunStyle { character_style: Some(docx_styles::HYPERLINK), ..style };
+                self.collect_inline_children(node, link_style, &mut hyperlink.children)?;
+                out.push(ParagraphChild::Hyperlink(hyperlink));
             }
-            _ => {
-                // Process children for other inline elements
-                for child in node.children() {
-                    self.process_inline_node(paragraph, child)?;
+            NodeValue::Image(image) => {
+                match self.build_image(&image.url) {
+                    Ok(image_run) => out.push(run(image_run)),
+                    Err(e) => {
+                        // Keep the alt text so the document still reads correctly
+                        Logger::warning(format!("Skipping image {}: {e}", image.url));
+                        let alt_text = self.extract_text_from_node(node)?;
+                        out.push(run(RunStyle { italic: true, ..style }.run(&format!("[{alt_text}]"))));
+                    }
                 }
             }
+            // Soft breaks are line endings inside a paragraph, not visible breaks
+            NodeValue::SoftBreak => out.push(run(style.run(" "))),
+            NodeValue::LineBreak => out.push(run(style.apply(Run::new().add_break(BreakType::TextWrapping)))),
+            _ => return self.collect_inline_children(node, style, out),
+        }
+        Ok(())
+    }
+
+    fn collect_inline_children<'a>(&self, node: &'a AstNode<'a>, style: RunStyle, out: &mut Vec<ParagraphChild>) -> Result<()> {
+        for child in node.children() {
+            self.collect_inline(child, style, out)?;
         }
         Ok(())
     }
@@ -293,4 +799,111 @@ impl DocxConverter {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// syntect colour → `RRGGBB`
+fn hex_color(color: Color) -> String {
+    format!("{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn document_xml(markdown: &str) -> String {
+        docx_part(DocxConverter::new(Config::default()), markdown, "word/document.xml")
+    }
+
+    fn docx_part(converter: DocxConverter, markdown: &str, part: &str) -> String {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        converter.convert_markdown(markdown, output.path()).unwrap();
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(output.path()).unwrap()).unwrap();
+        let mut xml = String::new();
+        archive.by_name(part).unwrap().read_to_string(&mut xml).unwrap();
+        xml
+    }
+
+    #[test]
+    fn test_tables_and_hyperlinks_are_native() {
+        let xml = document_xml("# Setup Guide\n\n[site](https://example.com), [top](#setup-guide)\n\n| Name | Size |\n|------|-----:|\n| a | 1 |\n");
+
+        assert_eq!(xml.matches("<w:tr>").count(), 2);
+        assert!(xml.contains(r#"<w:hyperlink w:anchor="setup-guide""#));
+        assert!(xml.contains(r#"w:name="setup-guide""#));
+        assert!(xml.contains(r#"<w:jc w:val="right" />"#));
+        assert!(!xml.contains("(https://example.com)"));
+    }
+
+    #[test]
+    fn test_nested_lists_and_inline_styles() {
+        let xml = document_xml("1. ***Both*** and `code`
+   - [x] done
+   - [ ] todo
+2. Second
+");
+
+        assert_eq!(xml.matches(r#"<w:numId w:val="2" />"#).count(), 2);
+        assert!(xml.contains(r#"<w:ind w:left="1440" w:right="0" w:hanging="360" />"#));
+        assert!(xml.contains("<w:b /><w:bCs /><w:i /><w:iCs />"));
+        assert!(xml.contains(r#"<w:rStyle w:val="CodeChar" />"#));
+        assert!(xml.contains("☒") && xml.contains("☐"));
+    }
+
+    #[test]
+    fn test_page_number_footer_and_toc_field() {
+        let mut config = Config::default();
+        config.toc.enabled = true;
+        config.page.page_numbers = Some(crate::config::PageNumberConfig {
+            enabled: true,
+            format: "{page} / {total}".to_string(),
+            position: PageNumberPosition::Footer,
+            start_from: Some(5),
+        });
+        let converter = || DocxConverter::new(config.clone())
+            .with_metadata(DocumentMetadata { title: Some("Report".to_string()), ..Default::default() });
+
+        let footer = docx_part(converter(), "# A\n", "word/footer1.xml");
+        assert!(footer.contains("<w:instrText>PAGE</w:instrText>"));
+        assert!(footer.contains("<w:instrText>NUMPAGES</w:instrText>"));
+        assert!(footer.contains(r#"<w:t xml:space="preserve"> / </w:t>"#));
+
+        let xml = docx_part(converter(), "[TOC]\n\n# A\n\n## B\n", "word/document.xml");
+        assert!(xml.contains(r#"<w:instrText>TOC \o &quot;1-3&quot; \h</w:instrText>"#));
+        assert!(xml.contains(r#"<w:pgNumType w:start="5" />"#));
+        assert!(!xml.contains("[TOC]"));
+    }
+
+    #[test]
+    fn test_header_tab_stops_span_the_text_width() {
+        let mut config = Config::default();
+        config.page.size.preset = Some("Letter".to_string());
+        config.page.header = Some(HeaderFooterConfig {
+            enabled: true,
+            slots: HeaderFooterSlots { left: "Report".to_string(), right: "{page}".to_string(), ..Default::default() },
+            ..Default::default()
+        });
+
+        // 8.5in less two 1in margins
+        let header = docx_part(DocxConverter::new(config.clone()), "# A\n", "word/header1.xml");
+        assert!(header.contains(r#"<w:tab w:val="center" w:pos="4680" />"#));
+        assert!(header.contains(r#"<w:tab w:val="right" w:pos="9360" />"#));
+
+        config.page.orientation = Orientation::Landscape;
+        let header = docx_part(DocxConverter::new(config), "# A\n", "word/header1.xml");
+        assert!(header.contains(r#"<w:tab w:val="right" w:pos="12960" />"#));
+    }
+
+    #[test]
+    fn test_code_blocks_are_highlighted() {
+        let mut config = Config::default();
+        config.code.line_numbers = true;
+        let xml = docx_part(DocxConverter::new(config), "```rust\nfn main() {}\n// done\n```\n", "word/document.xml");
+
+        // InspiredGitHub keyword colour, bold
+        assert!(xml.contains(r#"<w:color w:val="A71D5D" /><w:b /><w:bCs /></w:rPr><w:t xml:space="preserve">fn</w:t>"#));
+        assert!(xml.contains(r#"<w:t xml:space="preserve">2  </w:t>"#));
+        assert_eq!(xml.matches(r#"<w:br w:type="textWrapping" />"#).count(), 1);
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{Context, Result};
+use docx_rs::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::themes::ThemeManager;
+
+// Style ids match Word's built-in styles so a reference document can restyle them.
+// docx-rs always writes a bare "Normal", so body text uses "Body Text" instead.
+pub const NORMAL: &str = "Normal";
+pub const BODY: &str = "BodyText";
+pub const CODE: &str = "Code";
+pub const CODE_CHAR: &str = "CodeChar";
+pub const QUOTE: &str = "Quote";
+pub const LIST: &str = "ListParagraph";
+pub const HYPERLINK: &str = "Hyperlink";
+pub const HEADER: &str = "Header";
+pub const FOOTER: &str = "Footer";
+pub const TOC_HEADING: &str = "TOCHeading";
+
+pub fn heading(level: u8) -> String {
+    format!("Heading{}", level.clamp(1, 6))
+}
+
+lazy_static! {
+    static ref CSS_VAR_REGEX: Regex = Regex::new(r"--([\w-]+)\s*:\s*([^;]+);").expect("Invalid CSS variable regex");
+}
+
+/// Font sizes of Heading 1-6 relative to body text, as in the HTML themes
+const HEADING_SCALE: [f64; 6] = [2.0, 1.5, 1.25, 1.1, 1.0, 0.9];
+
+/// Vendor aliases and generic families that Word cannot resolve
+const NON_WORD_FONTS: [&str; 9] = [
+    "-apple-system", "BlinkMacSystemFont", "SFMono-Regular", "SF Mono",
+    "serif", "sans-serif", "monospace", "Apple Color Emoji", "Segoe UI Emoji",
+];
+
+/// Colours and fonts for the DOCX styles part, taken from the selected
+/// theme's `:root` variables and `FontConfig`
+#[derive(Debug, Clone)]
+pub struct DocxTheme {
+    pub body_font: String,
+    pub mono_font: String,
+    pub size: usize, // Half-points
+    pub line_height: Option<f64>,
+    pub text_color: String,
+    pub heading_color: String,
+    pub link_color: String,
+    pub code_background: String,
+    pub quote_color: String,
+    pub quote_border: String,
+    pub table_header_background: String,
+}
+
+impl DocxTheme {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let theme_manager = ThemeManager::new();
+        let mut css = theme_manager.resolve_theme(
+            config.theme.built_in.as_deref(),
+            config.theme.css_file.as_deref(),
+        )?;
+
+        // Word pages are white, so dark themes fall back to the default palette
+        if css_variable(&css, "color-bg").is_some_and(|bg| is_dark(&bg)) {
+            Logger::verbose("Dark theme detected; using the default palette for DOCX");
+            css = theme_manager.resolve_theme(None, None)?;
+        }
+
+        let color = |name: &str, fallback: &str| css_variable(&css, name)
+            .and_then(|value| hex_color(&value))
+            .unwrap_or_else(|| fallback.to_string());
+        let font = |names: &[&str], fallback: &str| names.iter()
+            .find_map(|name| css_variable(&css, name).and_then(|value| word_font(&value)))
+            .unwrap_or_else(|| fallback.to_string());
+
+        let text_color = color("color-text", "000000");
+
+        Ok(Self {
+            body_font: config.fonts.family.as_deref()
+                .and_then(word_font)
+                .unwrap_or_else(|| font(&["font-family-serif", "font-family-sans"], "Calibri")),
+            mono_font: font(&["font-family-mono"], "Consolas"),
+            size: config.fonts.size.as_deref().and_then(half_points).unwrap_or(22),
+            line_height: config.fonts.line_height,
+            heading_color: color("color-primary", &text_color),
+            link_color: color("color-link", "0563C1"),
+            code_background: color("color-pre-bg", "F6F8FA"),
+            quote_color: color("color-quote-text", "6A737D"),
+            quote_border: color("color-quote-border", "DFE2E5"),
+            table_header_background: color("table-header-bg", "D9D9D9"),
+            text_color,
+        })
+    }
+
+    /// Body Text, Heading 1-6, Code, Quote, List Paragraph, Hyperlink,
+    /// Header/Footer and TOC Heading styles
+    pub fn styles(&self) -> Styles {
+        let body_fonts = RunFonts::new().ascii(&self.body_font).hi_ansi(&self.body_font).cs(&self.body_font);
+        let mono_fonts = RunFonts::new().ascii(&self.mono_font).hi_ansi(&self.mono_font).cs(&self.mono_font);
+
+        let mut styles = Styles::new()
+            .default_fonts(body_fonts)
+            .default_size(self.size);
+        if let Some(line_height) = self.line_height {
+            styles = styles.default_line_spacing(LineSpacing::new()
+                .line((line_height * 240.0) as i32)
+                .line_rule(LineSpacingType::Auto));
+        }
+
+        styles = styles.add_style(Style::new(BODY, StyleType::Paragraph)
+            .name("Body Text")
+            .based_on(NORMAL)
+            .color(&self.text_color)
+            .line_spacing(LineSpacing::new().after(160)));
+
+        for level in 1..=6u8 {
+            let size = (self.size as f64 * HEADING_SCALE[level as usize - 1]).round() as usize;
+            styles = styles.add_style(Style::new(heading(level), StyleType::Paragraph)
+                .name(format!("Heading {level}"))
+                .based_on(NORMAL)
+                .next(BODY)
+                .size(size)
+                .bold()
+                .color(&self.heading_color)
+                .outline_lvl(level as usize - 1)
+                .line_spacing(LineSpacing::new().before(if level == 1 { 360 } else { 240 }).after(120)));
+        }
+
+        let mut code = Style::new(CODE, StyleType::Paragraph)
+            .name("Code")
+            .based_on(BODY)
+            .link(CODE_CHAR)
+            .fonts(mono_fonts.clone())
+            .size(self.size.saturating_sub(2).max(16))
+            .line_spacing(LineSpacing::new().before(0).after(160).line(240).line_rule(LineSpacingType::Auto));
+        code.run_property = code.run_property.shading(Shading::new().shd_type(ShdType::Clear).fill(&self.code_background));
+        // docx-rs has no paragraph shading, so a padded border frames the shaded runs
+        for position in [ParagraphBorderPosition::Top, ParagraphBorderPosition::Left, ParagraphBorderPosition::Bottom, ParagraphBorderPosition::Right] {
+            code.paragraph_property = code.paragraph_property.set_border(
+                ParagraphBorder::new(position)
+                    .val(BorderType::Single)
+                    .size(4)
+                    .space(4)
+                    .color(&self.quote_border)
+            );
+        }
+
+        let mut quote = Style::new(QUOTE, StyleType::Paragraph)
+            .name("Quote")
+            .based_on(BODY)
+            .next(BODY)
+            .italic()
+            .color(&self.quote_color)
+            .indent(Some(720), None, None, None);
+        quote.paragraph_property = quote.paragraph_property.set_border(
+            ParagraphBorder::new(ParagraphBorderPosition::Left)
+                .val(BorderType::Single)
+                .size(18)
+                .space(8)
+                .color(&self.quote_border)
+        );
+
+        styles
+            .add_style(code)
+            .add_style(Style::new(CODE_CHAR, StyleType::Character)
+                .name("Code Char")
+                .link(CODE)
+                .fonts(mono_fonts))
+            .add_style(quote)
+            .add_style(Style::new(LIST, StyleType::Paragraph)
+                .name("List Paragraph")
+                .based_on(BODY)
+                .indent(Some(720), None, None, None)
+                .line_spacing(LineSpacing::new().after(60)))
+            .add_style(Style::new(HYPERLINK, StyleType::Character)
+                .name("Hyperlink")
+                .color(&self.link_color)
+                .underline("single"))
+            .add_style(Style::new(HEADER, StyleType::Paragraph)
+                .name("header")
+                .based_on(NORMAL)
+                .size(self.size.saturating_sub(4).max(14))
+                .color(&self.quote_color)
+                .align(AlignmentType::Center))
+            .add_style(Style::new(FOOTER, StyleType::Paragraph)
+                .name("footer")
+                .based_on(HEADER))
+            // Outline level 9 is body text, keeping the contents title out of the TOC itself
+            .add_style(Style::new(TOC_HEADING, StyleType::Paragraph)
+                .name("TOC Heading")
+                .based_on(heading(1))
+                .next(BODY)
+                .outline_lvl(9))
+    }
+}
+
+/// Take styles and document defaults from a user-supplied `.docx`/`.dotx`,
+/// keeping ours for any style id the reference document does not define.
+/// Its "Normal" is dropped because docx-rs writes its own.
+pub fn reference_styles(path: &Path, generated: Styles) -> Result<Styles> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read reference document: {}", path.display()))?;
+    let reference = read_docx(&bytes)
+        .with_context(|| format!("Failed to parse reference document: {}", path.display()))?;
+
+    let mut styles = reference.styles;
+    styles.styles.retain(|style| style.style_id != NORMAL);
+    for style in generated.styles {
+        if styles.find_style_by_id(&style.style_id).is_none() {
+            styles = styles.add_style(style);
+        }
+    }
+
+    Ok(styles)
+}
+
+fn css_variable(css: &str, name: &str) -> Option<String> {
+    CSS_VAR_REGEX.captures_iter(css)
+        .find(|caps| &caps[1] == name)
+        .map(|caps| caps[2].trim().to_string())
+}
+
+/// `#abc` / `#aabbcc` → `AABBCC`; other colour syntaxes are ignored
+fn hex_color(value: &str) -> Option<String> {
+    let hex = value.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    match hex.len() {
+        3 => Some(hex.chars().flat_map(|c| [c, c]).collect::<String>().to_uppercase()),
+        6 => Some(hex.to_uppercase()),
+        _ => None,
+    }
+}
+
+pub fn is_dark(value: &str) -> bool {
+    let Some(hex) = hex_color(value) else {
+        return false;
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(255) as f64;
+    // Rec. 601 luma
+    0.299 * channel(0) + 0.587 * channel(2) + 0.114 * channel(4) < 128.0
+}
+
+/// First family in a CSS font stack that Word can use
+fn word_font(stack: &str) -> Option<String> {
+    stack.split(',')
+        .map(|family| family.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .find(|family| !family.is_empty() && !NON_WORD_FONTS.contains(&family.as_str()))
+}
+
+/// CSS font size (`12pt`, `16px`) → half-points
+pub fn half_points(size: &str) -> Option<usize> {
+    let size = size.trim().to_lowercase();
+    let points = if let Some(pt) = size.strip_suffix("pt") {
+        pt.trim().parse::<f64>().ok()?
+    } else if let Some(px) = size.strip_suffix("px") {
+        px.trim().parse::<f64>().ok()? * 0.75
+    } else {
+        return None;
+    };
+
+    Some((points * 2.0).round() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_colours_and_fonts() {
+        let mut config = Config::default();
+        config.theme.built_in = Some("modern".to_string());
+        config.fonts.family = None;
+        config.fonts.size = Some("12pt".to_string());
+
+        let theme = DocxTheme::from_config(&config).unwrap();
+        assert_eq!(theme.body_font, "Inter");
+        assert_eq!(theme.mono_font, "JetBrains Mono");
+        assert_eq!(theme.size, 24);
+        assert_eq!(theme.heading_color, "2563EB");
+
+        config.theme.built_in = Some("dark".to_string());
+        let theme = DocxTheme::from_config(&config).unwrap();
+        assert_eq!(theme.text_color, "24292E");
+        assert_eq!(theme.body_font, "Segoe UI");
+    }
+}
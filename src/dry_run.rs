@@ -1,10 +1,19 @@
 use anyhow::{Result, Context};
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
+use crate::backends::{Backend, Capabilities};
 use crate::config::Config;
 use crate::markdown_validator::{MarkdownValidator, ValidationResult};
 use crate::logger::Logger;
 
+lazy_static! {
+    // Display math or a fenced `math` block, which every math-capable backend renders
+    static ref MATH_REGEX: Regex = Regex::new(r"(?m)\$\$|^\s*(```|~~~)\s*math\b").expect("Invalid math regex");
+    static ref MERMAID_REGEX: Regex = Regex::new(r"(?m)^\s*(```|~~~)\s*mermaid\b").expect("Invalid mermaid regex");
+}
+
 pub struct DryRunResult {
     pub files_to_process: Vec<FileAnalysis>,
     pub total_size_bytes: u64,
@@ -29,18 +38,25 @@ pub struct FileAnalysis {
 }
 
 pub struct DryRunProcessor {
-    #[allow(dead_code)]
     config: Config,
     validate_markdown: bool,
     check_overwrite: bool,
+    format: &'static str,
+    extension: &'static str,
+    capabilities: Capabilities,
 }
 
 impl DryRunProcessor {
-    pub fn new(config: Config) -> Self {
+    /// Preview a conversion through `backend`, which decides output names and
+    /// which Markdown features will be rendered
+    pub fn new(config: Config, backend: &dyn Backend) -> Self {
         Self {
             config,
             validate_markdown: true,
             check_overwrite: true,
+            format: backend.name(),
+            extension: backend.extension(),
+            capabilities: backend.capabilities(),
         }
     }
     
@@ -56,7 +72,7 @@ impl DryRunProcessor {
     }
     
     pub fn analyze_single_file(&self, input_path: &Path, output_path: &Path) -> Result<DryRunResult> {
-        Logger::dry_run(&format!("Analyzing single file: {}", input_path.display()));
+        Logger::dry_run(format!("Analyzing single file: {} ({} output)", input_path.display(), self.format.to_uppercase()));
         
         let mut files_to_process = Vec::new();
         let mut validation_results = Vec::new();
@@ -83,7 +99,7 @@ impl DryRunProcessor {
         
         // Validate markdown if enabled
         if self.validate_markdown {
-            Logger::validation(&format!("Validating markdown: {}", input_path.display()));
+            Logger::validation(format!("Validating markdown: {}", input_path.display()));
             let validator = MarkdownValidator::new().with_base_path(
                 input_path.parent().unwrap_or(Path::new("."))
             );
@@ -117,7 +133,7 @@ impl DryRunProcessor {
     }
     
     pub fn analyze_batch(&self, input_dir: &Path, output_dir: &Path) -> Result<DryRunResult> {
-        Logger::dry_run(&format!("Analyzing batch processing: {}", input_dir.display()));
+        Logger::dry_run(format!("Analyzing batch processing: {} ({} output)", input_dir.display(), self.format.to_uppercase()));
         
         let mut files_to_process = Vec::new();
         let mut validation_results = Vec::new();
@@ -143,7 +159,7 @@ impl DryRunProcessor {
         let mut markdown_files = Vec::new();
         for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "md") {
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
                 markdown_files.push(path.to_path_buf());
             }
         }
@@ -160,13 +176,13 @@ impl DryRunProcessor {
             });
         }
         
-        Logger::dry_run(&format!("Found {} markdown files to process", markdown_files.len()));
+        Logger::dry_run(format!("Found {} markdown files to process", markdown_files.len()));
         
         // Analyze each file
         for input_path in markdown_files {
             let relative_path = input_path.strip_prefix(input_dir)
                 .context("Failed to calculate relative path")?;
-            let output_path = output_dir.join(relative_path).with_extension("pdf");
+            let output_path = output_dir.join(relative_path).with_extension(self.extension);
             
             match self.analyze_file(&input_path, &output_path) {
                 Ok(analysis) => {
@@ -285,14 +301,46 @@ impl DryRunProcessor {
             }
         }
         
+        self.check_backend_support(analysis, warnings);
+
         // Check file permissions (simplified)
-        if analysis.input_path.metadata().map_or(false, |m| m.permissions().readonly()) {
+        if analysis.input_path.metadata().is_ok_and(|m| m.permissions().readonly()) {
             warnings.push(format!(
                 "Input file is read-only: {}",
                 analysis.input_path.display()
             ));
         }
     }
+
+    /// Warn about content the selected backend will not render
+    fn check_backend_support(&self, analysis: &FileAnalysis, warnings: &mut Vec<String>) {
+        let mut unsupported = Vec::new();
+        let theme = &self.config.theme;
+        let styled = theme.css_file.is_some() || theme.built_in.as_deref().is_some_and(|name| name != "default");
+        if !self.capabilities.themes && styled {
+            unsupported.push("theme");
+        }
+
+        if !self.capabilities.math || !self.capabilities.diagrams {
+            if let Ok(content) = std::fs::read_to_string(&analysis.input_path) {
+                if !self.capabilities.math && MATH_REGEX.is_match(&content) {
+                    unsupported.push("math");
+                }
+                if !self.capabilities.diagrams && MERMAID_REGEX.is_match(&content) {
+                    unsupported.push("Mermaid diagrams");
+                }
+            }
+        }
+
+        if !unsupported.is_empty() {
+            warnings.push(format!(
+                "{}: {} output does not render {}",
+                analysis.input_path.display(),
+                self.format.to_uppercase(),
+                unsupported.join(", ")
+            ));
+        }
+    }
 }
 
 impl DryRunResult {
@@ -0,0 +1,462 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use crate::advanced_processing::{AdvancedProcessor, TocEntry};
+use crate::config::{Config, DocumentMetadata};
+use crate::logger::Logger;
+use crate::pdf_metadata::DEFAULT_CREATOR;
+
+lazy_static! {
+    static ref H1_REGEX: Regex = Regex::new(r"<h1[\s>]").expect("Invalid H1 regex");
+    static ref BOOK_SECTION_REGEX: Regex = Regex::new(r#"<section class="book-(?:part|chapter|front-matter|appendix)""#).expect("Invalid book section regex");
+    static ref ID_REGEX: Regex = Regex::new(r#"\sid="([^"]+)""#).expect("Invalid id regex");
+    static ref FRAGMENT_LINK_REGEX: Regex = Regex::new(r##"href="#([^"]+)""##).expect("Invalid fragment link regex");
+    static ref IMG_SRC_REGEX: Regex = Regex::new(r#"(<img\b[^>]*?\ssrc=")([^"]+)""#).expect("Invalid image regex");
+}
+
+/// Elements that XHTML writes as `<name/>`
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+// dc:language is mandatory, and documents carry no language of their own yet
+const LANGUAGE: &str = "en";
+
+/// An image copied into the package
+struct Resource {
+    href: String,
+    media_type: String,
+    data: Vec<u8>,
+}
+
+/// Packages processed HTML chapters, the theme CSS and their images as an
+/// EPUB 3 container with a navigation document built from the headings
+pub struct EpubWriter {
+    config: Config,
+    metadata: DocumentMetadata,
+    css: String,
+    base_path: PathBuf,
+}
+
+impl EpubWriter {
+    pub fn new(config: Config, metadata: DocumentMetadata) -> Self {
+        Self {
+            config,
+            metadata,
+            css: String::new(),
+            base_path: PathBuf::from("."),
+        }
+    }
+
+    pub fn with_css(mut self, css: String) -> Self {
+        self.css = css;
+        self
+    }
+
+    /// Directory that relative image paths are resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
+    }
+
+    /// The finished `.epub` archive
+    pub fn package(&self, chapters: &[String]) -> Result<Vec<u8>> {
+        let chapters: Vec<&String> = chapters.iter().filter(|c| !c.trim().is_empty()).collect();
+        if chapters.is_empty() {
+            anyhow::bail!("Document has no content to package as EPUB");
+        }
+        let file_name = |i: usize| format!("chapter-{}.xhtml", i + 1);
+
+        // Splitting breaks `#id` links between chapters, so point them at the owning file
+        let mut owners = HashMap::new();
+        for (i, chapter) in chapters.iter().enumerate() {
+            for caps in ID_REGEX.captures_iter(chapter) {
+                owners.entry(caps[1].to_string()).or_insert_with(|| file_name(i));
+            }
+        }
+
+        let processor = AdvancedProcessor::new(self.config.clone());
+        let mut resources: Vec<Resource> = Vec::new();
+        let mut image_hrefs: HashMap<String, String> = HashMap::new();
+        let mut documents = Vec::new();
+        let mut nav_entries = Vec::new();
+
+        for (i, chapter) in chapters.iter().enumerate() {
+            let linked = FRAGMENT_LINK_REGEX.replace_all(chapter, |caps: &regex::Captures| {
+                match owners.get(&caps[1]) {
+                    Some(owner) if *owner != file_name(i) => format!(r##"href="{owner}#{}""##, &caps[1]),
+                    _ => caps[0].to_string(),
+                }
+            });
+
+            let mut remote = false;
+            let body = IMG_SRC_REGEX.replace_all(&linked, |caps: &regex::Captures| {
+                let src = html_escape::decode_html_entities(&caps[2]).into_owned();
+                if src.starts_with("http://") || src.starts_with("https://") {
+                    remote = true;
+                    return caps[0].to_string();
+                }
+                if let Some(href) = image_hrefs.get(&src) {
+                    return format!(r#"{}{href}""#, &caps[1]);
+                }
+                match self.load_image(&src, resources.len() + 1) {
+                    Ok(resource) => {
+                        let href = resource.href.clone();
+                        image_hrefs.insert(src, href.clone());
+                        resources.push(resource);
+                        format!(r#"{}{href}""#, &caps[1])
+                    }
+                    Err(e) => {
+                        Logger::warning(format!("Leaving image out of the EPUB: {e}"));
+                        caps[0].to_string()
+                    }
+                }
+            }).into_owned();
+
+            let headings = processor.collect_headings(&body)?;
+            let title = headings.first()
+                .map(|heading| heading.text.clone())
+                .or_else(|| self.metadata.title.clone())
+                .unwrap_or_else(|| format!("Chapter {}", i + 1));
+            if headings.is_empty() {
+                nav_entries.push(NavPoint { level: 1, href: file_name(i), text: title.clone() });
+            }
+            nav_entries.extend(headings.iter().map(|heading: &TocEntry| NavPoint {
+                level: heading.level,
+                href: format!("{}#{}", file_name(i), heading.id),
+                text: heading.text.clone(),
+            }));
+
+            documents.push((file_name(i), xhtml_document(&title, &to_xhtml(&body)), remote));
+        }
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // The mimetype entry must come first and be stored uncompressed
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package_document(&documents, &resources, &chapters).as_bytes())?;
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.nav_document(&nav_entries).as_bytes())?;
+        zip.start_file("OEBPS/style.css", deflated)?;
+        zip.write_all(self.css.as_bytes())?;
+        for (name, xhtml, _) in &documents {
+            zip.start_file(format!("OEBPS/{name}"), deflated)?;
+            zip.write_all(xhtml.as_bytes())?;
+        }
+        for resource in &resources {
+            zip.start_file(format!("OEBPS/{}", resource.href), stored)?;
+            zip.write_all(&resource.data)?;
+        }
+
+        let archive = zip.finish().context("Failed to write EPUB archive")?;
+
+        Logger::verbose(format!("Packaged {} chapters and {} images", documents.len(), resources.len()));
+        Ok(archive.into_inner())
+    }
+
+    /// Copy an image into the package, decoding the data URLs the image
+    /// optimizer produces and reading anything else from disk
+    fn load_image(&self, src: &str, index: usize) -> Result<Resource> {
+        let (media_type, data) = if let Some(data_url) = src.strip_prefix("data:") {
+            let (media_type, encoded) = data_url.split_once(";base64,")
+                .context("Unsupported data URL")?;
+            (media_type.to_string(), STANDARD.decode(encoded.trim()).context("Invalid base64 image data")?)
+        } else {
+            let path = self.base_path.join(src);
+            let data = fs::read(&path)
+                .with_context(|| format!("Failed to read image: {}", path.display()))?;
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+            (media_type_for(&extension).to_string(), data)
+        };
+
+        let extension = match media_type.as_str() {
+            "image/jpeg" => "jpg",
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/svg+xml" => "svg",
+            "image/webp" => "webp",
+            other => anyhow::bail!("Unsupported image type for EPUB: {other}"),
+        };
+
+        Ok(Resource {
+            href: format!("images/image-{index}.{extension}"),
+            media_type,
+            data,
+        })
+    }
+
+    fn package_document(&self, documents: &[(String, String, bool)], resources: &[Resource], chapters: &[&String]) -> String {
+        let mut hasher = Sha256::new();
+        for chapter in chapters {
+            hasher.update(chapter.as_bytes());
+        }
+        let identifier = format!("urn:papercraft:{}", &hex::encode(hasher.finalize())[..32]);
+        let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+
+        let mut metadata = format!(
+            "<dc:identifier id=\"book-id\">{identifier}</dc:identifier>\n    <dc:title>{}</dc:title>\n    <dc:language>{LANGUAGE}</dc:language>\n    <meta property=\"dcterms:modified\">{modified}</meta>\n",
+            xml_escape(self.metadata.title.as_deref().unwrap_or("Document"))
+        );
+        for name in &self.metadata.authors {
+            metadata.push_str(&format!("    <dc:creator>{}</dc:creator>\n", xml_escape(name)));
+        }
+        if let Some(subject) = &self.metadata.subject {
+            metadata.push_str(&format!("    <dc:description>{}</dc:description>\n", xml_escape(subject)));
+        }
+        for keyword in &self.metadata.keywords {
+            metadata.push_str(&format!("    <dc:subject>{}</dc:subject>\n", xml_escape(keyword)));
+        }
+        if let Some(date) = &self.metadata.date {
+            metadata.push_str(&format!("    <dc:date>{}</dc:date>\n", xml_escape(date)));
+        }
+        metadata.push_str(&format!(
+            "    <meta name=\"generator\" content=\"{}\"/>\n",
+            xml_escape(self.metadata.creator.as_deref().unwrap_or(DEFAULT_CREATOR))
+        ));
+
+        let mut manifest = String::from(concat!(
+            "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+            "    <item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>\n",
+        ));
+        let mut spine = String::new();
+        for (i, (name, _, remote)) in documents.iter().enumerate() {
+            let properties = if *remote { r#" properties="remote-resources""# } else { "" };
+            manifest.push_str(&format!("    <item id=\"chapter-{}\" href=\"{name}\" media-type=\"application/xhtml+xml\"{properties}/>\n", i + 1));
+            spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", i + 1));
+        }
+        for (i, resource) in resources.iter().enumerate() {
+            manifest.push_str(&format!("    <item id=\"image-{}\" href=\"{}\" media-type=\"{}\"/>\n", i + 1, resource.href, resource.media_type));
+        }
+
+        format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{LANGUAGE}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    {metadata}  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#)
+    }
+
+    fn nav_document(&self, entries: &[NavPoint]) -> String {
+        let title = xml_escape(&self.config.toc.title);
+        format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{LANGUAGE}" lang="{LANGUAGE}">
+<head>
+  <title>{title}</title>
+  <link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <h1>{title}</h1>
+    {}
+  </nav>
+</body>
+</html>
+"#, nav_list(entries))
+    }
+}
+
+/// A navigation document entry
+struct NavPoint {
+    level: usize,
+    href: String,
+    text: String,
+}
+
+/// Nested `<ol>` where each entry owns the deeper entries that follow it
+fn nav_list(entries: &[NavPoint]) -> String {
+    let mut html = String::from("<ol>");
+    let mut i = 0;
+
+    while i < entries.len() {
+        let level = entries[i].level;
+        let end = entries[i + 1..].iter()
+            .position(|entry| entry.level <= level)
+            .map_or(entries.len(), |offset| i + 1 + offset);
+
+        html.push_str(&format!(
+            r#"<li><a href="{}">{}</a>"#,
+            xml_escape(&entries[i].href),
+            xml_escape(&entries[i].text)
+        ));
+        if end > i + 1 {
+            html.push_str(&nav_list(&entries[i + 1..end]));
+        }
+        html.push_str("</li>");
+        i = end;
+    }
+
+    html.push_str("</ol>");
+    html
+}
+
+/// Split a single document before each `<h1>`
+pub fn split_at_h1(body: &str) -> Vec<String> {
+    split_before(body, &H1_REGEX)
+}
+
+/// Split an assembled book into its part, chapter and appendix sections
+pub fn split_book_sections(body: &str) -> Vec<String> {
+    split_before(body, &BOOK_SECTION_REGEX)
+}
+
+fn split_before(body: &str, regex: &Regex) -> Vec<String> {
+    let mut chapters = Vec::new();
+    let mut start = 0;
+
+    for found in regex.find_iter(body) {
+        if found.start() > start {
+            chapters.push(body[start..found.start()].to_string());
+        }
+        start = found.start();
+    }
+    chapters.push(body[start..].to_string());
+
+    // Content before the first split point (e.g. the TOC) opens the first chapter
+    if chapters.len() > 1 && !regex.is_match(&chapters[0]) {
+        let leading = chapters.remove(0);
+        chapters[0].insert_str(0, &leading);
+    }
+
+    chapters
+}
+
+fn xhtml_document(title: &str, body: &str) -> String {
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{LANGUAGE}" lang="{LANGUAGE}">
+<head>
+  <title>{}</title>
+  <link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#, xml_escape(title))
+}
+
+/// Re-serialize an HTML fragment as well-formed XHTML: void elements are
+/// self-closed, text and attributes escaped, and unclosed tags closed
+fn to_xhtml(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut xhtml = String::new();
+    write_children(fragment.root_element(), &mut xhtml);
+    xhtml
+}
+
+fn write_children(parent: ElementRef, out: &mut String) {
+    for child in parent.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&xml_escape(text)),
+            Node::Element(element) => {
+                let name = element.name();
+                out.push('<');
+                out.push_str(name);
+                for (attr, value) in element.attrs() {
+                    out.push_str(&format!(" {attr}=\"{}\"", xml_escape(value)));
+                }
+
+                if VOID_ELEMENTS.contains(&name) {
+                    out.push_str("/>");
+                    continue;
+                }
+                out.push('>');
+                if let Some(element) = ElementRef::wrap(child) {
+                    write_children(element, out);
+                }
+                out.push_str(&format!("</{name}>"));
+            }
+            // Comments and processing instructions are not carried over
+            _ => {}
+        }
+    }
+}
+
+fn media_type_for(extension: &str) -> &'static str {
+    match extension {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_split_and_xhtml() {
+        let chapters = split_at_h1("<p>[TOC]</p><h1 id=\"a\">A</h1><p>x<br>y</p><h1 id=\"b\">B</h1><p>&nbsp;<img alt=\"1 < 2\"></p>");
+        assert_eq!(chapters.len(), 2);
+        assert!(chapters[0].starts_with("<p>[TOC]</p><h1"));
+
+        assert_eq!(to_xhtml(&chapters[1]), "<h1 id=\"b\">B</h1><p>\u{a0}<img alt=\"1 &lt; 2\"/></p>");
+        assert_eq!(to_xhtml("<ul><li>open"), "<ul><li>open</li></ul>");
+    }
+
+    #[test]
+    fn test_package_layout_and_cross_chapter_links() {
+        let metadata = DocumentMetadata { title: Some("Guide".to_string()), ..Default::default() };
+        let chapters = vec![
+            "<h1 id=\"intro\">Intro</h1><p><a href=\"#usage\">usage</a></p>".to_string(),
+            "<h1 id=\"usage\">Usage</h1><h2 id=\"cli\">CLI</h2>".to_string(),
+        ];
+        let epub = EpubWriter::new(Config::default(), metadata).package(&chapters).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(epub)).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        drop(mimetype);
+
+        let read = |archive: &mut zip::ZipArchive<Cursor<Vec<u8>>>, name: &str| {
+            let mut text = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut text).unwrap();
+            text
+        };
+        assert!(read(&mut archive, "OEBPS/chapter-1.xhtml").contains(r##"href="chapter-2.xhtml#usage""##));
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains(r##"<li><a href="chapter-2.xhtml#usage">Usage</a><ol><li><a href="chapter-2.xhtml#cli">CLI</a></li></ol></li>"##));
+        assert!(read(&mut archive, "OEBPS/content.opf").contains("<dc:title>Guide</dc:title>"));
+    }
+}
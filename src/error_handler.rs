@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum PapercraftError {
     #[error("File not found: {path}")]
     FileNotFound { path: PathBuf },
@@ -13,10 +14,6 @@ pub enum PapercraftError {
     #[error("Permission denied accessing: {path}")]
     PermissionDenied { path: PathBuf },
     
-    #[error("Configuration error in {file}: {message}")]
-    #[allow(dead_code)]
-    ConfigError { file: String, message: String },
-    
     #[error("Conversion failed for {input} -> {output}: {reason}")]
     ConversionFailed {
         input: PathBuf,
@@ -25,30 +22,28 @@ pub enum PapercraftError {
     },
     
     #[error("Chrome browser initialization failed: {reason}")]
-    #[allow(dead_code)]
     BrowserInitFailed { reason: String },
     
     #[error("Memory limit exceeded processing file: {path}. File size: {size_mb}MB")]
     MemoryLimitExceeded { path: PathBuf, size_mb: u64 },
     
-    #[error("Processing timeout for file: {path}. Timeout: {timeout_seconds}s")]
-    #[allow(dead_code)]
-    ProcessingTimeout { path: PathBuf, timeout_seconds: u64 },
-    
-    #[error("Disk space insufficient. Required: {required_mb}MB, Available: {available_mb}MB")]
-    #[allow(dead_code)]
-    InsufficientDiskSpace { required_mb: u64, available_mb: u64 },
-    
     #[error("Template error: {message}")]
-    #[allow(dead_code)]
     TemplateError { message: String },
-    
-    #[error("Image processing error for {path}: {reason}")]
-    #[allow(dead_code)]
-    ImageProcessingError { path: PathBuf, reason: String },
+
+    #[error("Invalid front matter: {reason}")]
+    FrontMatterError { reason: String },
+
+    #[error("Bibliography error: {reason}")]
+    BibliographyError { reason: String },
     
     #[error("Path traversal attempt detected: {path}")]
     PathTraversalAttempt { path: PathBuf },
+
+    #[error("Unknown output format '{format}' (available: {available})")]
+    UnknownFormat { format: String, available: String },
+
+    #[error("Rendering failed: {reason}")]
+    RenderFailed { reason: String },
 }
 
 pub struct ErrorReporter {
@@ -120,10 +115,13 @@ impl ErrorReporter {
                 eprintln!("   • Close other applications to free memory");
                 eprintln!("   • Use --optimize-images flag");
             }
-            PapercraftError::InsufficientDiskSpace { .. } => {
-                eprintln!("   • Free up disk space");
-                eprintln!("   • Use a different output directory");
-                eprintln!("   • Enable image optimization to reduce file size");
+            PapercraftError::FrontMatterError { .. } => {
+                eprintln!("   • Check the YAML or TOML between the --- or +++ delimiters");
+                eprintln!("   • Quote values that contain colons or brackets");
+            }
+            PapercraftError::BibliographyError { .. } => {
+                eprintln!("   • Check the bibliography path in the config or front matter");
+                eprintln!("   • Make sure every cited key exists in the bibliography file");
             }
             _ => {
                 eprintln!("   • Check the documentation for more details");
@@ -146,7 +144,7 @@ pub fn validate_input_file(path: &Path) -> Result<(), PapercraftError> {
     let valid_extensions = ["md", "markdown", "mdown", "mkd"];
     let has_valid_extension = path.extension()
         .and_then(|ext| ext.to_str())
-        .map_or(false, |ext| valid_extensions.contains(&ext.to_lowercase().as_str()));
+        .is_some_and(|ext| valid_extensions.contains(&ext.to_lowercase().as_str()));
     
     if !has_valid_extension {
         return Err(PapercraftError::InvalidFileFormat { path: path.to_path_buf() });
@@ -177,7 +175,7 @@ pub fn validate_input_file(path: &Path) -> Result<(), PapercraftError> {
     }
     
     // Validate file permissions
-    if let Err(_) = std::fs::File::open(path) {
+    if std::fs::File::open(path).is_err() {
         return Err(PapercraftError::PermissionDenied { path: path.to_path_buf() });
     }
     
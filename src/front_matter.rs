@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use crate::config::{Config, DocumentMetadata};
+use crate::error_handler::PapercraftError;
+use crate::logger::Logger;
+
+/// Top-level `Config` sections a document may override from its front matter
+const CONFIG_SECTIONS: [&str; 13] = [
+    "output", "page", "theme", "fonts", "toc", "images", "code", "references", "assets", "docx", "latex", "templates",
+    "cover",
+];
+
+/// Cover page fields that may also be given at the top level
+const COVER_FIELDS: [&str; 6] = ["subtitle", "organization", "organisation", "logo", "version", "confidentiality"];
+
+/// Parsed `---` YAML or `+++` TOML block at the top of a Markdown file
+#[derive(Debug, Default)]
+pub struct FrontMatter {
+    pub metadata: DocumentMetadata,
+    /// Every front matter key as written, for templates
+    pub fields: Map<String, Value>,
+    config_overrides: Map<String, Value>,
+}
+
+impl FrontMatter {
+    /// Split front matter from the Markdown body. Documents without front
+    /// matter are returned unchanged with empty metadata.
+    pub fn extract(source: &str) -> Result<(Self, &str)> {
+        Self::parse(source).map_err(front_matter_error)
+    }
+
+    fn parse(source: &str) -> Result<(Self, &str)> {
+        let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+
+        let Some((delimiter, block, body)) = split_block(source) else {
+            return Ok((Self::default(), source));
+        };
+
+        let map = match delimiter {
+            // `---` is also a Markdown thematic break, so a leading block that
+            // isn't a YAML mapping is ordinary content
+            "---" => match serde_yaml::from_str::<Value>(block) {
+                Ok(Value::Object(map)) => map,
+                Ok(Value::Null) => Map::new(),
+                _ => {
+                    Logger::verbose("Leading --- block is not YAML front matter; keeping it as Markdown");
+                    return Ok((Self::default(), source));
+                }
+            },
+            _ => match toml_to_json(toml::from_str(block).context("Failed to parse TOML front matter")?) {
+                Value::Object(map) => map,
+                _ => anyhow::bail!("Front matter must be a key/value mapping"),
+            },
+        };
+
+        Ok((Self::from_map(map), body))
+    }
+
+    fn from_map(map: Map<String, Value>) -> Self {
+        let mut front_matter = Self {
+            fields: map.clone(),
+            ..Self::default()
+        };
+
+        for (key, value) in map {
+            match key.as_str() {
+                "title" => front_matter.metadata.title = scalar_string(&value),
+                "author" | "authors" => front_matter.metadata.authors = string_list(&value).unwrap_or_default(),
+                "date" => front_matter.metadata.date = scalar_string(&value),
+                "subject" | "description" => front_matter.metadata.subject = scalar_string(&value),
+                "keywords" | "tags" => front_matter.metadata.keywords = string_list(&value)
+                    .unwrap_or_default()
+                    .iter()
+                    .flat_map(|keyword| keyword.split(','))
+                    .map(|keyword| keyword.trim().to_string())
+                    .filter(|keyword| !keyword.is_empty())
+                    .collect(),
+                // Pandoc-style shorthand for references.bibliography.file
+                "bibliography" => front_matter.add_overrides(serde_json::json!({
+                    "references": { "bibliography": { "enabled": true, "file": value } }
+                })),
+                // `theme: academic` picks a built-in theme
+                "theme" if value.is_string() => front_matter.add_overrides(serde_json::json!({
+                    "theme": { "built_in": value }
+                })),
+                // `cover: true` switches on the cover page with its defaults
+                "cover" if value.is_boolean() => front_matter.add_overrides(serde_json::json!({
+                    "cover": { "enabled": value }
+                })),
+                field if COVER_FIELDS.contains(&field) => {
+                    let field = if field == "organisation" { "organization" } else { field };
+                    front_matter.add_overrides(serde_json::json!({ "cover": { field: value } }));
+                }
+                section if CONFIG_SECTIONS.contains(&section) => {
+                    front_matter.add_overrides(Value::Object(Map::from_iter([(key, value)])));
+                }
+                _ => Logger::debug(format!("Ignoring unknown front matter key: {key}")),
+            }
+        }
+
+        front_matter
+    }
+
+    fn add_overrides(&mut self, overrides: Value) {
+        let mut merged = Value::Object(std::mem::take(&mut self.config_overrides));
+        merge_values(&mut merged, overrides);
+        if let Value::Object(map) = merged {
+            self.config_overrides = map;
+        }
+    }
+
+    pub fn has_config_overrides(&self) -> bool {
+        !self.config_overrides.is_empty()
+    }
+
+    /// Layer this document's config sections on top of `config`
+    pub fn apply_to(&self, config: &Config) -> Result<Config> {
+        if self.config_overrides.is_empty() {
+            return Ok(config.clone());
+        }
+
+        let mut merged = serde_json::to_value(config)
+            .context("Failed to serialize config for front matter overrides")?;
+        merge_values(&mut merged, Value::Object(self.config_overrides.clone()));
+
+        serde_json::from_value(merged)
+            .context("Invalid config override")
+            .map_err(front_matter_error)
+    }
+}
+
+/// Mark a failure as the document's fault, so library callers can tell it apart
+fn front_matter_error(error: anyhow::Error) -> anyhow::Error {
+    PapercraftError::FrontMatterError { reason: format!("{error:#}") }.into()
+}
+
+/// Find a leading front matter block, returning its delimiter, contents and the remaining body
+fn split_block(source: &str) -> Option<(&'static str, &str, &str)> {
+    let delimiter = ["---", "+++"].into_iter()
+        .find(|delimiter| source.lines().next().is_some_and(|line| line.trim_end() == *delimiter))?;
+
+    let block_start = source.find('\n')? + 1;
+    let mut offset = block_start;
+
+    for line in source[block_start..].split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        // YAML documents may also be closed with `...`
+        if trimmed == delimiter || (delimiter == "---" && trimmed == "...") {
+            return Some((delimiter, &source[block_start..offset], &source[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Recursively overwrite `base` with the keys present in `overrides`
+fn merge_values(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// TOML dates deserialize to a private wrapper, so convert them to plain strings
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()
+        ),
+    }
+}
+
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn string_list(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Array(items) => Some(items.iter().filter_map(scalar_string).collect()),
+        _ => scalar_string(value).map(|s| vec![s]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_front_matter_overrides_config() {
+        let source = "---\ntitle: Report\nauthor: [Ada, Grace]\nkeywords: rust, pdf\ntoc:\n  enabled: false\npage:\n  size:\n    preset: Letter\n---\n# Body\n";
+
+        let (front_matter, body) = FrontMatter::extract(source).unwrap();
+        assert_eq!(body, "# Body\n");
+        assert_eq!(front_matter.metadata.title.as_deref(), Some("Report"));
+        assert_eq!(front_matter.metadata.authors, vec!["Ada", "Grace"]);
+        assert_eq!(front_matter.metadata.keywords, vec!["rust", "pdf"]);
+
+        let config = front_matter.apply_to(&Config::default()).unwrap();
+        assert!(!config.toc.enabled);
+        assert_eq!(config.page.size.preset.as_deref(), Some("Letter"));
+        assert_eq!(config.toc.title, Config::default().toc.title);
+    }
+
+    #[test]
+    fn test_toml_front_matter_and_plain_documents() {
+        let (front_matter, body) = FrontMatter::extract("+++\ntitle = \"Notes\"\ndate = 2024-03-01\n+++\nText").unwrap();
+        assert_eq!(body, "Text");
+        assert_eq!(front_matter.metadata.date.as_deref(), Some("2024-03-01"));
+
+        let (front_matter, body) = FrontMatter::extract("# Title\n\n---\n").unwrap();
+        assert_eq!(body, "# Title\n\n---\n");
+        assert!(!front_matter.has_config_overrides());
+    }
+
+    #[test]
+    fn test_leading_thematic_break_is_not_front_matter() {
+        let source = "---
+
+Intro paragraph.
+
+---
+
+More text.
+";
+        let (front_matter, body) = FrontMatter::extract(source).unwrap();
+        assert_eq!(body, source);
+        assert!(front_matter.fields.is_empty());
+
+        let (front_matter, _) = FrontMatter::extract("---
+theme: academic
+---
+Text").unwrap();
+        let config = front_matter.apply_to(&Config::default()).unwrap();
+        assert_eq!(config.theme.built_in.as_deref(), Some("academic"));
+    }
+}
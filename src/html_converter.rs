@@ -1,38 +1,109 @@
 use anyhow::{Context, Result};
 use comrak::{markdown_to_html, ComrakOptions};
-use headless_chrome::{types::PrintToPdfOptions, Browser, LaunchOptions};
+use headless_chrome::{types::PrintToPdfOptions, Tab};
 use lazy_static::lazy_static;
 // use scraper::{Html, Selector}; // Removed - now handled by AdvancedProcessor
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use syntect::highlighting::{ThemeSet, Theme};
+use std::path::{Path, PathBuf};
+use serde_json::{Map, Value};
+use syntect::highlighting::Theme;
 use syntect::html::highlighted_html_for_string;
 use regex::Regex;
-use syntect::parsing::SyntaxSet;
 use base64::{engine::general_purpose::STANDARD, Engine};
-use crate::config::Config;
+use crate::config::{Config, DocumentMetadata, HeaderFooterConfig, PageNumberPosition};
 use crate::themes::ThemeManager;
-use crate::advanced_processing::AdvancedProcessor;
+use crate::advanced_processing::{AdvancedProcessor, TocEntry};
 use crate::image_optimization::ImageOptimizer;
 use crate::advanced_styles::AdvancedStyles;
 use crate::chrome_manager::ChromeManager;
+use crate::error_handler::PapercraftError;
+use crate::assets::AssetLoader;
+use crate::book::BookManifest;
+use crate::front_matter::FrontMatter;
+use crate::{pdf_headers, pdf_metadata, pdf_outline};
+use crate::syntax_highlighting::{self, SYNTAX_SET, THEME_SET};
+use crate::epub_writer::{self, EpubWriter};
+use crate::templates::{self, PageTemplates};
 
 // Struct for conversion options
 #[derive(Clone)]
 pub struct ConversionOptions {
     pub config: Config,
+    pub metadata: DocumentMetadata,
+    pub source: Option<PathBuf>,              // Input file, for the `file_name` template variable
+    pub front_matter: Map<String, Value>,     // Raw front matter fields, for templates
+}
+
+impl ConversionOptions {
+    /// Options for a single document, with its front matter layered on top.
+    /// Metadata precedence: command line, then front matter, then `[metadata]`.
+    pub fn for_document(&self, front_matter: &FrontMatter) -> Result<Self> {
+        let config = front_matter.apply_to(&self.config)?;
+        let metadata = self.metadata.clone()
+            .or(front_matter.metadata.clone())
+            .or(config.metadata.clone());
+
+        Ok(Self {
+            config,
+            metadata,
+            source: self.source.clone(),
+            front_matter: front_matter.fields.clone(),
+        })
+    }
+
+    /// Fall back to the document's first `<h1>` when no title was given
+    fn with_heading_title(mut self, html: &str) -> Self {
+        if self.metadata.title.is_none() {
+            self.metadata.title = first_heading(html);
+        }
+        self
+    }
+
+    /// Variables for the page templates, with `html` supplying the chapter name
+    fn template_context(&self, html: &str) -> tera::Context {
+        let mut context = templates::document_context(
+            &self.metadata,
+            first_heading(html).as_deref(),
+            self.source.as_deref(),
+            &self.front_matter,
+        );
+        templates::insert_cover(&mut context, &self.config.cover, &self.metadata);
+        context
+    }
+}
+
+fn read_document(input_path: &Path) -> Result<String> {
+    fs::read_to_string(input_path)
+        .with_context(|| format!("Failed to read file: {}", input_path.display()))
+}
+
+/// Text of the first `<h1>`, if any
+fn first_heading(html: &str) -> Option<String> {
+    FIRST_H1_REGEX.captures(html)
+        .map(|caps| html_escape::decode_html_entities(&TAG_REGEX.replace_all(&caps[1], "")).trim().to_string())
+        .filter(|title| !title.is_empty())
+}
+
+/// Load Chrome's output for bookmarks, headers and metadata
+fn parse_pdf(pdf_data: &[u8]) -> Result<lopdf::Document> {
+    lopdf::Document::load_mem(pdf_data).context("Failed to parse generated PDF")
 }
 
 lazy_static! {
-    // Load syntax and theme sets once
-    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
-    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
     static ref DEFAULT_THEME: &'static Theme = &THEME_SET.themes["Solarized (dark)"];
     // Regex to find code blocks
     static ref CODE_BLOCK_REGEX: Regex = Regex::new(r#"<pre><code class="language-(?P<lang>[^>]*)">(?P<code>[^<]*)</code></pre>"#).unwrap();
-    // Regex to find inline and block math (with non-greedy matching and multiline support)
-    static ref MATH_BLOCK_REGEX: Regex = Regex::new(r#"(?s)\$\$([^$]+?)\$\$"#).unwrap();
-    static ref INLINE_MATH_REGEX: Regex = Regex::new(r#"\$([^$\n]+?)\$"#).unwrap();
+    // Regex to find block or inline math in one pass, so the inline pattern
+    // never matches inside a `$$...$$` block (non-greedy, multiline blocks)
+    static ref MATH_REGEX: Regex = Regex::new(r#"(?s)\$\$([^$]+?)\$\$|\$([^$\n]+?)\$"#).unwrap();
+    // Empty TOC page number placeholders emitted by AdvancedProcessor::render_toc
+    static ref TOC_PAGE_NUMBER_REGEX: Regex = Regex::new(r##"<span class="toc-page-number" data-ref="#([^"]+)"></span>"##).unwrap();
+    // First top-level heading, used as the title when none is configured
+    static ref FIRST_H1_REGEX: Regex = Regex::new(r"(?s)<h1[^>]*>(.*?)</h1>").unwrap();
+    static ref TAG_REGEX: Regex = Regex::new(r"<[^>]+>").unwrap();
+    // Math spans, including ones that carry extra classes such as labeled equations
+    static ref MATH_CLASS_REGEX: Regex = Regex::new(r#"class="[^"]*\bmath-(display|inline)\b"#).unwrap();
 }
 
 // const DEFAULT_CSS: &str = include_str!("default-theme.css"); // Now handled by theme manager
@@ -52,13 +123,20 @@ impl HtmlToPdfConverter {
             chrome_manager,
         })
     }
+
+    /// Share `size` headless browsers across conversions, replacing each
+    /// after `recycle_after` documents
+    pub fn with_browser_pool(mut self, size: usize, recycle_after: usize) -> Self {
+        self.chrome_manager = self.chrome_manager.with_pool(size, recycle_after);
+        self
+    }
     
     /// Check if Chrome is available or needs to be downloaded
     pub fn check_chrome_status(&self) -> Result<()> {
         if self.chrome_manager.is_chrome_available() {
             let version = self.chrome_manager.get_chrome_version()
                 .unwrap_or_else(|_| "Unknown".to_string());
-            crate::logger::Logger::verbose(&format!("Chrome available: {}", version));
+            crate::logger::Logger::verbose(format!("Chrome available: {}", version));
             Ok(())
         } else {
             crate::logger::Logger::info("Chrome Headless Shell will be downloaded on first use");
@@ -72,18 +150,177 @@ impl HtmlToPdfConverter {
         output_path: &Path,
         options: ConversionOptions,
     ) -> Result<()> {
-        let markdown_content = fs::read_to_string(input_path)
-            .with_context(|| format!("Failed to read file: {}", input_path.display()))?;
+        let markdown_content = read_document(input_path)?;
+        let base_path = input_path.parent().unwrap_or(Path::new("."));
+        let options = ConversionOptions { source: Some(input_path.to_path_buf()), ..options };
+
+        let pdf_data = self.render_pdf(&markdown_content, base_path, options)?;
+        fs::write(output_path, pdf_data)
+            .with_context(|| format!("Failed to write PDF to {}", output_path.display()))
+    }
+
+    /// Write the themed, self-contained HTML document without launching Chrome
+    pub fn convert_file_to_html(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        options: ConversionOptions,
+    ) -> Result<()> {
+        let markdown_content = read_document(input_path)?;
+        let base_path = input_path.parent().unwrap_or(Path::new("."));
+        let options = ConversionOptions { source: Some(input_path.to_path_buf()), ..options };
+
+        let final_html = self.render_html_document(&markdown_content, base_path, options)?;
+        fs::write(output_path, final_html)
+            .with_context(|| format!("Failed to write HTML to {}", output_path.display()))
+    }
+
+    /// Package the document as an EPUB 3 book with one XHTML file per H1 section
+    pub fn convert_file_to_epub(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        options: ConversionOptions,
+    ) -> Result<()> {
+        let markdown_content = read_document(input_path)?;
+        let base_path = input_path.parent().unwrap_or(Path::new("."));
+        let options = ConversionOptions { source: Some(input_path.to_path_buf()), ..options };
+
+        let epub_data = self.render_epub(&markdown_content, base_path, options)?;
+        fs::write(output_path, epub_data)
+            .with_context(|| format!("Failed to write EPUB file: {}", output_path.display()))
+    }
+
+    /// PDF for Markdown already in memory; relative images resolve against `base_path`
+    pub fn render_pdf(&self, markdown_content: &str, base_path: &Path, options: ConversionOptions) -> Result<Vec<u8>> {
+        let (final_html, options) = self.render_html(markdown_content, base_path, &options)?;
+
+        self.print_pdf(&final_html, &options)
+    }
+
+    /// Standalone HTML for Markdown already in memory
+    pub fn render_html_document(&self, markdown_content: &str, base_path: &Path, options: ConversionOptions) -> Result<String> {
+        let (final_html, _) = self.render_html(markdown_content, base_path, &options)?;
+
+        Ok(final_html)
+    }
+
+    /// EPUB for Markdown already in memory
+    pub fn render_epub(&self, markdown_content: &str, base_path: &Path, options: ConversionOptions) -> Result<Vec<u8>> {
+        let (raw_html, options) = self.render_raw_html(markdown_content, &options)?;
+        let body = self.process_body(&raw_html, &options, base_path)?;
+
+        EpubWriter::new(options.config.clone(), options.metadata)
+            .with_css(self.get_theme_css(&options.config)?)
+            .with_base_path(base_path)
+            .package(&epub_writer::split_at_h1(&body))
+    }
+
+    /// Render a document to HTML, returning it with the options its front matter produced
+    fn render_html(&self, markdown_content: &str, base_path: &Path, options: &ConversionOptions) -> Result<(String, ConversionOptions)> {
+        let (raw_html, options) = self.render_raw_html(markdown_content, options)?;
+
+        let final_html = self.enhance_html(&raw_html, &options, base_path)?;
+
+        Ok((final_html, options))
+    }
+
+    /// Comrak's HTML for a document, before any processing
+    fn render_raw_html(&self, markdown_content: &str, options: &ConversionOptions) -> Result<(String, ConversionOptions)> {
+        let (front_matter, markdown_body) = FrontMatter::extract(markdown_content)?;
+        let options = options.for_document(&front_matter)?;
+
+        let raw_html = self.markdown_to_html(markdown_body)?;
+        let options = options.with_heading_title(&raw_html);
+
+        Ok((raw_html, options))
+    }
+
+    /// Assemble every chapter of a book into one PDF with a combined TOC
+    pub fn convert_book(
+        &self,
+        manifest: &BookManifest,
+        output_path: &Path,
+        options: ConversionOptions,
+    ) -> Result<()> {
+        let (final_html, options) = self.render_book_html(manifest, &options)?;
 
-        let raw_html = self.markdown_to_html(&markdown_content)?;
+        let pdf_data = self.print_pdf(&final_html, &options)?;
+        fs::write(output_path, pdf_data)
+            .with_context(|| format!("Failed to write PDF to {}", output_path.display()))
+    }
 
-        let final_html = self.enhance_html(&raw_html, &options, input_path)?;
+    /// Assemble every chapter of a book into one standalone HTML document
+    pub fn convert_book_to_html(
+        &self,
+        manifest: &BookManifest,
+        output_path: &Path,
+        options: ConversionOptions,
+    ) -> Result<()> {
+        let (final_html, _) = self.render_book_html(manifest, &options)?;
 
-        self.html_to_pdf(&final_html, output_path, &options)?;
+        fs::write(output_path, final_html)
+            .with_context(|| format!("Failed to write HTML to {}", output_path.display()))?;
 
         Ok(())
     }
 
+    /// Package a book as EPUB 3, one XHTML file per part, chapter and appendix
+    pub fn convert_book_to_epub(
+        &self,
+        manifest: &BookManifest,
+        output_path: &Path,
+        options: ConversionOptions,
+    ) -> Result<()> {
+        let (book_html, options) = self.assemble_book_html(manifest, &options)?;
+        let body = self.process_body(&book_html, &options, &manifest.root)?;
+
+        let epub_data = EpubWriter::new(options.config.clone(), options.metadata)
+            .with_css(self.get_theme_css(&options.config)?)
+            .with_base_path(&manifest.root)
+            .package(&epub_writer::split_book_sections(&body))?;
+        fs::write(output_path, epub_data)
+            .with_context(|| format!("Failed to write EPUB file: {}", output_path.display()))
+    }
+
+    fn render_book_html(&self, manifest: &BookManifest, options: &ConversionOptions) -> Result<(String, ConversionOptions)> {
+        let (book_html, options) = self.assemble_book_html(manifest, options)?;
+
+        let final_html = self.enhance_html(&book_html, &options, &manifest.root)?;
+        Ok((final_html, options))
+    }
+
+    /// Every chapter's HTML merged into one body, before any processing
+    fn assemble_book_html(&self, manifest: &BookManifest, options: &ConversionOptions) -> Result<(String, ConversionOptions)> {
+        let mut chapters = Vec::new();
+
+        for chapter_path in manifest.chapter_paths() {
+            let markdown_content = BookManifest::read_chapter(chapter_path)?;
+            let chapter_html = self.markdown_to_html(&markdown_content)?;
+
+            // Images are relative to each chapter, so resolve them before merging
+            let base_path = chapter_path.parent().unwrap_or(Path::new("."));
+            let mut image_optimizer = ImageOptimizer::new(options.config.images.clone());
+            chapters.push(image_optimizer.process_images_in_html(&chapter_html, base_path)?);
+        }
+
+        // Footnotes, cross-references and the TOC are processed once over the
+        // whole book so numbering is continuous across chapters
+        let book_html = manifest.assemble_html(chapters, options.config.toc.enabled);
+        let options = ConversionOptions {
+            metadata: options.metadata.clone()
+                .or(DocumentMetadata {
+                    title: manifest.title.clone(),
+                    ..Default::default()
+                })
+                .or(options.config.metadata.clone()),
+            ..options.clone()
+        }
+        .with_heading_title(&book_html);
+
+        Ok((book_html, options))
+    }
+
     fn markdown_to_html(&self, markdown: &str) -> Result<String> {
         let mut options = ComrakOptions::default();
         options.extension.strikethrough = true;
@@ -99,7 +336,18 @@ impl HtmlToPdfConverter {
         Ok(markdown_to_html(markdown, &options))
     }
 
-    fn enhance_html(&self, raw_html: &str, options: &ConversionOptions, input_path: &Path) -> Result<String> {
+    fn enhance_html(&self, raw_html: &str, options: &ConversionOptions, base_path: &Path) -> Result<String> {
+        let processed_html = self.process_body(raw_html, options, base_path)?;
+
+        // --- Final HTML Assembly ---
+        let theme_css = self.get_theme_css(&options.config)?;
+        let final_html = self.assemble_final_html(&processed_html, &theme_css, options, base_path)?;
+
+        Ok(final_html)
+    }
+
+    /// Highlighting, TOC, references and images applied to the document body
+    fn process_body(&self, raw_html: &str, options: &ConversionOptions, base_path: &Path) -> Result<String> {
         // Process Mermaid blocks first (before syntax highlighting)
         let mut processed_html = self.prepare_mermaid_blocks(raw_html)?;
 
@@ -110,19 +358,15 @@ impl HtmlToPdfConverter {
         processed_html = self.apply_syntax_highlighting_by_string(&processed_html)?;
 
         // Advanced processing for new features
-        let mut advanced_processor = AdvancedProcessor::new(options.config.clone());
+        let mut advanced_processor = AdvancedProcessor::new(options.config.clone())
+            .with_base_path(base_path);
         processed_html = advanced_processor.process_document(&processed_html)?;
 
         // Image optimization
-        let base_path = input_path.parent().unwrap_or(Path::new("."));
         let mut image_optimizer = ImageOptimizer::new(options.config.images.clone());
         processed_html = image_optimizer.process_images_in_html(&processed_html, base_path)?;
 
-        // --- Final HTML Assembly ---
-        let theme_css = self.get_theme_css(&options.config)?;
-        let final_html = self.assemble_final_html(&processed_html, &theme_css, &options.config);
-
-        Ok(final_html)
+        Ok(processed_html)
     }
 
     fn prepare_mermaid_blocks(&self, html: &str) -> Result<String> {
@@ -138,19 +382,17 @@ impl HtmlToPdfConverter {
     }
 
     fn prepare_math_blocks(&self, html: &str) -> Result<String> {
-        // Process block math first ($$...$$)
-        let block_processed = MATH_BLOCK_REGEX.replace_all(html, |caps: &regex::Captures| {
-            let content = caps.get(1).map_or("", |m| m.as_str()).trim();
-            format!("<div class=\"math-display\">$${content}$$</div>")
-        });
-        
-        // Then process inline math ($...$)
-        let inline_processed = INLINE_MATH_REGEX.replace_all(&block_processed, |caps: &regex::Captures| {
-            let content = caps.get(1).map_or("", |m| m.as_str()).trim();
-            format!("<span class=\"math-inline\">${content}$</span>")
+        let processed = MATH_REGEX.replace_all(html, |caps: &regex::Captures| {
+            match (caps.get(1), caps.get(2)) {
+                // Block math ($$...$$)
+                (Some(block), _) => format!("<div class=\"math-display\">$${}$$</div>", block.as_str().trim()),
+                // Inline math ($...$)
+                (None, Some(inline)) => format!("<span class=\"math-inline\">${}$</span>", inline.as_str().trim()),
+                _ => caps[0].to_string(),
+            }
         });
-        
-        Ok(inline_processed.into_owned())
+
+        Ok(processed.into_owned())
     }
 
     fn get_theme_css(&self, config: &Config) -> Result<String> {
@@ -187,50 +429,54 @@ impl HtmlToPdfConverter {
         Ok(combined_css)
     }
 
-    fn assemble_final_html(&self, body: &str, css: &str, _config: &Config) -> String {
+    fn assemble_final_html(&self, body: &str, css: &str, options: &ConversionOptions, base_path: &Path) -> Result<String> {
         let render_timeout = self.calculate_js_timeout(body);
-        
-        format!(r#"<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="UTF-8">
-    <title>Document</title>
-    <style>{css}</style>
-    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/katex.min.css">
-    <script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/katex.min.js"></script>
-    <script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.9/dist/contrib/auto-render.min.js"></script>
-    <script src="https://cdn.jsdelivr.net/npm/mermaid@9.4.3/dist/mermaid.min.js"></script>
-</head>
-<body>
-    {body}
-    <script>
-        window.addEventListener('load', function() {{
-            // Initialize Mermaid with simple configuration
-            mermaid.initialize({{ 
-                startOnLoad: true,
-                theme: 'default',
-                securityLevel: 'loose'
-            }});
-            
-            // Render math equations
-            renderMathInElement(document.body, {{
-                delimiters: [
-                    {{left: "$$", right: "$$", display: true}},
-                    {{left: "$", right: "$", display: false}}
-                ],
-                throwOnError: false
-            }});
-
-            // Signal that rendering is done after libraries have processed
-            window.setTimeout(() => {{ 
-                const sentinel = document.createElement('div');
-                sentinel.id = 'render_done';
-                document.body.appendChild(sentinel);
-            }}, {render_timeout});
-        }});
-    </script>
-</body>
-</html>"#)
+
+        let needs_math = MATH_CLASS_REGEX.is_match(body);
+        let needs_mermaid = body.contains("class=\"mermaid\"");
+        let library_tags = AssetLoader::new(options.config.assets.clone())
+            .head_html(needs_math, needs_mermaid)?;
+
+        let page_templates = PageTemplates::load(&options.config)?;
+        let mut context = options.template_context(body);
+        context.insert("render_timeout", &render_timeout);
+        let scripts = page_templates.render(templates::SCRIPTS, &context)?;
+
+        let title_page = if page_templates.has(templates::TITLE_PAGE) {
+            // Embed the logo and any other local images like the body's
+            let title_page = page_templates.render(templates::TITLE_PAGE, &context)?;
+            ImageOptimizer::new(options.config.images.clone()).process_images_in_html(&title_page, base_path)?
+        } else {
+            String::new()
+        };
+
+        context.insert("meta_tags", &self.build_meta_tags(&options.metadata));
+        context.insert("css", css);
+        context.insert("library_tags", &library_tags);
+        context.insert("title_page", &title_page);
+        context.insert("body", body);
+        context.insert("scripts", &scripts);
+
+        page_templates.render(templates::SHELL, &context)
+    }
+
+    fn build_meta_tags(&self, metadata: &DocumentMetadata) -> String {
+        let keywords = (!metadata.keywords.is_empty()).then(|| metadata.keywords.join(", "));
+        let author = metadata.author();
+
+        [
+            ("author", author.as_ref()),
+            ("description", metadata.subject.as_ref()),
+            ("keywords", keywords.as_ref()),
+            ("date", metadata.date.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!(
+            r#"<meta name="{name}" content="{}">"#,
+            html_escape::encode_double_quoted_attribute(value)
+        )))
+        .collect::<Vec<_>>()
+        .join("\n    ")
     }
 
     fn apply_syntax_highlighting_by_string(&self, html: &str) -> Result<String> {
@@ -247,33 +493,7 @@ impl HtmlToPdfConverter {
             // The code from regex can have escaped characters, we need to unescape it.
             let unescaped_code = html_escape::decode_html_entities(code).to_string();
 
-            // Better syntax detection by language name
-            let syntax = SYNTAX_SET.find_syntax_by_extension(lang)
-                .or_else(|| SYNTAX_SET.find_syntax_by_name(lang))
-                .or_else(|| match lang {
-                    "rs" | "rust" => SYNTAX_SET.find_syntax_by_name("Rust"),
-                    "py" | "python" => SYNTAX_SET.find_syntax_by_name("Python"),
-                    "js" | "javascript" => SYNTAX_SET.find_syntax_by_name("JavaScript"),
-                    "ts" | "typescript" => SYNTAX_SET.find_syntax_by_name("TypeScript"),
-                    "c" => SYNTAX_SET.find_syntax_by_name("C"),
-                    "cpp" | "c++" => SYNTAX_SET.find_syntax_by_name("C++"),
-                    "java" => SYNTAX_SET.find_syntax_by_name("Java"),
-                    "go" => SYNTAX_SET.find_syntax_by_name("Go"),
-                    "bash" | "sh" | "shell" => {
-                        // Try common bash/shell syntax names
-                        SYNTAX_SET.find_syntax_by_extension("sh")
-                            .or_else(|| SYNTAX_SET.find_syntax_by_name("Shell-Unix-Generic"))
-                            .or_else(|| SYNTAX_SET.find_syntax_by_name("Bourne Again Shell (bash)"))
-                            .or_else(|| SYNTAX_SET.find_syntax_by_name("Shell Script (Bash)"))
-                            .or_else(|| SYNTAX_SET.find_syntax_by_name("Bash"))
-                    },
-                    "console" | "terminal" => SYNTAX_SET.find_syntax_by_extension("sh"),
-                    "cmd" | "bat" | "batch" => SYNTAX_SET.find_syntax_by_name("Batch File"),
-                    "ps1" | "powershell" => SYNTAX_SET.find_syntax_by_name("PowerShell"),
-                    "zsh" => SYNTAX_SET.find_syntax_by_extension("sh"),
-                    _ => None
-                })
-                .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+            let syntax = syntax_highlighting::find_syntax(lang);
             
             highlighted_html_for_string(&unescaped_code, &SYNTAX_SET, syntax, &DEFAULT_THEME).unwrap_or_else(|_| "<pre>Error highlighting code</pre>".to_string())
         });
@@ -282,41 +502,59 @@ impl HtmlToPdfConverter {
     
     // Old ToC generation method removed - now handled by AdvancedProcessor
 
-    fn html_to_pdf(
-        &self,
-        html: &str,
-        output_path: &Path,
-        options: &ConversionOptions,
-    ) -> Result<()> {
-        // Use a closure with proper cleanup to ensure browser is dropped
-        // Even if conversion fails, we want to ensure proper cleanup
-        self.convert_with_browser(html, output_path, options)
+    /// Print the finished HTML with Chrome, then add page headers, bookmarks and metadata
+    fn print_pdf(&self, html: &str, options: &ConversionOptions) -> Result<Vec<u8>> {
+        // Headings become PDF bookmarks; link to each so Chrome records where it lands
+        let headings = AdvancedProcessor::new(options.config.clone()).collect_headings(html)?;
+        let body_end = html.rfind("</body>").unwrap_or(html.len());
+        let html = format!("{}{}\n{}", &html[..body_end], pdf_outline::link_targets_html(&headings), &html[body_end..]);
+
+        // Every post-processing step below edits this one parsed document
+        let mut doc = parse_pdf(&self.convert_with_browser(&html, options)?)?;
+
+        // Second pass: headings have now landed on pages, so fill in the TOC
+        if options.config.toc.enabled && options.config.toc.page_numbers && html.contains("toc-page-number") {
+            match pdf_outline::page_numbers(&doc) {
+                Ok(pages) => {
+                    let numbered_html = self.fill_toc_page_numbers(&html, &pages);
+                    doc = parse_pdf(&self.convert_with_browser(&numbered_html, options)?)?;
+                }
+                Err(e) => crate::logger::Logger::warning(format!("Failed to resolve TOC page numbers: {e}")),
+            }
+        }
+
+        // Chrome repeats one header/footer on every page, so per-page ones are drawn afterwards
+        if pdf_headers::varies_by_page(&options.config) {
+            self.add_page_headers(&mut doc, &html, &headings, options)?;
+        }
+
+        if let Err(e) = pdf_outline::add_outline(&mut doc, &headings) {
+            crate::logger::Logger::warning(format!("Failed to add PDF bookmarks: {e}"));
+        }
+
+        if let Err(e) = pdf_metadata::apply_metadata(&mut doc, &options.metadata) {
+            crate::logger::Logger::warning(format!("Failed to write PDF metadata: {e}"));
+        }
+
+        let mut pdf_data = Vec::new();
+        doc.save_to(&mut pdf_data).context("Failed to write PDF")?;
+        Ok(pdf_data)
     }
     
     fn convert_with_browser(
         &self,
         html: &str,
-        output_path: &Path,
         options: &ConversionOptions,
-    ) -> Result<()> {
-        // Ensure Chrome is available (download if necessary)
-        let chrome_path = self.chrome_manager.ensure_chrome()
-            .context("Failed to ensure Chrome availability")?;
-        
-        // Launch Chrome with custom path
-        let launch_options = LaunchOptions::default_builder()
-            .path(Some(chrome_path))
-            .headless(true)
-            .build()
-            .context("Failed to build Chrome launch options")?;
-        
-        let browser = Browser::new(launch_options)
-            .context("Failed to launch headless Chrome")?;
-        
-        // Ensure browser cleanup on drop with a custom wrapper
-        let _browser_guard = BrowserGuard::new(&browser);
-        
-        let tab = browser.new_tab()?;
+    ) -> Result<Vec<u8>> {
+        // Borrow a browser from the pool; it is returned when the lease drops
+        let mut lease = self.chrome_manager.acquire_browser()
+            .map_err(|e| PapercraftError::BrowserInitFailed { reason: format!("{e:#}") })?;
+
+        let tab = lease.browser().new_tab()
+            .context("Failed to open a browser tab")?;
+
+        // Close the tab even if conversion fails so pooled browsers don't accumulate tabs
+        let _tab_guard = TabGuard::new(&tab);
 
         let encoded_html = STANDARD.encode(html);
         let data_url = format!("data:text/html;base64,{encoded_html}");
@@ -336,11 +574,28 @@ impl HtmlToPdfConverter {
         }
         
         // Check if we actually found the element
-        if tab.find_element("#render_done").is_err() {
-            return Err(anyhow::anyhow!("Timeout waiting for page rendering to complete"));
+        let sentinel = tab.find_element("#render_done")
+            .map_err(|_| anyhow::anyhow!("Timeout waiting for page rendering to complete"))?;
+
+        // Surface rendering libraries that never loaded instead of printing raw source
+        let missing = sentinel.get_attribute_value("data-missing")?.unwrap_or_default();
+        if !missing.is_empty() {
+            if options.config.assets.strict {
+                anyhow::bail!(
+                    "Rendering libraries failed to load: {missing} (check assets.mode or network access)"
+                );
+            }
+            crate::logger::Logger::warning(format!(
+                "Rendering libraries failed to load: {missing}; math or diagrams will appear as raw source"
+            ));
         }
 
-        let (header_template, footer_template) = self.build_header_footer_templates(&options.config);
+        let per_page = pdf_headers::varies_by_page(&options.config);
+        let (header_template, footer_template) = if per_page {
+            (None, None)
+        } else {
+            self.build_header_footer_templates(html, options)?
+        };
         
         let pdf_options = PrintToPdfOptions {
             header_template,
@@ -348,96 +603,75 @@ impl HtmlToPdfConverter {
             print_background: Some(true),
             prefer_css_page_size: Some(true),
             landscape: Some(matches!(options.config.page.orientation, crate::config::Orientation::Landscape)),
-            display_header_footer: Some(
+            display_header_footer: Some(!per_page && (
                 options.config.page.header.as_ref().map(|h| h.enabled).unwrap_or(false) ||
                 options.config.page.footer.as_ref().map(|f| f.enabled).unwrap_or(false) ||
                 options.config.page.page_numbers.as_ref().map(|p| p.enabled).unwrap_or(false)
-            ),
+            )),
             ..Default::default()
         };
 
-        let pdf_data = tab.print_to_pdf(Some(pdf_options))?;
-        fs::write(output_path, pdf_data)
-            .with_context(|| format!("Failed to write PDF to {}", output_path.display()))?;
+        let pdf = tab.print_to_pdf(Some(pdf_options))?;
+        lease.complete();
+        Ok(pdf)
+    }
 
-        // Browser will be properly cleaned up by BrowserGuard when it goes out of scope
-        Ok(())
+    /// Draw first-page, odd/even and running headers and footers, taking
+    /// chapter titles from the pages their top-level headings landed on
+    fn add_page_headers(&self, doc: &mut lopdf::Document, html: &str, headings: &[TocEntry], options: &ConversionOptions) -> Result<()> {
+        let pages = pdf_outline::page_numbers(doc)?;
+        let chapters: Vec<(u32, String)> = headings.iter()
+            .filter(|heading| heading.level == 1)
+            .filter_map(|heading| pages.get(&heading.id).map(|page| (*page, heading.text.clone())))
+            .collect();
+        let page_count = doc.get_pages().len() as u32;
+
+        let texts = pdf_headers::page_texts(&options.config, &options.template_context(html), &chapters, page_count)?;
+        pdf_headers::stamp(doc, &texts, &options.config)
     }
 
-    fn build_header_footer_templates(&self, config: &Config) -> (Option<String>, Option<String>) {
-        let header_template = if let Some(header_config) = &config.page.header {
-            if header_config.enabled {
-                Some(format!(
-                    r#"<div style="font-size: {}; height: {}; width: 100%; text-align: center;">{}</div>"#,
-                    header_config.font_size.as_deref().unwrap_or("10px"),
-                    header_config.height.as_deref().unwrap_or("1cm"),
-                    header_config.template
-                ))
-            } else {
-                None
+    fn fill_toc_page_numbers(&self, html: &str, pages: &HashMap<String, u32>) -> String {
+        TOC_PAGE_NUMBER_REGEX.replace_all(html, |caps: &regex::Captures| {
+            match pages.get(&caps[1]) {
+                Some(page) => format!(r##"<span class="toc-page-number" data-ref="#{}">{page}</span>"##, &caps[1]),
+                None => caps[0].to_string(),
             }
-        } else {
-            None
-        };
+        }).into_owned()
+    }
 
-        let footer_template = if let Some(footer_config) = &config.page.footer {
-            if footer_config.enabled {
-                Some(format!(
-                    r#"<div style="font-size: {}; height: {}; width: 100%; text-align: center;">{}</div>"#,
-                    footer_config.font_size.as_deref().unwrap_or("10px"),
-                    footer_config.height.as_deref().unwrap_or("1cm"),
-                    footer_config.template
-                ))
-            } else {
-                None
-            }
-        } else if let Some(page_numbers) = &config.page.page_numbers {
-            if page_numbers.enabled {
-                let template = match page_numbers.position {
-                    crate::config::PageNumberPosition::Footer => {
-                        page_numbers.format
-                            .replace("{page}", r#"<span class="pageNumber"></span>"#)
-                            .replace("{total}", r#"<span class="totalPages"></span>"#)
-                    },
-                    crate::config::PageNumberPosition::Header => String::new(),
-                };
-                
-                if !template.is_empty() {
-                    Some(format!(
-                        r#"<div style="font-size: 10px; height: 1cm; width: 100%; text-align: center;">{template}</div>"#
-                    ))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
+    /// Chrome header and footer templates: the configured text, or the page
+    /// number when it is placed there, inside the header/footer template
+    fn build_header_footer_templates(&self, html: &str, options: &ConversionOptions) -> Result<(Option<String>, Option<String>)> {
+        let config = &options.config;
+        let page_templates = PageTemplates::load(config)?;
+        let mut context = options.template_context(html);
+        let page_numbers = config.page.page_numbers.as_ref().filter(|page_numbers| page_numbers.enabled);
+
+        let mut build = |name: &str, configured: Option<&HeaderFooterConfig>, position: PageNumberPosition| -> Result<Option<String>> {
+            let (content, font_size, height) = match configured.filter(|configured| configured.enabled) {
+                Some(configured) => (
+                    PageTemplates::render_str(&configured.template, &context)?,
+                    configured.font_size.as_deref().unwrap_or("10px"),
+                    configured.height.as_deref().unwrap_or("1cm"),
+                ),
+                None => match page_numbers.filter(|page_numbers| page_numbers.position == position) {
+                    Some(page_numbers) => (page_numbers.format.clone(), "10px", "1cm"),
+                    None => return Ok(None),
+                },
+            };
+
+            context.insert("content", &content
+                .replace("{page}", r#"<span class="pageNumber"></span>"#)
+                .replace("{total}", r#"<span class="totalPages"></span>"#));
+            context.insert("font_size", font_size);
+            context.insert("height", height);
+            page_templates.render(name, &context).map(Some)
         };
 
-        // If page numbers are in header, build header template
-        let header_template = if header_template.is_none() {
-            if let Some(page_numbers) = &config.page.page_numbers {
-                if page_numbers.enabled && matches!(page_numbers.position, crate::config::PageNumberPosition::Header) {
-                    let template = page_numbers.format
-                        .replace("{page}", r#"<span class="pageNumber"></span>"#)
-                        .replace("{total}", r#"<span class="totalPages"></span>"#);
-                    
-                    Some(format!(
-                        r#"<div style="font-size: 10px; height: 1cm; width: 100%; text-align: center;">{template}</div>"#
-                    ))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            header_template
-        };
+        let header_template = build(templates::HEADER, config.page.header.as_ref(), PageNumberPosition::Header)?;
+        let footer_template = build(templates::FOOTER, config.page.footer.as_ref(), PageNumberPosition::Footer)?;
 
-        (header_template, footer_template)
+        Ok((header_template, footer_template))
     }
     
     /// Calculate dynamic render timeout based on content complexity
@@ -446,7 +680,7 @@ impl HtmlToPdfConverter {
         
         // Add time for math rendering
         let math_count = html.matches("$$").count() + html.matches("$").count();
-        timeout_seconds += (math_count / 10).max(0);
+        timeout_seconds += math_count / 10;
         
         // Add time for mermaid diagrams
         let mermaid_count = html.matches("class=\"mermaid\"").count();
@@ -454,7 +688,7 @@ impl HtmlToPdfConverter {
         
         // Add time for images
         let image_count = html.matches("<img").count();
-        timeout_seconds += (image_count / 5).max(0);
+        timeout_seconds += image_count / 5;
         
         // Add time for large documents
         let content_size_kb = html.len() / 1024;
@@ -485,25 +719,20 @@ impl HtmlToPdfConverter {
     }
 }
 
-/// RAII guard to ensure proper browser cleanup
-struct BrowserGuard<'a> {
-    _browser: &'a Browser,
+/// RAII guard that closes a tab on a pooled browser
+struct TabGuard<'a> {
+    tab: &'a Tab,
 }
 
-impl<'a> BrowserGuard<'a> {
-    fn new(browser: &'a Browser) -> Self {
-        Self { _browser: browser }
+impl<'a> TabGuard<'a> {
+    fn new(tab: &'a Tab) -> Self {
+        Self { tab }
     }
 }
 
-impl<'a> Drop for BrowserGuard<'a> {
+impl Drop for TabGuard<'_> {
     fn drop(&mut self) {
-        // Attempt to close the browser gracefully
-        // Note: headless_chrome Browser implements Drop, but we can add extra cleanup here
-        if self._browser.get_version().is_err() {
-            // Browser is already closed or in error state
-            // Could log this if we had logging available
-        }
-        // The actual browser cleanup happens in the Browser's Drop implementation
+        // A failure here means the browser is gone; the pool's health check replaces it
+        let _ = self.tab.close(false);
     }
-}
\ No newline at end of file
+}
@@ -3,117 +3,77 @@ use image::{ImageFormat, DynamicImage, GenericImageView};
 use printpdf::{Image as PdfImage, ImageTransform, PdfLayerReference, Mm, Px};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use url::Url;
 
 pub struct ImageHandler {
-    client: reqwest::Client,
+    base_path: PathBuf,
 }
 
 impl ImageHandler {
     pub fn new() -> Self {
         Self {
-            client: reqwest::Client::new(),
+            base_path: PathBuf::from("."),
         }
     }
 
-    pub async fn add_image_to_pdf(
-        &self,
-        layer: &PdfLayerReference,
-        image_url: &str,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        max_height: Mm,
-    ) -> Result<Mm> {
-        // Load the image
-        let image_data = if self.is_url(image_url) {
-            self.load_image_from_url(image_url).await?
-        } else {
-            self.load_image_from_file(image_url)?
-        };
-
-        // Calculate appropriate dimensions
-        let (width, height) = self.calculate_dimensions(&image_data, max_width, max_height);
-        
-        // Convert image to PDF format and add to layer
-        self.add_image_to_layer(layer, image_data, x, y, width, height)?;
-        
-        // Return the height used by the image
-        Ok(height)
+    /// Directory that relative image paths are resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
     }
 
-    pub fn add_image_to_pdf_sync(
-        &self,
-        layer: &PdfLayerReference,
-        image_path: &str,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        max_height: Mm,
-    ) -> Result<Mm> {
-        let image_data = if self.is_url(image_path) {
-            // For URLs, use blocking download
-            self.download_image_sync(image_path)?
+    /// Load a local or remote image
+    pub fn load(&self, image_path: &str) -> Result<DynamicImage> {
+        if self.is_url(image_path) {
+            self.download_image_sync(image_path)
         } else {
-            self.load_image_from_file(image_path)?
-        };
-
-        let (width, height) = self.calculate_dimensions(&image_data, max_width, max_height);
-        self.add_image_to_layer(layer, image_data, x, y, width, height)?;
-        
-        Ok(height)
+            self.load_image_from_file(&self.base_path.join(image_path))
+        }
     }
 
     fn download_image_sync(&self, url: &str) -> Result<DynamicImage> {
         // Use blocking HTTP request for image download
         let response = reqwest::blocking::get(url)
             .context("Failed to download image from URL")?;
-        
+
         let bytes = response.bytes()
             .context("Failed to read image bytes from response")?;
-        
+
         let image = image::load_from_memory(&bytes)
             .context("Failed to decode downloaded image")?;
-        
+
         Ok(image)
     }
 
-    fn add_image_to_layer(
+    /// Draw `image` with its top-left corner at (`x`, `y`), scaled to `width` × `height`
+    pub fn add_image_to_layer(
         &self,
         layer: &PdfLayerReference,
-        image: DynamicImage,
+        image: &DynamicImage,
         x: Mm,
         y: Mm,
         width: Mm,
         height: Mm,
-    ) -> Result<()> {
+    ) {
         // Convert image to RGB format
         let rgb_image = image.to_rgb8();
         let (img_width, img_height) = rgb_image.dimensions();
-        
-        // Create image data vector
-        let mut image_data = Vec::new();
-        for pixel in rgb_image.pixels() {
-            image_data.push(pixel[0]); // R
-            image_data.push(pixel[1]); // G  
-            image_data.push(pixel[2]); // B
-        }
 
         // Create PDF image object
-        let pdf_image = PdfImage::try_from(printpdf::ImageXObject {
+        let pdf_image = PdfImage::from(printpdf::ImageXObject {
             width: Px(img_width as usize),
             height: Px(img_height as usize),
             color_space: printpdf::ColorSpace::Rgb,
             bits_per_component: printpdf::ColorBits::Bit8,
             interpolate: true,
-            image_data,
+            image_data: rgb_image.into_raw(),
             image_filter: None,
             clipping_bbox: None,
-        })?;
+        });
 
-        // Calculate scale factors  
-        let scale_x = width.0 / (img_width as f32 / 72.0 * 25.4); // Convert pixels to mm
+        // At 72 DPI one pixel is one point
+        let scale_x = width.0 / (img_width as f32 / 72.0 * 25.4);
         let scale_y = height.0 / (img_height as f32 / 72.0 * 25.4);
 
         // Create transform
@@ -122,48 +82,28 @@ impl ImageHandler {
             translate_y: Some(y - height), // PDF coordinates are bottom-left origin
             scale_x: Some(scale_x),
             scale_y: Some(scale_y),
+            dpi: Some(72.0),
             ..Default::default()
         };
-        
+
         // Add image to layer
         pdf_image.add_to_layer(layer.clone(), transform);
-        
-        Ok(())
-    }
-
-    async fn load_image_from_url(&self, url: &str) -> Result<DynamicImage> {
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .context("Failed to download image")?;
-        
-        let bytes = response
-            .bytes()
-            .await
-            .context("Failed to read image bytes")?;
-        
-        let image = image::load_from_memory(&bytes)
-            .context("Failed to decode downloaded image")?;
-        
-        Ok(image)
     }
 
-    fn load_image_from_file(&self, path: &str) -> Result<DynamicImage> {
-        if !Path::new(path).exists() {
-            anyhow::bail!("Image file not found: {}", path);
+    fn load_image_from_file(&self, path: &Path) -> Result<DynamicImage> {
+        if !path.exists() {
+            anyhow::bail!("Image file not found: {}", path.display());
         }
-        
+
         let file = File::open(path)
-            .with_context(|| format!("Failed to open image file: {}", path))?;
-        
+            .with_context(|| format!("Failed to open image file: {}", path.display()))?;
+
         let reader = BufReader::new(file);
         let format = self.detect_format(path)?;
-        
+
         let image = image::load(reader, format)
-            .with_context(|| format!("Failed to load image: {}", path))?;
-        
+            .with_context(|| format!("Failed to load image: {}", path.display()))?;
+
         Ok(image)
     }
 
@@ -171,8 +111,8 @@ impl ImageHandler {
         Url::parse(path).is_ok() && (path.starts_with("http://") || path.starts_with("https://"))
     }
 
-    fn detect_format(&self, path: &str) -> Result<ImageFormat> {
-        let extension = Path::new(path)
+    fn detect_format(&self, path: &Path) -> Result<ImageFormat> {
+        let extension = path
             .extension()
             .and_then(|ext| ext.to_str())
             .unwrap_or("")
@@ -189,7 +129,8 @@ impl ImageHandler {
         }
     }
 
-    fn calculate_dimensions(
+    /// Size for `image` within the bounds, never enlarging it
+    pub fn calculate_dimensions(
         &self,
         image: &DynamicImage,
         max_width: Mm,
@@ -216,8 +157,4 @@ impl ImageHandler {
             (Mm(max_height.0 * aspect_ratio), max_height)
         }
     }
-
-    pub fn placeholder_for_image(&self, url: &str) -> String {
-        format!("[Image: {}]", url)
-    }
-}
\ No newline at end of file
+}
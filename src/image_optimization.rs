@@ -99,7 +99,8 @@ impl ImageOptimizer {
         Ok(data_url)
     }
 
-    fn optimize_image(&self, mut img: DynamicImage) -> Result<DynamicImage> {
+    /// Downscale to the configured maximum dimensions, keeping the aspect ratio
+    pub fn optimize_image(&self, mut img: DynamicImage) -> Result<DynamicImage> {
         let (width, height) = img.dimensions();
 
         // Resize if needed
@@ -0,0 +1,638 @@
+use anyhow::{Context, Result};
+use comrak::{nodes::{AstNode, ListType, NodeList, NodeTable, NodeValue, TableAlignment}, parse_document, Anchorizer, Arena, ComrakOptions};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::advanced_processing::CITATION_REGEX;
+use crate::bibliography::{Bibliography, CitationStyle};
+use crate::config::{Config, DocumentMetadata, Orientation};
+use crate::error_handler::PapercraftError;
+use crate::front_matter::FrontMatter;
+use crate::logger::Logger;
+use crate::templates;
+
+// Built-in preambles, chosen by theme name
+const DEFAULT_TEMPLATE: &str = include_str!("themes/latex/default.tex");
+const ACADEMIC_TEMPLATE: &str = include_str!("themes/latex/academic.tex");
+
+const SECTION_COMMANDS: [&str; 6] = ["section", "subsection", "subsubsection", "paragraph", "subparagraph", "subparagraph"];
+const ENUM_COUNTERS: [&str; 4] = ["enumi", "enumii", "enumiii", "enumiv"];
+
+lazy_static! {
+    // Formatted references from `Bibliography` carry a little HTML
+    static ref REFERENCE_MARKUP_REGEX: Regex = Regex::new(r#"(?s)<em>(.*?)</em>|<a href="([^"]*)">.*?</a>"#).expect("Invalid reference markup regex");
+}
+
+/// Per-document state, reset at the start of each conversion
+#[derive(Default)]
+struct DocumentState {
+    // Heading slugs, matching the HTML ids so `#anchor` links resolve to labels
+    anchorizer: Anchorizer,
+    // Footnote definitions by name, rendered where they are referenced
+    footnotes: HashMap<String, String>,
+    // Citation keys in order of first use
+    cited: Vec<String>,
+}
+
+pub struct LatexConverter {
+    config: Config,
+    metadata: DocumentMetadata,
+    base_path: PathBuf,
+    state: RefCell<DocumentState>,
+}
+
+impl LatexConverter {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            metadata: DocumentMetadata::default(),
+            base_path: PathBuf::from("."),
+            state: RefCell::new(DocumentState::default()),
+        }
+    }
+
+    /// Title, author, date and abstract for the preamble
+    pub fn with_metadata(mut self, metadata: DocumentMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Directory that the bibliography file is resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
+    }
+
+    pub fn convert_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
+        Logger::verbose(format!("Converting {} to LaTeX", input_path.display()));
+
+        let markdown_content = fs::read_to_string(input_path)
+            .with_context(|| format!("Failed to read input file: {}", input_path.display()))?;
+
+        let latex = Self::new(self.config.clone())
+            .with_metadata(self.metadata.clone())
+            .with_base_path(input_path.parent().unwrap_or(Path::new(".")))
+            .convert_str(&markdown_content)?;
+        self.write_output(&latex, output_path)
+    }
+
+    /// Convert a whole document held in memory, applying its front matter
+    pub fn convert_str(&self, markdown_content: &str) -> Result<String> {
+        let (front_matter, markdown_body) = FrontMatter::extract(markdown_content)?;
+        let config = front_matter.apply_to(&self.config)?;
+        let metadata = self.metadata.clone()
+            .or(front_matter.metadata)
+            .or(config.metadata.clone());
+
+        Self::new(config)
+            .with_metadata(metadata)
+            .with_base_path(&self.base_path)
+            .render(markdown_body)
+    }
+
+    /// Convert Markdown source that has already been loaded, e.g. an assembled book
+    pub fn convert_markdown(&self, markdown_content: &str, output_path: &Path) -> Result<()> {
+        let latex = self.render(markdown_content)?;
+        self.write_output(&latex, output_path)
+    }
+
+    fn write_output(&self, latex: &str, output_path: &Path) -> Result<()> {
+        fs::write(output_path, latex)
+            .with_context(|| format!("Failed to write LaTeX file: {}", output_path.display()))?;
+
+        Logger::verbose(format!("Successfully converted to {}", output_path.display()));
+        Ok(())
+    }
+
+    /// The complete `.tex` source: the preamble template around the converted body
+    pub fn render(&self, markdown_content: &str) -> Result<String> {
+        let arena = Arena::new();
+        let mut options = ComrakOptions::default();
+        options.extension.strikethrough = true;
+        options.extension.table = true;
+        options.extension.autolink = true;
+        options.extension.tasklist = true;
+        options.extension.superscript = true;
+        options.extension.footnotes = true;
+        options.extension.description_lists = true;
+        options.extension.math_dollars = true;
+        options.extension.front_matter_delimiter = Some("---".to_string());
+
+        let root = parse_document(&arena, markdown_content, &options);
+
+        *self.state.borrow_mut() = DocumentState::default();
+        self.collect_footnotes(root)?;
+
+        let mut body = String::new();
+        self.write_blocks(root, 0, &mut body)?;
+        let cited = std::mem::take(&mut self.state.borrow_mut().cited);
+        let bibliography = self.bibliography(&cited)
+            .map_err(|e| PapercraftError::BibliographyError { reason: format!("{e:#}") })?;
+
+        let mut context = tera::Context::new();
+        context.insert("class_options", &self.class_options());
+        context.insert("geometry", &self.geometry());
+        context.insert("toc_depth", &self.config.toc.max_depth.clamp(1, 5));
+        context.insert("title", &self.metadata.title.as_deref().map(escape).unwrap_or_default());
+        context.insert("author", &self.metadata.authors.iter().map(|author| escape(author)).collect::<Vec<_>>().join(" \\and "));
+        // PDF metadata strings cannot contain \and
+        context.insert("pdf_author", &self.metadata.author().as_deref().map(escape).unwrap_or_default());
+        context.insert("date", &self.metadata.date.as_deref().map(escape).unwrap_or_default());
+        context.insert("abstract", &self.metadata.subject.as_deref().map(escape).unwrap_or_default());
+        context.insert("keywords", &escape(&self.metadata.keywords.join(", ")));
+        context.insert("body", body.trim_end());
+        context.insert("bibliography", &bibliography);
+
+        tera::Tera::one_off(&self.template()?, &context, false)
+            .map_err(|e| anyhow::anyhow!("Failed to render LaTeX template: {}", templates::error_chain(&e)))
+    }
+
+    /// `latex.template` if set, otherwise the built-in preamble for the theme
+    fn template(&self) -> Result<String> {
+        if let Some(path) = &self.config.latex.template {
+            return fs::read_to_string(path)
+                .with_context(|| format!("Failed to read LaTeX template: {}", path.display()));
+        }
+
+        Ok(match self.config.theme.built_in.as_deref() {
+            Some("academic") => ACADEMIC_TEMPLATE,
+            _ => DEFAULT_TEMPLATE,
+        }.to_string())
+    }
+
+    fn class_options(&self) -> String {
+        let mut options = vec![match self.config.page.size.preset.as_deref() {
+            Some("Letter") => "letterpaper",
+            Some("Legal") => "legalpaper",
+            Some("A3") => "a3paper",
+            Some("A5") => "a5paper",
+            _ => "a4paper",
+        }.to_string()];
+
+        // Standard classes only offer 10pt, 11pt and 12pt
+        if let Some(size) = self.config.fonts.size.as_deref().filter(|s| ["10pt", "11pt", "12pt"].contains(s)) {
+            options.push(size.to_string());
+        }
+        if matches!(self.config.page.orientation, Orientation::Landscape) {
+            options.push("landscape".to_string());
+        }
+
+        options.join(",")
+    }
+
+    fn geometry(&self) -> String {
+        let margins = &self.config.page.margins;
+        [("top", &margins.top), ("right", &margins.right), ("bottom", &margins.bottom), ("left", &margins.left)]
+            .iter()
+            .map(|(side, value)| {
+                let value = value.trim();
+                // CSS pixels have no TeX equivalent
+                let value = if ["in", "mm", "cm", "pt"].iter().any(|unit| value.ends_with(unit)) { value } else { "1in" };
+                format!("{side}={value}")
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Render every footnote definition up front, since references may come first
+    fn collect_footnotes<'a>(&self, root: &'a AstNode<'a>) -> Result<()> {
+        for node in root.descendants() {
+            if let NodeValue::FootnoteDefinition(definition) = &node.data.borrow().value {
+                let mut text = String::new();
+                for child in node.children() {
+                    self.write_blocks(child, 0, &mut text)?;
+                }
+                let text = text.trim().replace("\n\n", "\\par ");
+                self.state.borrow_mut().footnotes.insert(definition.name.clone(), text);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_blocks<'a>(&self, node: &'a AstNode<'a>, list_depth: usize, out: &mut String) -> Result<()> {
+        match &node.data.borrow().value {
+            NodeValue::Document => {
+                for child in node.children() {
+                    self.write_blocks(child, list_depth, out)?;
+                }
+            }
+            NodeValue::Heading(heading) => {
+                let text = self.extract_text_from_node(node);
+                let label = self.state.borrow_mut().anchorizer.anchorize(text);
+                let command = SECTION_COMMANDS[heading.level.clamp(1, 6) as usize - 1];
+                out.push_str(&format!("\\{command}{{{}}}\\label{{{label}}}\n\n", self.inline_children(node)?));
+            }
+            NodeValue::Paragraph if self.extract_text_from_node(node).trim() == "[TOC]" => {
+                // Same marker the HTML path replaces; dropped when the TOC is disabled
+                if self.config.toc.enabled {
+                    out.push_str("\\tableofcontents\n\n");
+                }
+            }
+            NodeValue::Paragraph => {
+                if let Some(figure) = self.figure(node) {
+                    out.push_str(&figure);
+                } else {
+                    out.push_str(&self.inline_children(node)?);
+                    out.push_str("\n\n");
+                }
+            }
+            NodeValue::List(list) => {
+                self.write_list(node, list, list_depth, out)?;
+            }
+            NodeValue::DescriptionList => {
+                out.push_str("\\begin{description}\n");
+                for item in node.children() {
+                    let mut term = String::new();
+                    let mut details = String::new();
+                    for part in item.children() {
+                        match &part.data.borrow().value {
+                            NodeValue::DescriptionTerm => {
+                                for child in part.children() {
+                                    term.push_str(&self.inline_children(child)?);
+                                }
+                            }
+                            _ => {
+                                for child in part.children() {
+                                    self.write_blocks(child, list_depth, &mut details)?;
+                                }
+                            }
+                        }
+                    }
+                    out.push_str(&format!("\\item[{term}] {}\n", details.trim()));
+                }
+                out.push_str("\\end{description}\n\n");
+            }
+            NodeValue::CodeBlock(code_block) if code_block.info.trim() == "math" => {
+                out.push_str(&format!("\\[\n{}\n\\]\n\n", code_block.literal.trim()));
+            }
+            NodeValue::CodeBlock(code_block) => {
+                let language = code_block.info.split_whitespace().next().unwrap_or("");
+                let options = listings_language(language)
+                    .map(|language| format!("[language={language}]"))
+                    .unwrap_or_default();
+                out.push_str(&format!(
+                    "\\begin{{lstlisting}}{options}\n{}\n\\end{{lstlisting}}\n\n",
+                    code_block.literal.trim_end_matches('\n')
+                ));
+            }
+            NodeValue::Table(table) => {
+                self.write_table(node, table, out)?;
+            }
+            NodeValue::BlockQuote => {
+                out.push_str("\\begin{quote}\n");
+                for child in node.children() {
+                    self.write_blocks(child, list_depth, out)?;
+                }
+                out.push_str("\\end{quote}\n\n");
+            }
+            NodeValue::ThematicBreak => {
+                out.push_str("\\noindent\\rule{\\linewidth}{0.4pt}\n\n");
+            }
+            // Definitions are emitted as \footnote at their references; raw HTML has no LaTeX form
+            NodeValue::FootnoteDefinition(_) | NodeValue::HtmlBlock(_) | NodeValue::FrontMatter(_) => {}
+            _ => {
+                for child in node.children() {
+                    self.write_blocks(child, list_depth, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// itemize/enumerate with task items as checkbox labels; nested lists
+    /// are written inside their item
+    fn write_list<'a>(&self, node: &'a AstNode<'a>, list: &NodeList, depth: usize, out: &mut String) -> Result<()> {
+        let environment = match list.list_type {
+            ListType::Bullet => "itemize",
+            ListType::Ordered => "enumerate",
+        };
+        out.push_str(&format!("\\begin{{{environment}}}\n"));
+        if matches!(list.list_type, ListType::Ordered) && list.start > 1 {
+            let counter = ENUM_COUNTERS[depth.min(ENUM_COUNTERS.len() - 1)];
+            out.push_str(&format!("\\setcounter{{{counter}}}{{{}}}\n", list.start - 1));
+        }
+
+        for item in node.children() {
+            let label = match &item.data.borrow().value {
+                NodeValue::TaskItem(Some(_)) => "[$\\boxtimes$]",
+                NodeValue::TaskItem(None) => "[$\\square$]",
+                _ => "",
+            };
+            let mut blocks = Vec::new();
+            for child in item.children() {
+                let mut content = String::new();
+                match &child.data.borrow().value {
+                    NodeValue::List(nested) => self.write_list(child, nested, depth + 1, &mut content)?,
+                    _ => self.write_blocks(child, depth + 1, &mut content)?,
+                }
+                blocks.push(content.trim().to_string());
+            }
+            out.push_str(&format!("\\item{label} {}\n", blocks.join("\n")));
+        }
+
+        out.push_str(&format!("\\end{{{environment}}}\n\n"));
+        Ok(())
+    }
+
+    fn write_table<'a>(&self, node: &'a AstNode<'a>, table: &NodeTable, out: &mut String) -> Result<()> {
+        let columns: String = table.alignments.iter()
+            .map(|alignment| match alignment {
+                TableAlignment::Center => 'c',
+                TableAlignment::Right => 'r',
+                _ => 'l',
+            })
+            .collect();
+
+        out.push_str(&format!("\\begin{{table}}[htbp]\n\\centering\n\\begin{{tabular}}{{{columns}}}\n\\toprule\n"));
+        for row in node.children() {
+            let header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+            let cells = row.children()
+                .map(|cell| self.inline_children(cell).map(|text| if header { format!("\\textbf{{{text}}}") } else { text }))
+                .collect::<Result<Vec<_>>>()?;
+            out.push_str(&format!("{} \\\\\n", cells.join(" & ")));
+            if header {
+                out.push_str("\\midrule\n");
+            }
+        }
+        out.push_str("\\bottomrule\n\\end{tabular}\n\\end{table}\n\n");
+        Ok(())
+    }
+
+    /// A paragraph holding only an image becomes a captioned figure
+    fn figure<'a>(&self, node: &'a AstNode<'a>) -> Option<String> {
+        let mut children = node.children();
+        let image = children.next()?;
+        if children.next().is_some() {
+            return None;
+        }
+
+        let NodeValue::Image(link) = &image.data.borrow().value else {
+            return None;
+        };
+        let caption = self.inline_children(image).ok()?;
+        let caption = if caption.is_empty() { String::new() } else { format!("\\caption{{{caption}}}\n") };
+
+        Some(format!(
+            "\\begin{{figure}}[htbp]\n\\centering\n{}\n{caption}\\end{{figure}}\n\n",
+            include_graphics(&link.url)
+        ))
+    }
+
+    fn inline_children<'a>(&self, node: &'a AstNode<'a>) -> Result<String> {
+        let mut out = String::new();
+        // Adjacent text nodes are joined so citations split by the parser still match
+        let mut text = String::new();
+
+        for child in node.children() {
+            if let NodeValue::Text(literal) = &child.data.borrow().value {
+                text.push_str(literal);
+                continue;
+            }
+            out.push_str(&self.text_with_citations(&std::mem::take(&mut text)));
+            self.write_inline(child, &mut out)?;
+        }
+        out.push_str(&self.text_with_citations(&text));
+
+        Ok(out)
+    }
+
+    fn write_inline<'a>(&self, node: &'a AstNode<'a>, out: &mut String) -> Result<()> {
+        match &node.data.borrow().value {
+            NodeValue::Text(text) => out.push_str(&self.text_with_citations(text)),
+            NodeValue::Code(code) => out.push_str(&format!("\\texttt{{{}}}", escape(&code.literal))),
+            NodeValue::Emph => out.push_str(&format!("\\emph{{{}}}", self.inline_children(node)?)),
+            NodeValue::Strong => out.push_str(&format!("\\textbf{{{}}}", self.inline_children(node)?)),
+            NodeValue::Strikethrough => out.push_str(&format!("\\sout{{{}}}", self.inline_children(node)?)),
+            NodeValue::Superscript => out.push_str(&format!("\\textsuperscript{{{}}}", self.inline_children(node)?)),
+            NodeValue::Math(math) if math.display_math => out.push_str(&format!("\\[{}\\]", math.literal.trim())),
+            NodeValue::Math(math) => out.push_str(&format!("${}$", math.literal.trim())),
+            NodeValue::Link(link) => {
+                let text = self.inline_children(node)?;
+                match link.url.strip_prefix('#') {
+                    Some(label) => out.push_str(&format!("\\hyperref[{label}]{{{text}}}")),
+                    None => out.push_str(&format!("\\href{{{}}}{{{text}}}", escape_url(&link.url))),
+                }
+            }
+            NodeValue::Image(link) => out.push_str(&include_graphics(&link.url)),
+            NodeValue::FootnoteReference(reference) => {
+                let text = self.state.borrow().footnotes.get(&reference.name).cloned();
+                match text {
+                    Some(text) => out.push_str(&format!("\\footnote{{{text}}}")),
+                    None => Logger::warning(format!("Footnote [^{}] has no definition", reference.name)),
+                }
+            }
+            NodeValue::SoftBreak => out.push('\n'),
+            NodeValue::LineBreak => out.push_str("\\\\\n"),
+            NodeValue::HtmlInline(_) => {}
+            _ => out.push_str(&self.inline_children(node)?),
+        }
+        Ok(())
+    }
+
+    /// Escape text, turning `[@cite:key1,key2]` into `\cite{key1,key2}`
+    fn text_with_citations(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut last = 0;
+
+        for caps in CITATION_REGEX.captures_iter(text) {
+            let Some(found) = caps.get(0) else { continue };
+            let keys: Vec<&str> = caps[1].split(',').map(str::trim).filter(|key| !key.is_empty()).collect();
+
+            let mut state = self.state.borrow_mut();
+            for key in &keys {
+                if !state.cited.iter().any(|cited| cited == key) {
+                    state.cited.push(key.to_string());
+                }
+            }
+
+            out.push_str(&escape(&text[last..found.start()]));
+            out.push_str(&format!("\\cite{{{}}}", keys.join(",")));
+            last = found.end();
+        }
+        out.push_str(&escape(&text[last..]));
+
+        out
+    }
+
+    /// BibTeX commands for a `.bib` file, or a `thebibliography` list
+    /// formatted like the HTML output for CSL-JSON sources
+    fn bibliography(&self, cited: &[String]) -> Result<String> {
+        let bib_config = &self.config.references.bibliography;
+        if cited.is_empty() || !bib_config.enabled {
+            return Ok(String::new());
+        }
+
+        let file = bib_config.file.as_ref().ok_or_else(|| anyhow::anyhow!(
+            "Document cites {} but no bibliography file is configured (set references.bibliography.file or `bibliography` in front matter)",
+            cited.join(", ")
+        ))?;
+        let style = CitationStyle::parse(&bib_config.style)?;
+
+        if file.extension().is_some_and(|ext| ext == "bib") {
+            let bibtex_style = match style {
+                CitationStyle::Apa => "apalike",
+                CitationStyle::Ieee => "ieeetr",
+                CitationStyle::Mla | CitationStyle::Chicago => "plain",
+            };
+            let database = file.with_extension("").to_string_lossy().replace('\\', "/");
+            return Ok(format!("\\bibliographystyle{{{bibtex_style}}}\n\\bibliography{{{database}}}\n"));
+        }
+
+        let bib_path = if file.is_relative() && self.base_path.join(file).exists() {
+            self.base_path.join(file)
+        } else {
+            file.clone()
+        };
+        let bibliography = Bibliography::load(&bib_path)?;
+
+        let mut latex = String::from("\\begin{thebibliography}{99}\n");
+        for entry in bibliography.sorted_entries(cited, &bib_config.sort_by, style) {
+            latex.push_str(&format!(
+                "\\bibitem{{{}}} {}\n",
+                entry.key,
+                reference_to_latex(&bibliography.format_reference(entry, style))
+            ));
+        }
+        latex.push_str("\\end{thebibliography}\n");
+
+        Ok(latex)
+    }
+
+    fn extract_text_from_node<'a>(&self, node: &'a AstNode<'a>) -> String {
+        let mut text = String::new();
+        for descendant in node.descendants() {
+            match &descendant.data.borrow().value {
+                NodeValue::Text(literal) => text.push_str(literal),
+                NodeValue::Code(code) => text.push_str(&code.literal),
+                _ => {}
+            }
+        }
+        text
+    }
+}
+
+fn include_graphics(url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        // graphicx cannot fetch remote images
+        return format!("\\url{{{}}}", escape_url(url));
+    }
+    format!("\\includegraphics[width=\\linewidth,keepaspectratio]{{{}}}", escape_url(url))
+}
+
+/// Fence language → `listings` language, for the languages it knows
+fn listings_language(language: &str) -> Option<&'static str> {
+    Some(match language.to_lowercase().as_str() {
+        "c" => "C",
+        "cpp" | "c++" => "C++",
+        "java" => "Java",
+        "python" | "py" => "Python",
+        "bash" | "sh" | "shell" | "zsh" => "bash",
+        "sql" => "SQL",
+        "html" => "HTML",
+        "xml" => "XML",
+        "ruby" | "rb" => "Ruby",
+        "perl" => "Perl",
+        "php" => "PHP",
+        "r" => "R",
+        "matlab" => "Matlab",
+        "haskell" | "hs" => "Haskell",
+        "tex" | "latex" => "TeX",
+        "make" | "makefile" => "make",
+        _ => return None,
+    })
+}
+
+/// Escape LaTeX's special characters in running text
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `\href`, `\url` and `\includegraphics` take paths verbatim apart from these
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "/").replace('%', "\\%").replace('#', "\\#")
+}
+
+/// `<em>` and `<a>` in a formatted reference → `\emph` and `\url`
+fn reference_to_latex(reference: &str) -> String {
+    let decode = |text: &str| html_escape::decode_html_entities(text).into_owned();
+    let mut latex = String::new();
+    let mut last = 0;
+
+    for caps in REFERENCE_MARKUP_REGEX.captures_iter(reference) {
+        let Some(found) = caps.get(0) else { continue };
+        latex.push_str(&escape(&decode(&reference[last..found.start()])));
+        match (caps.get(1), caps.get(2)) {
+            (Some(emphasis), _) => latex.push_str(&format!("\\emph{{{}}}", escape(&decode(emphasis.as_str())))),
+            (None, Some(href)) => latex.push_str(&format!("\\url{{{}}}", escape_url(&decode(href.as_str())))),
+            _ => {}
+        }
+        last = found.end();
+    }
+    latex.push_str(&escape(&decode(&reference[last..])));
+
+    latex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_structure() {
+        let mut config = Config::default();
+        config.toc.enabled = true;
+        let converter = LatexConverter::new(config)
+            .with_metadata(DocumentMetadata { title: Some("R&D".to_string()), ..Default::default() });
+
+        let latex = converter.render(concat!(
+            "[TOC]\n\n# Intro\n\nCost is 5% of $x^2$ [see](#intro).[^n]\n\n",
+            "1. one\n   - [x] nested\n\n",
+            "| a | b |\n|---|--:|\n| 1 | 2 |\n\n",
+            "```python\nprint('{}')\n```\n\n$$\n\\sum_i x_i\n$$\n\n[^n]: A note.\n\n",
+            "![chart](figures/q3#2-50%.png)\n",
+        )).unwrap();
+
+        assert!(latex.contains("\\title{R\\&D}"));
+        assert!(latex.contains("\\tableofcontents"));
+        assert!(latex.contains("\\section{Intro}\\label{intro}"));
+        assert!(latex.contains("Cost is 5\\% of $x^2$ \\hyperref[intro]{see}.\\footnote{A note.}"));
+        assert!(latex.contains("\\begin{enumerate}\n\\item one\n\\begin{itemize}\n\\item[$\\boxtimes$] nested"));
+        assert!(latex.contains("\\begin{tabular}{lr}\n\\toprule\n\\textbf{a} & \\textbf{b} \\\\\n\\midrule\n1 & 2 \\\\"));
+        assert!(latex.contains("\\begin{lstlisting}[language=Python]\nprint('{}')\n\\end{lstlisting}"));
+        assert!(latex.contains("\\[\\sum_i x_i\\]"));
+        assert!(!latex.contains("A note.\n"));
+        assert!(latex.contains("\\includegraphics[width=\\linewidth,keepaspectratio]{figures/q3\\#2-50\\%.png}"));
+    }
+
+    #[test]
+    fn test_citations_and_academic_template() {
+        let mut config = Config::default();
+        config.theme.built_in = Some("academic".to_string());
+        config.references.bibliography.enabled = true;
+        config.references.bibliography.file = Some(PathBuf::from("refs/library.bib"));
+        let converter = LatexConverter::new(config)
+            .with_metadata(DocumentMetadata { subject: Some("We study things.".to_string()), ..Default::default() });
+
+        let latex = converter.render("As shown [@cite:knuth84, lamport94].\n").unwrap();
+        assert!(latex.contains("\\usepackage{mathptmx}"));
+        assert!(latex.contains("\\begin{abstract}\nWe study things."));
+        assert!(latex.contains("As shown \\cite{knuth84,lamport94}."));
+        assert!(latex.contains("\\bibliography{refs/library}"));
+    }
+}
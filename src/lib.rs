@@ -0,0 +1,57 @@
+//! PaperCraft converts Markdown to PDF, DOCX, HTML, EPUB and LaTeX.
+//!
+//! ```no_run
+//! use papercraft::{Config, Converter, Format};
+//!
+//! # fn main() -> Result<(), papercraft::PapercraftError> {
+//! let converter = Converter::new(Config::default())?.with_format(Format::Docx);
+//! let docx = converter.convert_str("# Release notes\n\nAll tests pass.")?;
+//! # let _ = docx;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `papercraft` binary is a thin wrapper around [`cli::run`].
+
+mod html_converter;
+pub mod config;
+mod themes;
+mod advanced_processing;
+mod image_optimization;
+mod advanced_styles;
+mod error_handler;
+mod progress_tracker;
+mod memory_optimizer;
+mod resume_handler;
+mod logger;
+mod markdown_validator;
+mod config_wizard;
+mod dry_run;
+mod chrome_manager;
+mod docx_converter;
+mod docx_styles;
+mod assets;
+mod book;
+mod front_matter;
+mod bibliography;
+mod cross_references;
+mod pdf_outline;
+mod pdf_metadata;
+mod pdf_headers;
+mod epub_writer;
+mod latex_converter;
+mod syntax_highlighting;
+mod templates;
+mod document_model;
+mod text_renderer;
+mod image_handler;
+mod pdf_generator;
+mod pdf_fonts;
+mod native_pdf;
+mod backends;
+mod converter;
+pub mod cli;
+
+pub use config::{Config, DocumentMetadata};
+pub use converter::{Converter, Format};
+pub use error_handler::PapercraftError;
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::fmt::Display;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -22,6 +22,7 @@ impl From<u8> for LogLevel {
 }
 
 static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Normal as u8);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
 
 pub struct Logger;
 
@@ -33,42 +34,55 @@ impl Logger {
     pub fn get_level() -> LogLevel {
         LogLevel::from(LOG_LEVEL.load(Ordering::Relaxed))
     }
+
+    /// Print every message to stderr, keeping stdout free for document output
+    pub fn redirect_to_stderr() {
+        TO_STDERR.store(true, Ordering::Relaxed);
+    }
+
+    fn print(line: impl Display) {
+        if TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    }
     
     #[allow(dead_code)]
     pub fn quiet<T: Display>(message: T) {
         if Self::get_level() != LogLevel::Quiet {
-            println!("{message}");
+            Self::print(message);
         }
     }
     
     pub fn info<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Normal {
-            println!("{message}");
+            Self::print(message);
         }
     }
     
     pub fn verbose<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Verbose {
-            println!("🔍 {}", message);
+            Self::print(format_args!("🔍 {}", message));
         }
     }
     
     #[allow(dead_code)]
     pub fn debug<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Debug {
-            println!("🐛 DEBUG: {}", message);
+            Self::print(format_args!("🐛 DEBUG: {}", message));
         }
     }
     
     pub fn success<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Normal {
-            println!("✓ {}", message);
+            Self::print(format_args!("✓ {}", message));
         }
     }
     
     pub fn warning<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Normal {
-            println!("⚠️  {}", message);
+            Self::print(format_args!("⚠️  {}", message));
         }
     }
     
@@ -81,25 +95,25 @@ impl Logger {
     
     pub fn step<T: Display>(step: u32, total: u32, message: T) {
         if Self::get_level() >= LogLevel::Normal {
-            println!("[{}/{}] {}", step, total, message);
+            Self::print(format_args!("[{}/{}] {}", step, total, message));
         }
     }
     
     pub fn progress<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Normal {
-            println!("🔄 {}", message);
+            Self::print(format_args!("🔄 {}", message));
         }
     }
     
     pub fn dry_run<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Normal {
-            println!("🏃 DRY RUN: {}", message);
+            Self::print(format_args!("🏃 DRY RUN: {}", message));
         }
     }
     
     pub fn validation<T: Display>(message: T) {
         if Self::get_level() >= LogLevel::Verbose {
-            println!("🔍 VALIDATION: {}", message);
+            Self::print(format_args!("🔍 VALIDATION: {}", message));
         }
     }
 }
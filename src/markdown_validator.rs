@@ -3,6 +3,7 @@ use std::path::Path;
 use std::fs;
 use regex::Regex;
 use lazy_static::lazy_static;
+use crate::logger::Logger;
 
 lazy_static! {
     static ref MARKDOWN_LINK_REGEX: Regex = Regex::new(r"\[([^\]]*)\]\(([^)]+)\)").unwrap();
@@ -386,20 +387,20 @@ impl ValidationResult {
     }
     
     pub fn print_summary(&self) {
-        println!("📊 Validation Summary:");
-        println!("  📄 Total lines: {}", self.stats.total_lines);
-        println!("  📝 Headings: {}", self.stats.headings);
-        println!("  🔗 Links: {}", self.stats.links);
-        println!("  🖼️  Images: {}", self.stats.images);
-        println!("  💻 Code blocks: {}", self.stats.code_blocks);
-        println!("  📋 Tables: {}", self.stats.tables);
-        println!("  ❌ Errors: {}", self.stats.errors);
-        println!("  ⚠️  Warnings: {}", self.stats.warnings);
+        Logger::info("📊 Validation Summary:");
+        Logger::info(format!("  📄 Total lines: {}", self.stats.total_lines));
+        Logger::info(format!("  📝 Headings: {}", self.stats.headings));
+        Logger::info(format!("  🔗 Links: {}", self.stats.links));
+        Logger::info(format!("  🖼️  Images: {}", self.stats.images));
+        Logger::info(format!("  💻 Code blocks: {}", self.stats.code_blocks));
+        Logger::info(format!("  📋 Tables: {}", self.stats.tables));
+        Logger::info(format!("  ❌ Errors: {}", self.stats.errors));
+        Logger::info(format!("  ⚠️  Warnings: {}", self.stats.warnings));
     }
     
     pub fn print_issues(&self, show_info: bool) {
         if self.issues.is_empty() {
-            println!("✓ No validation issues found!");
+            Logger::info("✓ No validation issues found!");
             return;
         }
         
@@ -415,13 +416,13 @@ impl ValidationResult {
             };
             
             if let Some(col) = issue.column {
-                println!("{}  Line {}, Column {}: {}", icon, issue.line, col, issue.message);
+                Logger::info(format!("{}  Line {}, Column {}: {}", icon, issue.line, col, issue.message));
             } else {
-                println!("{}  Line {}: {}", icon, issue.line, issue.message);
+                Logger::info(format!("{}  Line {}: {}", icon, issue.line, issue.message));
             }
             
             if let Some(ref suggestion) = issue.suggestion {
-                println!("     💡 {}", suggestion);
+                Logger::info(format!("     💡 {}", suggestion));
             }
         }
     }
@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use lopdf::Document;
+use printpdf::Pt;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::advanced_processing::TocEntry;
+use crate::config::{Config, DocumentMetadata, PageNumberPosition};
+use crate::document_model::{self, ElementType};
+use crate::front_matter::FrontMatter;
+use crate::logger::Logger;
+use crate::pdf_generator::{GeneratedPdf, PageLayout, PdfGenerator};
+use crate::{pdf_headers, pdf_metadata, pdf_outline, templates};
+
+/// PDF output drawn directly with printpdf, for machines without Chrome.
+/// Themes, math and diagrams need the browser and are not rendered.
+pub struct NativePdfConverter {
+    config: Config,
+    metadata: DocumentMetadata,
+    base_path: PathBuf,
+    source: Option<PathBuf>,
+    front_matter: Map<String, Value>,
+}
+
+impl NativePdfConverter {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            metadata: DocumentMetadata::default(),
+            base_path: PathBuf::from("."),
+            source: None,
+            front_matter: Map::new(),
+        }
+    }
+
+    /// Title, author and keywords for the PDF Info dictionary and page templates
+    pub fn with_metadata(mut self, metadata: DocumentMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Directory that relative image paths are resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.base_path = base_path.to_path_buf();
+        self
+    }
+
+    pub fn convert_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
+        Logger::verbose(format!("Converting {} to PDF with the native engine", input_path.display()));
+
+        let markdown_content = fs::read_to_string(input_path)
+            .with_context(|| format!("Failed to read input file: {}", input_path.display()))?;
+
+        let converter = Self {
+            source: Some(input_path.to_path_buf()),
+            ..Self::new(self.config.clone())
+                .with_metadata(self.metadata.clone())
+                .with_base_path(input_path.parent().unwrap_or(Path::new(".")))
+        };
+        let pdf_data = converter.convert_str(&markdown_content)?;
+        self.write_output(&pdf_data, output_path)
+    }
+
+    /// Convert a whole document held in memory, applying its front matter
+    pub fn convert_str(&self, markdown_content: &str) -> Result<Vec<u8>> {
+        // Front matter may adjust the layout of this document only
+        let (front_matter, markdown_body) = FrontMatter::extract(markdown_content)?;
+        let config = front_matter.apply_to(&self.config)?;
+        let metadata = self.metadata.clone()
+            .or(front_matter.metadata)
+            .or(config.metadata.clone());
+
+        let converter = Self {
+            source: self.source.clone(),
+            front_matter: front_matter.fields,
+            ..Self::new(config)
+                .with_metadata(metadata)
+                .with_base_path(&self.base_path)
+        };
+        converter.render(markdown_body)
+    }
+
+    /// Convert Markdown source that has already been loaded, e.g. an assembled book
+    pub fn convert_markdown(&self, markdown_content: &str, output_path: &Path) -> Result<()> {
+        let pdf_data = self.render(markdown_content)?;
+        self.write_output(&pdf_data, output_path)
+    }
+
+    fn write_output(&self, pdf_data: &[u8], output_path: &Path) -> Result<()> {
+        fs::write(output_path, pdf_data)
+            .with_context(|| format!("Failed to write PDF file: {}", output_path.display()))?;
+
+        Logger::verbose(format!("Successfully converted to {}", output_path.display()));
+        Ok(())
+    }
+
+    /// The finished PDF: laid-out pages plus headers, bookmarks and metadata
+    pub fn render(&self, markdown_content: &str) -> Result<Vec<u8>> {
+        if self.config.cover.enabled {
+            Logger::warning("Cover pages are not supported by the native PDF engine; use --engine chrome to include one");
+        }
+
+        let elements = document_model::parse_markdown(markdown_content);
+        let first_heading = elements.iter()
+            .find(|element| element.element_type == ElementType::Heading && element.level == Some(1))
+            .map(|element| element.content.clone());
+        let title = self.metadata.title.clone().or(first_heading.clone()).unwrap_or_else(|| "Document".to_string());
+
+        let mut generator = PdfGenerator::new(PageLayout::from_config(&self.config))
+            .with_base_path(&self.base_path)
+            .with_title(&title)
+            .with_line_numbers(self.config.code.line_numbers)
+            .with_fonts(self.config.fonts.clone());
+        if let Some(body_size) = self.config.fonts.size.as_deref().and_then(pdf_headers::points) {
+            generator = generator.with_body_size(body_size);
+        }
+        let page = &self.config.page;
+        if page.header_footer(PageNumberPosition::Header).is_some() || page.header_footer(PageNumberPosition::Footer).is_some() {
+            let context = templates::document_context(&self.metadata, first_heading.as_deref(), self.source.as_deref(), &self.front_matter);
+            // No cover is drawn here, so page 1 is content and keeps its header and footer
+            let mut config = self.config.clone();
+            config.cover.enabled = false;
+            generator = generator.with_headers(config, context);
+        }
+
+        let GeneratedPdf { data, page_count, headings } = generator.generate_pdf(&elements)?;
+        Logger::verbose(format!("Laid out {} elements on {} pages", elements.len(), page_count));
+
+        // Bookmarks point at destinations named after each heading's position
+        let headings: Vec<_> = headings.into_iter()
+            .filter(|heading| heading.level <= self.config.toc.max_depth)
+            .enumerate()
+            .collect();
+        let targets: Vec<(String, u32, f32)> = headings.iter()
+            .map(|(index, heading)| (format!("heading-{index}"), heading.page, Pt::from(heading.top).0))
+            .collect();
+        let entries: Vec<TocEntry> = headings.into_iter()
+            .map(|(index, heading)| TocEntry {
+                level: heading.level as usize,
+                text: heading.text,
+                id: format!("heading-{index}"),
+                section_number: String::new(),
+            })
+            .collect();
+        let mut doc = Document::load_mem(&data).context("Failed to parse generated PDF")?;
+        if let Err(e) = pdf_outline::add_destinations(&mut doc, &targets).and_then(|()| pdf_outline::add_outline(&mut doc, &entries)) {
+            Logger::warning(format!("Failed to add PDF bookmarks: {e}"));
+        }
+
+        let metadata = DocumentMetadata { title: Some(title), ..self.metadata.clone() };
+        if let Err(e) = pdf_metadata::apply_metadata(&mut doc, &metadata) {
+            Logger::warning(format!("Failed to write PDF metadata: {e}"));
+        }
+
+        let mut output = Vec::new();
+        doc.save_to(&mut output).context("Failed to write PDF")?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_pdf_paginates_and_bookmarks_headings() {
+        let mut markdown = String::from("# Introduction\n\nOpening text.\n\n");
+        for section in 1..=3 {
+            markdown.push_str(&format!("## Section {section}\n\n"));
+            for _ in 0..12 {
+                markdown.push_str("A paragraph long enough to wrap over several lines of body text on an A4 page, \
+                    so that the document needs more than one page.\n\n");
+            }
+        }
+
+        let pdf = NativePdfConverter::new(Config::default()).render(&markdown).unwrap();
+        let doc = Document::load_mem(&pdf).unwrap();
+        assert!(doc.get_pages().len() > 1);
+
+        let pages = pdf_outline::page_numbers(&doc).unwrap();
+        assert_eq!(pages["heading-0"], 1);
+        assert!(pages["heading-3"] > 1);
+        assert!(doc.catalog().unwrap().get(b"Outlines").is_ok());
+    }
+}
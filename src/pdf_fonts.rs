@@ -0,0 +1,344 @@
+use ab_glyph::{Font, FontArc};
+use anyhow::{Context, Result};
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, Pt, PdfDocumentReference};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::config::{CustomFont, FontConfig};
+use crate::logger::Logger;
+
+/// Helvetica advance widths for ASCII 32-126, in 1/1000 em
+pub const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Helvetica-Bold advance widths for ASCII 32-126, in 1/1000 em
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+enum Metrics {
+    Embedded(FontArc),
+    Builtin(&'static [u16; 95]),
+    Monospace, // Courier
+}
+
+/// One font in the PDF and the metrics to measure text set in it
+struct Face {
+    font: IndirectFontRef,
+    metrics: Metrics,
+}
+
+impl Face {
+    fn embedded(doc: &PdfDocumentReference, path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("Failed to read font file: {}", path.display()))?;
+
+        // printpdf embeds fonts as CIDFontType2, which needs TrueType outlines
+        if bytes.starts_with(b"OTTO") {
+            anyhow::bail!("{} has PostScript (CFF) outlines; only TrueType-flavoured fonts can be embedded", path.display());
+        }
+
+        let metrics = FontArc::try_from_vec(bytes.clone())
+            .with_context(|| format!("Invalid font file: {}", path.display()))?;
+        let font = doc.add_external_font(bytes.as_slice())
+            .with_context(|| format!("Failed to embed font: {}", path.display()))?;
+
+        Ok(Self { font, metrics: Metrics::Embedded(metrics) })
+    }
+
+    fn builtin(doc: &PdfDocumentReference, builtin: BuiltinFont) -> Result<Self> {
+        let metrics = match builtin {
+            BuiltinFont::Courier | BuiltinFont::CourierBold | BuiltinFont::CourierOblique | BuiltinFont::CourierBoldOblique => Metrics::Monospace,
+            BuiltinFont::HelveticaBold | BuiltinFont::HelveticaBoldOblique => Metrics::Builtin(&HELVETICA_BOLD_WIDTHS),
+            _ => Metrics::Builtin(&HELVETICA_WIDTHS),
+        };
+        Ok(Self { font: doc.add_builtin_font(builtin)?, metrics })
+    }
+
+    fn covers(&self, c: char) -> bool {
+        match &self.metrics {
+            Metrics::Embedded(font) => font.glyph_id(c).0 != 0,
+            // Built-in fonts are limited to WinAnsiEncoding
+            _ => !lopdf::Document::encode_text(Some("WinAnsiEncoding"), c.encode_utf8(&mut [0; 4])).is_empty(),
+        }
+    }
+
+    /// Advance width of `c`, in 1/1000 em
+    fn advance(&self, c: char) -> f32 {
+        match &self.metrics {
+            Metrics::Embedded(font) => {
+                let units_per_em = font.units_per_em().unwrap_or(1000.0);
+                font.h_advance_unscaled(font.glyph_id(c)) * 1000.0 / units_per_em
+            }
+            Metrics::Builtin(widths) => match c {
+                ' '..='~' => widths[c as usize - 32] as f32,
+                '•' => 350.0,
+                '‘' | '’' => 222.0,
+                '“' | '”' => 333.0,
+                '—' | '…' => 1000.0,
+                _ => 556.0,
+            },
+            Metrics::Monospace => 600.0,
+        }
+    }
+}
+
+/// Faces tried in order for each character, like a CSS font stack. Every
+/// chain includes a built-in font, so Latin-1 text can always be set.
+#[derive(Clone)]
+pub struct FontChain {
+    faces: Vec<Rc<Face>>,
+    missing: Rc<RefCell<BTreeSet<char>>>,
+}
+
+impl FontChain {
+    /// Split `text` into runs that share a face. Characters no face covers
+    /// are left to the built-in font, which drops them, and are recorded.
+    pub fn runs(&self, text: &str) -> Vec<(&IndirectFontRef, String)> {
+        let mut runs: Vec<(usize, String)> = Vec::new();
+
+        for c in text.chars() {
+            // Emoji presentation selectors and joiners have no glyph of their own
+            if matches!(c, '\u{200D}' | '\u{FE0E}' | '\u{FE0F}') {
+                continue;
+            }
+
+            let face = self.face_for(c);
+            match runs.last_mut() {
+                Some((last, run)) if *last == face => run.push(c),
+                _ => runs.push((face, c.to_string())),
+            }
+        }
+
+        runs.into_iter()
+            .map(|(face, run)| (&self.faces[face].font, run))
+            .collect()
+    }
+
+    /// Width of `text` set at `font_size` points
+    pub fn width(&self, text: &str, font_size: f32) -> Mm {
+        let units: f32 = text.chars()
+            .filter(|c| !matches!(c, '\u{200D}' | '\u{FE0E}' | '\u{FE0F}'))
+            .map(|c| self.faces[self.face_for(c)].advance(c))
+            .sum();
+        Mm::from(Pt(units * font_size / 1000.0))
+    }
+
+    fn face_for(&self, c: char) -> usize {
+        self.faces.iter().position(|face| face.covers(c)).unwrap_or_else(|| {
+            if !c.is_whitespace() {
+                self.missing.borrow_mut().insert(c);
+            }
+            self.faces.iter()
+                .position(|face| !matches!(face.metrics, Metrics::Embedded(_)))
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// Font stacks for each text style
+#[derive(Clone)]
+pub struct FontSystem {
+    pub regular: FontChain,
+    pub bold: FontChain,
+    pub italic: FontChain,
+    pub bold_italic: FontChain,
+    pub code: FontChain,
+    missing: Rc<RefCell<BTreeSet<char>>>,
+}
+
+/// Regular, bold, italic and bold italic files of one custom font family
+#[derive(Default)]
+struct Family {
+    name: String,
+    preferred: bool, // Named in `fonts.family`
+    regular: Option<PathBuf>,
+    bold: Option<PathBuf>,
+    italic: Option<PathBuf>,
+    bold_italic: Option<PathBuf>,
+}
+
+impl Family {
+    /// Files to try for regular, bold, italic and bold italic text, closest
+    /// variant first, so every style falls back to one the family has
+    fn variants(&self) -> [[&Option<PathBuf>; 4]; 4] {
+        [
+            [&self.regular, &self.bold, &self.italic, &self.bold_italic],
+            [&self.bold, &self.regular, &self.bold_italic, &self.italic],
+            [&self.italic, &self.regular, &self.bold_italic, &self.bold],
+            [&self.bold_italic, &self.bold, &self.italic, &self.regular],
+        ]
+    }
+}
+
+impl FontSystem {
+    /// Embed `fonts.custom_fonts` around the built-in Helvetica and Courier.
+    /// Families named in `fonts.family` replace the built-in fonts; any other
+    /// custom font is a fallback for characters they lack, such as CJK,
+    /// Cyrillic or emoji, tried in the order listed.
+    pub fn load(doc: &PdfDocumentReference, fonts: &FontConfig) -> Result<Self> {
+        let families = families(fonts);
+        let mut loaded: HashMap<PathBuf, Option<Rc<Face>>> = HashMap::new();
+        let mut face = |path: &Option<PathBuf>| -> Option<Rc<Face>> {
+            let path = path.as_ref()?;
+            loaded.entry(path.clone())
+                .or_insert_with(|| match Face::embedded(doc, path) {
+                    Ok(face) => Some(Rc::new(face)),
+                    Err(e) => {
+                        Logger::warning(format!("Skipping custom font: {e:#}"));
+                        None
+                    }
+                })
+                .clone()
+        };
+
+        // Regular, bold, italic and bold italic stacks, before and after the built-in font
+        let mut primary: [Vec<Rc<Face>>; 4] = Default::default();
+        let mut fallback: [Vec<Rc<Face>>; 4] = Default::default();
+        for family in &families {
+            let stacks = if family.preferred { &mut primary } else { &mut fallback };
+            for (stack, candidates) in stacks.iter_mut().zip(family.variants()) {
+                if let Some(face) = candidates.into_iter().find_map(&mut face) {
+                    stack.push(face);
+                }
+            }
+        }
+        Logger::debug(format!("Native PDF fonts: {} custom families", families.len()));
+
+        let missing = Rc::new(RefCell::new(BTreeSet::new()));
+        let chain = |primary: &[Rc<Face>], builtin: BuiltinFont, fallback: &[Rc<Face>]| -> Result<FontChain> {
+            let mut faces = primary.to_vec();
+            faces.push(Rc::new(Face::builtin(doc, builtin)?));
+            faces.extend_from_slice(fallback);
+            Ok(FontChain { faces, missing: missing.clone() })
+        };
+
+        // Code stays in Courier; custom fonts only fill in what it lacks
+        let code_fallback = [primary[0].as_slice(), fallback[0].as_slice()].concat();
+
+        Ok(Self {
+            regular: chain(&primary[0], BuiltinFont::Helvetica, &fallback[0])?,
+            bold: chain(&primary[1], BuiltinFont::HelveticaBold, &fallback[1])?,
+            italic: chain(&primary[2], BuiltinFont::HelveticaOblique, &fallback[2])?,
+            bold_italic: chain(&primary[3], BuiltinFont::HelveticaBoldOblique, &fallback[3])?,
+            code: chain(&[], BuiltinFont::Courier, &code_fallback)?,
+            missing,
+        })
+    }
+
+    /// Characters that no configured font could draw
+    pub fn missing_glyphs(&self) -> Vec<char> {
+        self.missing.borrow().iter().copied().collect()
+    }
+}
+
+/// Group `custom_fonts` into families: those named in `fonts.family` first,
+/// in that order, then the rest in the order they are listed
+fn families(fonts: &FontConfig) -> Vec<Family> {
+    let preferred: Vec<String> = fonts.family.as_deref().unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .collect();
+    let mut families: Vec<Family> = Vec::new();
+
+    for font in fonts.custom_fonts.iter().flatten() {
+        let index = match families.iter().position(|family| family.name == font.name) {
+            Some(index) => index,
+            None => {
+                families.push(Family {
+                    name: font.name.clone(),
+                    preferred: preferred.contains(&font.name),
+                    ..Default::default()
+                });
+                families.len() - 1
+            }
+        };
+        let slot = match (is_bold(font), is_italic(font)) {
+            (false, false) => &mut families[index].regular,
+            (true, false) => &mut families[index].bold,
+            (false, true) => &mut families[index].italic,
+            (true, true) => &mut families[index].bold_italic,
+        };
+        slot.get_or_insert_with(|| font.path.clone());
+    }
+
+    families.sort_by_key(|family| preferred.iter().position(|name| *name == family.name).unwrap_or(preferred.len()));
+    families
+}
+
+fn is_bold(font: &CustomFont) -> bool {
+    match font.weight.as_deref().map(str::trim) {
+        Some("bold" | "bolder") => true,
+        Some(weight) => weight.parse::<u16>().is_ok_and(|weight| weight >= 600),
+        None => false,
+    }
+}
+
+fn is_italic(font: &CustomFont) -> bool {
+    matches!(font.style.as_deref().map(str::trim), Some("italic" | "oblique"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::PdfDocument;
+
+    #[test]
+    fn test_text_outside_latin1_falls_back_and_is_reported() {
+        let (doc, _, _) = PdfDocument::new("Fonts", Mm(210.0), Mm(297.0), "Layer 1");
+        let fonts = FontSystem::load(&doc, &FontConfig::default()).unwrap();
+
+        // Real Helvetica metrics: "Wi" is 944 + 222 units
+        assert!((fonts.regular.width("Wi", 10.0).0 - Mm::from(Pt(11.66)).0).abs() < 0.001);
+        assert!(fonts.bold.width("Wi", 10.0) > fonts.regular.width("Wi", 10.0));
+
+        let runs = fonts.regular.runs("Café Привет");
+        assert_eq!(runs.len(), 1);
+        assert_eq!(fonts.missing_glyphs(), vec!['П', 'в', 'е', 'и', 'р', 'т']);
+    }
+
+    #[test]
+    fn test_families_put_named_fonts_first_and_fill_missing_styles() {
+        let font = |name: &str, path: &str, weight: Option<&str>| CustomFont {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+            weight: weight.map(str::to_string),
+            style: None,
+        };
+        let fonts = FontConfig {
+            family: Some("'Body Serif', sans-serif".to_string()),
+            custom_fonts: Some(vec![
+                font("Noto CJK", "cjk.otf", None),
+                font("Body Serif", "serif.ttf", None),
+                font("Body Serif", "serif-bold.ttf", Some("700")),
+            ]),
+            ..Default::default()
+        };
+
+        let families = families(&fonts);
+        let names: Vec<_> = families.iter().map(|family| (family.name.as_str(), family.preferred)).collect();
+        assert_eq!(names, vec![("Body Serif", true), ("Noto CJK", false)]);
+
+        // Italic text uses the regular file and bold italic the bold one
+        let closest = |family: &Family| -> Vec<PathBuf> {
+            family.variants().iter()
+                .map(|candidates| candidates.iter().find_map(|path| path.as_ref()).unwrap().clone())
+                .collect()
+        };
+        assert_eq!(closest(&families[0]), ["serif.ttf", "serif-bold.ttf", "serif.ttf", "serif-bold.ttf"].map(PathBuf::from));
+        assert_eq!(closest(&families[1]), ["cjk.otf"; 4].map(PathBuf::from));
+    }
+}
@@ -1,10 +1,16 @@
-use crate::{DocumentElement, ElementType, ListType, TableData, TextFormat};
-use crate::text_renderer::{TextRenderer, FontSystem};
+use crate::config::{Config, FontConfig, Orientation};
+use crate::document_model::{DocumentElement, ElementType, ListType, TableData, TextSpan};
 use crate::image_handler::ImageHandler;
+use crate::logger::Logger;
+use crate::pdf_headers;
+use crate::pdf_fonts::{FontChain, FontSystem};
+use crate::text_renderer::{FontSizes, LineStyle, TextLine, TextRenderer};
 use anyhow::{Context, Result};
-use printpdf::{BuiltinFont, Mm, PdfDocument, Color, Rgb, PdfLayerReference};
-use std::fs::File;
-use std::io::BufWriter;
+use printpdf::{Color, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Pt, Rgb};
+use std::path::Path;
+
+// Bullets by list nesting depth; the built-in fonts only cover WinAnsi
+const BULLETS: [&str; 3] = ["•", "–", "·"];
 
 pub struct PageLayout {
     pub width: Mm,
@@ -13,8 +19,6 @@ pub struct PageLayout {
     pub margin_bottom: Mm,
     pub margin_left: Mm,
     pub margin_right: Mm,
-    pub header_height: Mm,
-    pub footer_height: Mm,
 }
 
 impl Default for PageLayout {
@@ -22,847 +26,633 @@ impl Default for PageLayout {
         Self {
             width: Mm(210.0),  // A4 width
             height: Mm(297.0), // A4 height
-            margin_top: Mm(30.0),     // Larger top margin for professional look
-            margin_bottom: Mm(30.0),  // Larger bottom margin
-            margin_left: Mm(25.0),    // Comfortable left margin
-            margin_right: Mm(25.0),   // Comfortable right margin
-            header_height: Mm(18.0),  // Slightly larger header
-            footer_height: Mm(18.0),  // Slightly larger footer
+            margin_top: Mm(25.4),
+            margin_bottom: Mm(25.4),
+            margin_left: Mm(25.4),
+            margin_right: Mm(25.4),
         }
     }
 }
 
-pub struct PageInfo {
-    pub current_page: usize,
-    pub total_pages: usize,
-    pub title: String,
+impl PageLayout {
+    /// Paper size, orientation and margins from `[page]`
+    pub fn from_config(config: &Config) -> Self {
+        let default = Self::default();
+        let size = &config.page.size;
+        let (width, height) = match size.preset.as_deref().map(str::to_uppercase).as_deref() {
+            Some("LETTER") => (Mm(215.9), Mm(279.4)),
+            Some("LEGAL") => (Mm(215.9), Mm(355.6)),
+            Some("A3") => (Mm(297.0), Mm(420.0)),
+            Some("A5") => (Mm(148.0), Mm(210.0)),
+            Some(_) => (default.width, default.height),
+            None => match (size.width.as_deref().and_then(length), size.height.as_deref().and_then(length)) {
+                (Some(width), Some(height)) => (width, height),
+                _ => (default.width, default.height),
+            },
+        };
+        let (width, height) = match config.page.orientation {
+            Orientation::Landscape => (height, width),
+            Orientation::Portrait => (width, height),
+        };
+
+        let margins = &config.page.margins;
+        Self {
+            width,
+            height,
+            margin_top: length(&margins.top).unwrap_or(default.margin_top),
+            margin_bottom: length(&margins.bottom).unwrap_or(default.margin_bottom),
+            margin_left: length(&margins.left).unwrap_or(default.margin_left),
+            margin_right: length(&margins.right).unwrap_or(default.margin_right),
+        }
+    }
+
+    fn content_width(&self) -> Mm {
+        self.width - self.margin_left - self.margin_right
+    }
+
+    fn content_top(&self) -> Mm {
+        self.height - self.margin_top
+    }
+
+    fn content_height(&self) -> Mm {
+        self.content_top() - self.margin_bottom
+    }
+}
+
+fn length(value: &str) -> Option<Mm> {
+    pdf_headers::points(value).map(|points| Mm::from(Pt(points)))
+}
+
+/// Where a heading was placed, for bookmarks and running headers
+#[derive(Debug, Clone)]
+pub struct HeadingPosition {
+    pub level: u8,
+    pub text: String,
+    pub page: u32,
+    pub top: Mm,
+}
+
+pub struct GeneratedPdf {
+    pub data: Vec<u8>,
+    pub page_count: u32,
+    pub headings: Vec<HeadingPosition>,
+}
+
+/// The current page and how far down it content has reached
+struct PageCursor<'a> {
+    doc: &'a PdfDocumentReference,
+    layout: &'a PageLayout,
+    layer: PdfLayerReference,
+    layers: Vec<PdfLayerReference>, // Every page so far, for headers and footers
+    page: u32,
+    y: Mm,
+}
+
+impl PageCursor<'_> {
+    fn at_top(&self) -> bool {
+        self.y >= self.layout.content_top()
+    }
+
+    fn remaining(&self) -> Mm {
+        self.y - self.layout.margin_bottom
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(self.layout.width, self.layout.height, "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.layers.push(self.layer.clone());
+        self.page += 1;
+        self.y = self.layout.content_top();
+    }
+
+    /// Start a new page unless `height` still fits above the bottom margin
+    fn reserve(&mut self, height: Mm) {
+        if self.remaining() < height && !self.at_top() {
+            self.new_page();
+        }
+    }
+
+    /// Vertical space between blocks, dropped at the top of a page
+    fn skip(&mut self, space: Mm) {
+        if !self.at_top() {
+            self.y -= space;
+        }
+    }
 }
 
 pub struct PdfGenerator {
     image_handler: ImageHandler,
     layout: PageLayout,
-    page_info: PageInfo,
+    title: String,
+    body_size: f32,
+    line_numbers: bool,
+    fonts: FontConfig,
+    headers: Option<(Config, tera::Context)>,
 }
 
 impl PdfGenerator {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
+    pub fn new(layout: PageLayout) -> Self {
+        Self {
             image_handler: ImageHandler::new(),
-            layout: PageLayout::default(),
-            page_info: PageInfo {
-                current_page: 1,
-                total_pages: 0,
-                title: "Professional Document".to_string(),
-            },
-        })
+            layout,
+            title: "Document".to_string(),
+            body_size: FontSizes::default().body,
+            line_numbers: false,
+            fonts: FontConfig::default(),
+            headers: None,
+        }
     }
 
-    pub fn generate_pdf(&mut self, elements: &[DocumentElement], output_path: &str) -> Result<()> {
-        // First pass: count pages (simplified estimation)
-        self.page_info.total_pages = self.estimate_total_pages(elements);
-        
-        let (doc, page1, layer1) = PdfDocument::new("Professional Markdown to PDF", self.layout.width, self.layout.height, "Layer 1");
-        let mut current_layer_ref = doc.get_page(page1).get_layer(layer1);
-
-        // Load professional fonts
-        let font_regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
-        let font_bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
-        let font_italic = doc.add_builtin_font(BuiltinFont::HelveticaOblique)?;
-        let font_bold_italic = doc.add_builtin_font(BuiltinFont::HelveticaBoldOblique)?;
-        let font_code = doc.add_builtin_font(BuiltinFont::Courier)?;
-
-        let font_system = FontSystem {
-            regular: font_regular,
-            bold: font_bold,
-            italic: font_italic,
-            bold_italic: font_bold_italic,
-            code: font_code,
-        };
+    /// Directory that relative image paths are resolved against
+    pub fn with_base_path(mut self, base_path: &Path) -> Self {
+        self.image_handler = ImageHandler::new().with_base_path(base_path);
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Body text size in points; headings and code scale with it
+    pub fn with_body_size(mut self, body_size: f32) -> Self {
+        self.body_size = body_size;
+        self
+    }
+
+    pub fn with_line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Custom fonts to embed; see `FontSystem::load`
+    pub fn with_fonts(mut self, fonts: FontConfig) -> Self {
+        self.fonts = fonts;
+        self
+    }
+
+    /// Draw the headers and footers in `config.page` once the pages are laid
+    /// out, rendering their templates with `context`
+    pub fn with_headers(mut self, config: Config, context: tera::Context) -> Self {
+        self.headers = Some((config, context));
+        self
+    }
 
-        let text_renderer = TextRenderer::new(font_system.clone());
-        
-        // Professional page layout
-        let content_width = self.get_content_width();
-        let mut y_position = self.get_content_start_y();
-        
-        // Add header and footer to first page
-        self.add_header_footer(&current_layer_ref, self.page_info.current_page, &font_system, &text_renderer);
+    pub fn generate_pdf(&self, elements: &[DocumentElement]) -> Result<GeneratedPdf> {
+        let (doc, page1, layer1) = PdfDocument::new(&self.title, self.layout.width, self.layout.height, "Layer 1");
+
+        let font_system = FontSystem::load(&doc, &self.fonts)?;
+        let text_renderer = TextRenderer::with_custom_sizes(font_system.clone(), FontSizes::for_body(self.body_size));
+
+        let content_width = self.layout.content_width();
+        let left = self.layout.margin_left;
+        let block_spacing = text_renderer.calculate_paragraph_spacing(self.body_size);
+        let first_layer = doc.get_page(page1).get_layer(layer1);
+        let mut cursor = PageCursor {
+            doc: &doc,
+            layout: &self.layout,
+            layer: first_layer.clone(),
+            layers: vec![first_layer],
+            page: 1,
+            y: self.layout.content_top(),
+        };
 
-        let mut list_counters: Vec<u64> = Vec::new();
+        let mut headings = Vec::new();
         let mut footnotes = Vec::new();
         let mut in_list = false;
-        let mut _current_list_type: Option<ListType> = None;
 
         for element in elements {
-            // Check if we need a new page
-            let min_space_needed = self.calculate_element_height(element, &text_renderer, content_width);
-            if y_position - min_space_needed < self.layout.margin_bottom + self.layout.footer_height + Mm(10.0) {
-                self.page_info.current_page += 1;
-                let (new_page, new_layer) = doc.add_page(self.layout.width, self.layout.height, "Layer 1");
-                current_layer_ref = doc.get_page(new_page).get_layer(new_layer);
-                y_position = self.get_content_start_y();
-                
-                // Add header and footer to new page
-                self.add_header_footer(&current_layer_ref, self.page_info.current_page, &font_system, &text_renderer);
+            let is_list_item = matches!(element.element_type, ElementType::ListItem | ElementType::TaskListItem);
+            if in_list && !is_list_item {
+                cursor.skip(block_spacing); // Space after list
             }
+            in_list = is_list_item;
 
-            // Handle list state changes
-            match &element.element_type {
-                ElementType::ListItem | ElementType::TaskListItem => {
-                    if !in_list {
-                        in_list = true;
-                        _current_list_type = element.list_type.clone();
-                        if matches!(element.list_type, Some(ListType::Ordered(_))) {
-                            if let Some(ListType::Ordered(start)) = &element.list_type {
-                                list_counters.push(*start);
-                            }
-                        }
-                        y_position -= Mm(4.0); // Space before list
-                    }
-                }
-                _ => {
-                    if in_list {
-                        in_list = false;
-                        _current_list_type = None;
-                        list_counters.clear();
-                        y_position -= Mm(6.0); // Space after list
-                    }
-                }
-            }
-            
             match element.element_type {
                 ElementType::Heading => {
                     let level = element.level.unwrap_or(1);
-                    y_position -= text_renderer.calculate_heading_spacing_before(level);
-                    
-                    let (height_used, _) = text_renderer.render_heading(
-                        &current_layer_ref,
-                        &self.process_formatting(&element.content, &element.formatting),
+                    let font_size = text_renderer.heading_font_size(level);
+                    let lines = text_renderer.wrap_text(&element.content, content_width, font_size, &text_renderer.get_fonts().bold);
+                    let height = text_renderer.calculate_line_height(font_size) * lines.len() as f32;
+                    let spacing_after = text_renderer.calculate_heading_spacing_after(level);
+
+                    // Keep the heading with the first lines that follow it
+                    cursor.skip(text_renderer.calculate_heading_spacing_before(level));
+                    cursor.reserve(height + spacing_after + text_renderer.calculate_line_height(self.body_size) * 2.0);
+
+                    headings.push(HeadingPosition {
                         level,
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                    );
-                    
-                    y_position -= height_used + text_renderer.calculate_heading_spacing_after(level);
+                        text: element.content.clone(),
+                        page: cursor.page,
+                        top: cursor.y,
+                    });
+                    text_renderer.render_heading(&cursor.layer, &element.content, level, left, cursor.y, content_width);
+                    cursor.y -= height + spacing_after;
                 }
 
                 ElementType::Paragraph => {
-                    if !in_list {
-                        y_position -= text_renderer.calculate_paragraph_spacing(text_renderer.get_font_size("body"));
-                    }
-                    
-                    let processed_text = self.process_formatting(&element.content, &element.formatting);
-                    let (height_used, _) = text_renderer.render_formatted_text(
-                        &current_layer_ref,
-                        &processed_text,
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                        true,
+                    let style = text_renderer.body_style(true);
+                    let lines = text_renderer.wrap_formatted(
+                        &expand_links(&element.spans),
                         &element.formatting,
-                        text_renderer.get_font_size("body"),
+                        content_width,
+                        style.font_size,
                     );
-                    
-                    y_position -= height_used;
-                    if !in_list {
-                        y_position -= text_renderer.calculate_paragraph_spacing(text_renderer.get_font_size("body"));
-                    }
+                    self.flow_lines(&mut cursor, &text_renderer, &lines, &style);
+                    cursor.y -= block_spacing;
                 }
 
-                ElementType::ListItem => {
-                    let list_type = element.list_type.as_ref().unwrap_or(&ListType::Bullet);
-                    let bullet = match list_type {
-                        ListType::Bullet => "•".to_string(),
-                        ListType::Ordered(_) => {
-                            let counter = if let Some(last) = list_counters.last_mut() {
-                                let current = *last;
-                                *last += 1;
-                                current
-                            } else {
-                                1
-                            };
-                            format!("{}.", counter)
-                        }
-                        ListType::Task => "☐".to_string(),
+                ElementType::ListItem | ElementType::TaskListItem => {
+                    let depth = element.level.unwrap_or(0) as usize;
+                    let indent = Mm(8.0 * depth as f32);
+                    let bullet = match (element.is_checked, &element.list_type) {
+                        (Some(true), _) => "[x]".to_string(),
+                        (Some(false), _) => "[ ]".to_string(),
+                        (None, Some(ListType::Ordered(number))) => format!("{number}."),
+                        (None, _) => BULLETS[depth % BULLETS.len()].to_string(),
                     };
-                    
-                    let (height_used, _) = text_renderer.render_list_item(
-                        &current_layer_ref,
-                        &self.process_formatting(&element.content, &element.formatting),
-                        &bullet,
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                        0,
-                    );
-                    
-                    y_position -= height_used + Mm(3.0);
-                }
 
-                ElementType::TaskListItem => {
-                    let checkbox = if element.is_checked.unwrap_or(false) { "☑" } else { "☐" };
-                    
-                    let (height_used, _) = text_renderer.render_list_item(
-                        &current_layer_ref,
-                        &self.process_formatting(&element.content, &element.formatting),
-                        checkbox,
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                        0,
+                    let spans = expand_links(&element.spans);
+                    let lines = text_renderer.list_item_lines(&spans, content_width - indent);
+                    cursor.reserve(text_renderer.calculate_line_height(self.body_size) * lines.len() as f32);
+
+                    let (height, _) = text_renderer.render_list_item(
+                        &cursor.layer,
+                        &spans,
+                        &bullet,
+                        left + indent,
+                        cursor.y,
+                        content_width - indent,
                     );
-                    
-                    y_position -= height_used + Mm(3.0);
+                    cursor.y -= height + Mm(1.0);
                 }
 
                 ElementType::BlockQuote => {
-                    y_position -= Mm(8.0);
-                    
-                    let (height_used, _) = text_renderer.render_blockquote(
-                        &current_layer_ref,
-                        &self.process_formatting(&element.content, &element.formatting),
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                    );
-                    
-                    y_position -= height_used + Mm(8.0);
+                    let spans = expand_links(&element.spans);
+                    let lines = text_renderer.blockquote_lines(&spans, content_width);
+                    cursor.reserve(text_renderer.blockquote_height(lines.len()));
+
+                    let (height, _) = text_renderer.render_blockquote(&cursor.layer, &spans, left, cursor.y, content_width);
+                    cursor.y -= height + block_spacing;
                 }
 
                 ElementType::Table => {
                     if let Some(table_data) = &element.table_data {
-                        y_position -= Mm(10.0);
-                        let table_height = self.render_professional_table(
-                            &current_layer_ref,
-                            table_data,
-                            self.layout.margin_left,
-                            y_position,
-                            content_width,
-                            &font_system,
-                            &text_renderer,
-                        )?;
-                        y_position -= table_height + Mm(10.0);
+                        self.render_professional_table(&mut cursor, table_data, &text_renderer);
+                        cursor.y -= block_spacing;
                     }
                 }
 
-                ElementType::Code => {
-                    let font_size = text_renderer.get_font_size("code");
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().code.clone());
-                    current_layer_ref.use_text(
-                        &format!("`{}`", element.content),
-                        font_size,
-                        self.layout.margin_left,
-                        y_position,
-                        &font_system.code,
-                    );
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-                    y_position -= text_renderer.calculate_line_height(font_size) + Mm(2.0);
-                }
-
                 ElementType::CodeBlock => {
-                    y_position -= Mm(10.0);
-                    
-                    let (height_used, _) = text_renderer.render_code_block(
-                        &current_layer_ref,
-                        &element.content,
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                        true,
-                    );
-                    
-                    y_position -= height_used + Mm(10.0);
-                }
-
-                ElementType::Link => {
-                    let link_text = if let Some(url) = &element.url {
-                        format!("{} ({})", element.content, url)
-                    } else {
-                        element.content.clone()
-                    };
-                    
-                    let font_size = text_renderer.get_font_size("body");
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().link.clone());
-                    current_layer_ref.use_text(
-                        &link_text,
-                        font_size,
-                        self.layout.margin_left,
-                        y_position,
-                        &font_system.regular,
-                    );
-                    
-                    // Add underline to links
-                    let text_width = text_renderer.calculate_text_width(&link_text, font_size);
-                    text_renderer.draw_line(
-                        &current_layer_ref,
-                        self.layout.margin_left,
-                        y_position - Mm(2.0),
-                        self.layout.margin_left + text_width,
-                        y_position - Mm(2.0),
-                        0.5,
-                    );
-                    
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-                    y_position -= text_renderer.calculate_line_height(font_size) + Mm(2.0);
+                    self.render_code_block(&mut cursor, &element.content, &text_renderer);
+                    cursor.y -= block_spacing;
                 }
 
                 ElementType::Image => {
                     if let Some(url) = &element.url {
-                        y_position -= Mm(16.0); // More space before image
-                        
-                        // Professional image sizing with better proportions
-                        let max_image_width = content_width * 0.85; // Slightly smaller for better margins
-                        let max_image_height = Mm(180.0); // Allow larger images
-                        
-                        match self.image_handler.add_image_to_pdf_sync(
-                            &current_layer_ref,
-                            url,
-                            self.layout.margin_left + (content_width - max_image_width) / 2.0,
-                            y_position,
-                            max_image_width,
-                            max_image_height,
-                        ) {
-                            Ok(image_height) => {
-                                // Image positioning without unnecessary boxes
-                                
-                                // Simple image caption without background box
-                                if !element.content.is_empty() {
-                                    let caption_y = y_position - image_height - Mm(8.0);
-                                    let caption_font_size = text_renderer.get_font_size("caption");
-                                    let caption_width = text_renderer.calculate_text_width(&element.content, caption_font_size);
-                                    let caption_x = self.layout.margin_left + (content_width - caption_width) / 2.0;
-                                    
-                                    // Simple caption text
-                                    current_layer_ref.set_fill_color(text_renderer.get_colors().blockquote.clone());
-                                    current_layer_ref.use_text(
-                                        &format!("Figure: {}", element.content),
-                                        caption_font_size,
-                                        caption_x,
-                                        caption_y,
-                                        &font_system.italic,
-                                    );
-                                    current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-                                    y_position = caption_y - text_renderer.calculate_line_height(caption_font_size) - Mm(12.0);
-                                } else {
-                                    y_position -= image_height + Mm(16.0);
-                                }
-                            }
-                            Err(e) => {
-                                // Professional placeholder for missing images
-                                let placeholder_height = Mm(60.0);
-                                let placeholder_width = content_width * 0.7;
-                                let placeholder_x = self.layout.margin_left + (content_width - placeholder_width) / 2.0;
-                                
-                                // Draw placeholder background
-                                text_renderer.draw_rectangle(
-                                    &current_layer_ref,
-                                    placeholder_x,
-                                    y_position,
-                                    placeholder_width,
-                                    placeholder_height,
-                                    Some(Color::Rgb(Rgb::new(0.95, 0.95, 0.95, None))), // Light gray
-                                    Some(Color::Rgb(Rgb::new(0.8, 0.8, 0.8, None))), // Dashed border effect
-                                    1.0,
-                                );
-                                
-                                // Placeholder text
-                                let placeholder_text = "Image not available";
-                                let error_text = format!("Error: {}", e.to_string().chars().take(50).collect::<String>());
-                                
-                                current_layer_ref.set_fill_color(text_renderer.get_colors().blockquote.clone());
-                                current_layer_ref.use_text(
-                                    placeholder_text,
-                                    text_renderer.get_font_size("body"),
-                                    placeholder_x + Mm(8.0),
-                                    y_position - Mm(20.0),
-                                    &font_system.bold,
-                                );
-                                
-                                current_layer_ref.use_text(
-                                    &error_text,
-                                    text_renderer.get_font_size("small"),
-                                    placeholder_x + Mm(8.0),
-                                    y_position - Mm(35.0),
-                                    &font_system.italic,
-                                );
-                                
-                                eprintln!("Image loading failed: {}", e);
-                                current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-                                y_position -= placeholder_height + Mm(16.0);
-                            }
-                        }
+                        self.render_image(&mut cursor, url, &element.content, &text_renderer);
+                        cursor.y -= block_spacing;
                     }
                 }
 
                 ElementType::HorizontalRule => {
-                    y_position -= Mm(16.0);
-                    
-                    // Draw professional decorative horizontal rule with gradient effect
+                    cursor.skip(block_spacing);
+
+                    // Decorative rule across most of the text width
                     let rule_width = content_width * 0.8;
-                    let rule_x = self.layout.margin_left + (content_width - rule_width) / 2.0;
-                    
-                    // Main rule line
-                    current_layer_ref.set_outline_color(Color::Rgb(Rgb::new(0.4, 0.5, 0.7, None)));
-                    text_renderer.draw_line(
-                        &current_layer_ref,
-                        rule_x,
-                        y_position,
-                        rule_x + rule_width,
-                        y_position,
-                        1.5,
-                    );
-                    
-                    // Add decorative dots at the ends
-                    current_layer_ref.set_fill_color(Color::Rgb(Rgb::new(0.4, 0.5, 0.7, None)));
-                    current_layer_ref.use_text(
-                        "●",
-                        text_renderer.get_font_size("small"),
-                        rule_x - Mm(4.0),
-                        y_position + Mm(1.0),
-                        &text_renderer.get_fonts().regular,
-                    );
-                    current_layer_ref.use_text(
-                        "●",
-                        text_renderer.get_font_size("small"),
-                        rule_x + rule_width + Mm(1.0),
-                        y_position + Mm(1.0),
-                        &text_renderer.get_fonts().regular,
-                    );
-                    
-                    // Reset color
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-                    y_position -= Mm(16.0);
+                    let rule_x = left + (content_width - rule_width) / 2.0;
+                    cursor.layer.set_outline_color(Color::Rgb(Rgb::new(0.4, 0.5, 0.7, None)));
+                    text_renderer.draw_line(&cursor.layer, rule_x, cursor.y, rule_x + rule_width, cursor.y, 1.0);
+                    cursor.y -= block_spacing * 2.0;
                 }
 
                 ElementType::Footnote => {
-                    footnotes.push(element.clone());
-                }
-
-                ElementType::FootnoteReference => {
-                    let reference_text = format!("[{}]", element.content);
-                    let font_size = text_renderer.get_font_size("small");
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().link.clone());
-                    current_layer_ref.use_text(
-                        &reference_text,
-                        font_size,
-                        self.layout.margin_left,
-                        y_position + Mm(2.0), // Superscript effect
-                        &font_system.regular,
-                    );
-                    current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-                }
-
-                _ => {
-                    y_position -= Mm(4.0);
+                    footnotes.push(element);
                 }
             }
         }
 
-        // Add professional footnotes section
+        // Footnotes are collected into a notes section at the end
         if !footnotes.is_empty() {
-            y_position -= Mm(24.0);
-            
-            // Footnotes separator line
-            text_renderer.draw_line(
-                &current_layer_ref,
-                self.layout.margin_left,
-                y_position,
-                self.layout.margin_left + content_width * 0.3,
-                y_position,
-                1.0,
-            );
-            y_position -= Mm(8.0);
-            
-            // Footnotes title
-            current_layer_ref.set_fill_color(text_renderer.get_colors().heading.clone());
-            current_layer_ref.use_text(
-                "References",
-                text_renderer.get_font_size("h6"),
-                self.layout.margin_left,
-                y_position,
-                &font_system.bold,
-            );
-            current_layer_ref.set_fill_color(text_renderer.get_colors().text.clone());
-            y_position -= Mm(12.0);
-            
+            cursor.skip(block_spacing * 2.0);
+            cursor.reserve(Mm(20.0));
+
+            cursor.layer.set_outline_color(text_renderer.get_colors().table_border.clone());
+            text_renderer.draw_line(&cursor.layer, left, cursor.y, left + content_width * 0.3, cursor.y, 0.75);
+            cursor.y -= Mm(4.0);
+
+            let style = LineStyle {
+                font_size: text_renderer.get_font_size("small"),
+                color: text_renderer.get_colors().text.clone(),
+                justify: false,
+            };
             for footnote in footnotes {
-                if let Some(label) = &footnote.url {
-                    let footnote_text = format!("[{}]: {}", label, footnote.content);
-                    let (height_used, _) = text_renderer.render_paragraph(
-                        &current_layer_ref,
-                        &footnote_text,
-                        self.layout.margin_left,
-                        y_position,
-                        content_width,
-                        false,
-                    );
-                    y_position -= height_used + Mm(4.0);
-                }
+                let label = footnote.url.as_deref().unwrap_or_default();
+                let mut spans = vec![TextSpan::plain(format!("[{label}] "))];
+                spans.extend(expand_links(&footnote.spans));
+                let lines = text_renderer.wrap_formatted(&spans, &[], content_width, style.font_size);
+                self.flow_lines(&mut cursor, &text_renderer, &lines, &style);
+                cursor.y -= Mm(1.5);
             }
         }
 
-        // Save the professional PDF
-        doc.save(&mut BufWriter::new(
-            File::create(output_path).context("Failed to create output file")?,
-        ))?;
+        if let Some((config, context)) = &self.headers {
+            self.draw_headers(&cursor.layers, &headings, config, context, &text_renderer)?;
+        }
 
-        Ok(())
-    }
+        let missing = font_system.missing_glyphs();
+        if !missing.is_empty() {
+            Logger::warning(format!(
+                "No font covers {}; add a font with these characters to fonts.custom_fonts",
+                missing.iter().take(20).collect::<String>()
+            ));
+        }
 
-    fn get_content_width(&self) -> Mm {
-        self.layout.width - self.layout.margin_left - self.layout.margin_right
-    }
+        let page_count = cursor.page;
+        let data = doc.save_to_bytes().context("Failed to write native PDF")?;
 
-    fn get_content_start_y(&self) -> Mm {
-        self.layout.height - self.layout.margin_top - self.layout.header_height
+        Ok(GeneratedPdf { data, page_count, headings })
     }
 
-    fn add_header_footer(&self, layer: &PdfLayerReference, page_num: usize, font_system: &FontSystem, text_renderer: &TextRenderer) {
-        let colors = text_renderer.get_colors();
-        
-        // Clean Header without background box
-        let header_y = self.layout.height - self.layout.margin_top + Mm(8.0);
-        
-        // Header title
-        layer.set_fill_color(Color::Rgb(Rgb::new(0.2, 0.3, 0.5, None))); // Professional blue
-        layer.use_text(
-            &self.page_info.title,
-            text_renderer.get_font_size("small"),
-            self.layout.margin_left,
-            header_y,
-            &font_system.bold,
-        );
-        
-        // Remove date/time from header
-        
-        // Professional header line with gradient effect
-        layer.set_outline_color(Color::Rgb(Rgb::new(0.3, 0.4, 0.6, None)));
-        text_renderer.draw_line(
-            layer,
-            self.layout.margin_left,
-            header_y - Mm(4.0),
-            self.layout.width - self.layout.margin_right,
-            header_y - Mm(4.0),
-            1.0,
-        );
-        
-        // Clean Footer without background box
-        let footer_y = self.layout.margin_bottom - Mm(3.0);
-        
-        // Footer line above text
-        layer.set_outline_color(Color::Rgb(Rgb::new(0.3, 0.4, 0.6, None)));
-        text_renderer.draw_line(
-            layer,
-            self.layout.margin_left,
-            footer_y + Mm(5.0),
-            self.layout.width - self.layout.margin_right,
-            footer_y + Mm(5.0),
-            1.0,
-        );
-        
-        // Professional page numbering
-        let page_text = format!("Page {} of {}", page_num, self.page_info.total_pages);
-        let page_width = text_renderer.calculate_text_width(&page_text, text_renderer.get_font_size("small"));
-        layer.set_fill_color(colors.text.clone());
-        layer.use_text(
-            &page_text,
-            text_renderer.get_font_size("small"),
-            self.layout.width - self.layout.margin_right - page_width,
-            footer_y,
-            &font_system.regular,
-        );
-        
-        // Footer branding
-        let branding = "Generated by Professional MD-to-PDF Converter";
-        layer.set_fill_color(colors.blockquote.clone());
-        layer.use_text(
-            branding,
-            text_renderer.get_font_size("small") * 0.85,
-            self.layout.margin_left,
-            footer_y,
-            &font_system.italic,
-        );
+    /// Headers and footers in the document's regular font chain, so they can
+    /// show any character the body text can
+    fn draw_headers(
+        &self,
+        layers: &[PdfLayerReference],
+        headings: &[HeadingPosition],
+        config: &Config,
+        context: &tera::Context,
+        text_renderer: &TextRenderer,
+    ) -> Result<()> {
+        let chapters: Vec<(u32, String)> = headings.iter()
+            .filter(|heading| heading.level == 1)
+            .map(|heading| (heading.page, heading.text.clone()))
+            .collect();
+        let texts = pdf_headers::page_texts(config, context, &chapters, layers.len() as u32)?;
+
+        let font = &text_renderer.get_fonts().regular;
+        let page_size = (Pt::from(self.layout.width).0, Pt::from(self.layout.height).0);
+        let gray = pdf_headers::TEXT_GRAY;
+        for (layer, text) in layers.iter().zip(&texts) {
+            layer.set_fill_color(Color::Rgb(Rgb::new(gray, gray, gray, None)));
+            for placed in pdf_headers::place(text, config, page_size, |text, size| Pt::from(font.width(text, size)).0) {
+                text_renderer.draw_text(layer, placed.text, placed.size, Mm::from(Pt(placed.x)), Mm::from(Pt(placed.y)), font);
+            }
+        }
+        Ok(())
     }
 
-    fn estimate_total_pages(&self, elements: &[DocumentElement]) -> usize {
-        // Simple estimation - count elements and estimate space usage
-        let estimated_height_per_page = self.get_content_start_y() - self.layout.margin_bottom - self.layout.footer_height;
-        let mut total_estimated_height = Mm(0.0);
-        
-        for element in elements {
-            let estimated_height = match element.element_type {
-                ElementType::Heading => Mm(20.0),
-                ElementType::Paragraph => Mm(15.0),
-                ElementType::ListItem | ElementType::TaskListItem => Mm(8.0),
-                ElementType::BlockQuote => Mm(20.0),
-                ElementType::Table => Mm(50.0),
-                ElementType::CodeBlock => Mm(30.0),
-                ElementType::Image => Mm(100.0),
-                ElementType::HorizontalRule => Mm(24.0),
-                _ => Mm(10.0),
-            };
-            total_estimated_height += estimated_height;
+    /// Draw wrapped lines at the left margin, continuing on new pages as needed
+    fn flow_lines(&self, cursor: &mut PageCursor, text_renderer: &TextRenderer, lines: &[TextLine], style: &LineStyle) {
+        let line_height = text_renderer.calculate_line_height(style.font_size);
+        let mut rest = lines;
+
+        while !rest.is_empty() {
+            let fit = (cursor.remaining().0 / line_height.0).floor().max(0.0) as usize;
+            if fit == 0 && !cursor.at_top() {
+                cursor.new_page();
+                continue;
+            }
+
+            let (chunk, remaining) = rest.split_at(fit.clamp(1, rest.len()));
+            cursor.y -= text_renderer.render_lines(
+                &cursor.layer,
+                chunk,
+                self.layout.margin_left,
+                cursor.y,
+                self.layout.content_width(),
+                style,
+            );
+            rest = remaining;
         }
-        
-        ((total_estimated_height.0 / estimated_height_per_page.0).ceil() as usize).max(1)
     }
-    
-    fn calculate_element_height(&self, element: &DocumentElement, text_renderer: &TextRenderer, content_width: Mm) -> Mm {
-        match element.element_type {
-            ElementType::Heading => {
-                let level = element.level.unwrap_or(1);
-                text_renderer.calculate_heading_spacing_before(level) + 
-                text_renderer.calculate_line_height(text_renderer.get_font_size(&format!("h{}", level))) +
-                text_renderer.calculate_heading_spacing_after(level)
+
+    /// Code blocks longer than the space left are split across pages
+    fn render_code_block(&self, cursor: &mut PageCursor, code: &str, text_renderer: &TextRenderer) {
+        let lines: Vec<&str> = code.lines().collect();
+        let line_height = text_renderer.calculate_line_height(text_renderer.get_font_size("code"));
+        let mut start = 0;
+
+        while start < lines.len() {
+            let available = cursor.remaining() - text_renderer.code_block_height(0);
+            let fit = (available.0 / line_height.0).floor().max(0.0) as usize;
+            if fit == 0 && !cursor.at_top() {
+                cursor.new_page();
+                continue;
             }
-            ElementType::Paragraph => {
-                let font_size = text_renderer.get_font_size("body");
-                let lines = text_renderer.wrap_text(&element.content, content_width, font_size);
-                text_renderer.calculate_line_height(font_size) * lines.len() as f32 + Mm(8.0)
+
+            let end = (start + fit.max(1)).min(lines.len());
+            let (height, _) = text_renderer.render_code_block(
+                &cursor.layer,
+                &lines[start..end].join("\n"),
+                self.layout.margin_left,
+                cursor.y,
+                self.layout.content_width(),
+                self.line_numbers.then_some(start + 1),
+            );
+            cursor.y -= height;
+            start = end;
+        }
+    }
+
+    /// Centred image scaled to fit, with its alt text as a caption
+    fn render_image(&self, cursor: &mut PageCursor, url: &str, caption: &str, text_renderer: &TextRenderer) {
+        let content_width = self.layout.content_width();
+        let caption_size = text_renderer.get_font_size("caption");
+        let caption_height = if caption.is_empty() {
+            Mm(0.0)
+        } else {
+            text_renderer.calculate_line_height(caption_size) + Mm(2.0)
+        };
+
+        match self.image_handler.load(url) {
+            Ok(image) => {
+                let (width, height) = self.image_handler.calculate_dimensions(
+                    &image,
+                    content_width,
+                    self.layout.content_height() - caption_height,
+                );
+                cursor.reserve(height + caption_height);
+
+                let x = self.layout.margin_left + (content_width - width) / 2.0;
+                self.image_handler.add_image_to_layer(&cursor.layer, &image, x, cursor.y, width, height);
+                cursor.y -= height;
             }
-            ElementType::Table => Mm(60.0), // Conservative estimate
-            ElementType::Image => Mm(120.0), // Conservative estimate
-            ElementType::CodeBlock => {
-                let lines = element.content.lines().count();
-                text_renderer.calculate_line_height(text_renderer.get_font_size("code")) * lines as f32 + Mm(20.0)
+            Err(e) => {
+                Logger::warning(format!("Image loading failed: {e}"));
+
+                // Placeholder box for missing images
+                let placeholder_height = Mm(20.0);
+                let placeholder_width = content_width * 0.7;
+                let placeholder_x = self.layout.margin_left + (content_width - placeholder_width) / 2.0;
+                cursor.reserve(placeholder_height + caption_height);
+
+                text_renderer.draw_rectangle(
+                    &cursor.layer,
+                    placeholder_x,
+                    cursor.y,
+                    placeholder_width,
+                    placeholder_height,
+                    Some(Color::Rgb(Rgb::new(0.95, 0.95, 0.95, None))), // Light gray
+                );
+                cursor.layer.set_fill_color(text_renderer.get_colors().blockquote.clone());
+                text_renderer.draw_text(
+                    &cursor.layer,
+                    &format!("Image not available: {url}"),
+                    text_renderer.get_font_size("small"),
+                    placeholder_x + Mm(4.0),
+                    cursor.y - placeholder_height / 2.0,
+                    &text_renderer.get_fonts().italic,
+                );
+                cursor.y -= placeholder_height;
             }
-            _ => Mm(15.0),
+        }
+
+        if !caption.is_empty() {
+            let caption_width = text_renderer.get_fonts().italic.width(caption, caption_size);
+            cursor.y -= Mm(2.0);
+            cursor.layer.set_fill_color(text_renderer.get_colors().blockquote.clone());
+            text_renderer.draw_text(
+                &cursor.layer,
+                caption,
+                caption_size,
+                self.layout.margin_left + (content_width - caption_width) / 2.0,
+                cursor.y - text_renderer.baseline_offset(caption_size),
+                &text_renderer.get_fonts().italic,
+            );
+            cursor.layer.set_fill_color(text_renderer.get_colors().text.clone());
+            cursor.y -= text_renderer.calculate_line_height(caption_size);
         }
     }
 
-    fn render_professional_table(
-        &self,
-        layer: &printpdf::PdfLayerReference,
-        table_data: &TableData,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        font_system: &FontSystem,
-        text_renderer: &TextRenderer,
-    ) -> Result<Mm> {
-        let cell_padding = Mm(4.0);
-        let min_cell_height = Mm(15.0);
+    /// Table with a shaded header row and zebra striping. Rows that do not
+    /// fit move to the next page, which repeats the header.
+    fn render_professional_table(&self, cursor: &mut PageCursor, table_data: &TableData, text_renderer: &TextRenderer) {
+        let cell_padding = Mm(2.0);
         let border_thickness = 0.8;
-        
+        let font_size = text_renderer.get_font_size("body");
+        let line_height = text_renderer.calculate_line_height(font_size);
+        let x = self.layout.margin_left;
+        let max_width = self.layout.content_width();
+
         let col_count = table_data.headers.len().max(
             table_data.rows.iter().map(|row| row.len()).max().unwrap_or(0)
         );
-        
         if col_count == 0 {
-            return Ok(Mm(0.0));
+            return;
         }
-        
+
         let cell_width = max_width / col_count as f32;
-        let row_count = if table_data.headers.is_empty() { 0 } else { 1 } + table_data.rows.len();
-        
-        // Calculate dynamic row heights based on content
-        let mut row_heights = Vec::new();
-        
-        // Header height
-        if !table_data.headers.is_empty() {
-            let mut max_lines = 1;
-            for header in &table_data.headers {
-                let lines = text_renderer.wrap_text(header, cell_width - cell_padding * 2.0, text_renderer.get_font_size("body"));
-                max_lines = max_lines.max(lines.len());
-            }
-            let header_height = text_renderer.calculate_line_height(text_renderer.get_font_size("body")) * max_lines as f32 + cell_padding * 2.0;
-            row_heights.push(header_height.max(min_cell_height));
-        }
-        
-        // Row heights
-        for row in &table_data.rows {
-            let mut max_lines = 1;
-            for cell in row {
-                let lines = text_renderer.wrap_text(cell, cell_width - cell_padding * 2.0, text_renderer.get_font_size("body"));
-                max_lines = max_lines.max(lines.len());
-            }
-            let row_height = text_renderer.calculate_line_height(text_renderer.get_font_size("body")) * max_lines as f32 + cell_padding * 2.0;
-            row_heights.push(row_height.max(min_cell_height));
-        }
-        
-        let total_height: Mm = row_heights.iter().fold(Mm(0.0), |acc, &h| acc + h);
-        
-        let mut current_y = y;
-        let mut row_index = 0;
-        
-        // Set table colors
+        let cell_font = |header: bool| -> &FontChain {
+            if header { &text_renderer.get_fonts().bold } else { &text_renderer.get_fonts().regular }
+        };
+        let row_height = |cells: &[String], header: bool| -> Mm {
+            let max_lines = cells.iter()
+                .map(|cell| text_renderer.wrap_text(cell, cell_width - cell_padding * 2.0, font_size, cell_font(header)).len())
+                .max()
+                .unwrap_or(1);
+            line_height * max_lines as f32 + cell_padding * 2.0
+        };
+        let header_height = if table_data.headers.is_empty() { Mm(0.0) } else { row_height(&table_data.headers, true) };
         let colors = text_renderer.get_colors();
-        layer.set_outline_color(colors.table_border.clone());
-        layer.set_outline_thickness(border_thickness);
-        
-        // Draw table outer border with rounded corners effect
-        text_renderer.draw_rectangle(
-            layer,
-            x,
-            current_y,
-            max_width,
-            total_height,
-            None,
-            Some(colors.table_border.clone()),
-            border_thickness * 1.5,
-        );
-        
-        // Draw headers with professional background
-        if !table_data.headers.is_empty() {
-            let header_height = row_heights[row_index];
-            
-            // Header background with gradient-like effect (dark header)
-            text_renderer.draw_rectangle(
-                layer,
-                x,
-                current_y,
-                max_width,
-                header_height,
-                Some(Color::Rgb(Rgb::new(0.25, 0.35, 0.55, None))), // Professional blue-gray
-                None,
-                0.0,
-            );
-            
-            for (i, header) in table_data.headers.iter().enumerate() {
+
+        let draw_cells = |layer: &PdfLayerReference, cells: &[String], y: Mm, height: Mm, header: bool| {
+            for (i, cell) in cells.iter().take(col_count).enumerate() {
                 let cell_x = x + cell_width * i as f32;
-                
-                // Vertical line between headers
+
+                // Vertical line between cells
                 if i > 0 {
-                    text_renderer.draw_line(
-                        layer,
-                        cell_x,
-                        current_y,
-                        cell_x,
-                        current_y - header_height,
-                        border_thickness,
-                    );
+                    layer.set_outline_color(if header { colors.table_border.clone() } else { Color::Rgb(Rgb::new(0.85, 0.85, 0.85, None)) });
+                    text_renderer.draw_line(layer, cell_x, y, cell_x, y - height, border_thickness * 0.7);
                 }
-                
+
                 // Header text in white for contrast
-                layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None))); // White text
-                let wrapped_text = text_renderer.wrap_text(header, cell_width - cell_padding * 2.0, text_renderer.get_font_size("body"));
-                let line_height = text_renderer.calculate_line_height(text_renderer.get_font_size("body"));
-                let mut text_y = current_y - cell_padding - line_height * 0.8;
-                
-                for line in wrapped_text {
-                    layer.use_text(
-                        &line,
-                        text_renderer.get_font_size("body"),
-                        cell_x + cell_padding,
-                        text_y,
-                        &font_system.bold,
-                    );
-                    text_y -= line_height;
+                let color = if header { Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None)) } else { colors.text.clone() };
+                layer.set_fill_color(color);
+                let mut baseline = y - cell_padding - text_renderer.baseline_offset(font_size);
+                for line in text_renderer.wrap_text(cell, cell_width - cell_padding * 2.0, font_size, cell_font(header)) {
+                    text_renderer.draw_text(layer, &line, font_size, cell_x + cell_padding, baseline, cell_font(header));
+                    baseline -= line_height;
                 }
             }
-            current_y -= header_height;
-            row_index += 1;
-            
-            // Thick horizontal line after headers
-            text_renderer.draw_line(
-                layer,
+            layer.set_fill_color(colors.text.clone());
+        };
+
+        let draw_header = |cursor: &mut PageCursor| {
+            if table_data.headers.is_empty() {
+                return;
+            }
+            text_renderer.draw_rectangle(
+                &cursor.layer,
                 x,
-                current_y,
-                x + max_width,
-                current_y,
-                border_thickness * 2.0,
+                cursor.y,
+                max_width,
+                header_height,
+                Some(Color::Rgb(Rgb::new(0.25, 0.35, 0.55, None))), // Professional blue-gray
             );
-        }
-        
-        // Draw rows with professional styling
-        layer.set_fill_color(colors.text.clone());
+            draw_cells(&cursor.layer, &table_data.headers, cursor.y, header_height, true);
+            cursor.y -= header_height;
+        };
+
+        cursor.reserve(header_height + table_data.rows.first().map(|row| row_height(row, false)).unwrap_or(Mm(0.0)));
+        let mut table_top = cursor.y;
+        draw_header(cursor);
+
         for (row_idx, row) in table_data.rows.iter().enumerate() {
-            let row_height = row_heights[row_index];
-            
+            let height = row_height(row, false);
+            if cursor.remaining() < height {
+                // Close the part of the table on this page and continue on the next
+                cursor.layer.set_outline_color(colors.table_border.clone());
+                text_renderer.stroke_rectangle(&cursor.layer, x, table_top, max_width, table_top - cursor.y, border_thickness);
+                cursor.new_page();
+                table_top = cursor.y;
+                draw_header(cursor);
+            }
+
             // Professional alternating row colors
             if row_idx % 2 == 1 {
                 text_renderer.draw_rectangle(
-                    layer,
+                    &cursor.layer,
                     x,
-                    current_y,
+                    cursor.y,
                     max_width,
-                    row_height,
+                    height,
                     Some(Color::Rgb(Rgb::new(0.97, 0.98, 0.99, None))), // Very light blue
-                    None,
-                    0.0,
                 );
             }
-            
-            for (i, cell) in row.iter().enumerate() {
-                if i >= col_count { break; }
-                let cell_x = x + cell_width * i as f32;
-                
-                // Vertical line with subtle color
-                if i > 0 {
-                    layer.set_outline_color(Color::Rgb(Rgb::new(0.85, 0.85, 0.85, None)));
-                    text_renderer.draw_line(
-                        layer,
-                        cell_x,
-                        current_y,
-                        cell_x,
-                        current_y - row_height,
-                        border_thickness * 0.7,
-                    );
-                    layer.set_outline_color(colors.table_border.clone());
-                }
-                
-                // Cell text with proper vertical centering
-                let wrapped_text = text_renderer.wrap_text(cell, cell_width - cell_padding * 2.0, text_renderer.get_font_size("body"));
-                let line_height = text_renderer.calculate_line_height(text_renderer.get_font_size("body"));
-                let total_text_height = line_height * wrapped_text.len() as f32;
-                let vertical_offset = (row_height - total_text_height) / 2.0;
-                let mut text_y = current_y - cell_padding - vertical_offset - line_height * 0.8;
-                
-                layer.set_fill_color(colors.text.clone());
-                for line in wrapped_text {
-                    layer.use_text(
-                        &line,
-                        text_renderer.get_font_size("body"),
-                        cell_x + cell_padding,
-                        text_y,
-                        &font_system.regular,
-                    );
-                    text_y -= line_height;
-                }
-            }
-            current_y -= row_height;
-            row_index += 1;
-            
+            draw_cells(&cursor.layer, row, cursor.y, height, false);
+            cursor.y -= height;
+
             // Subtle horizontal line after each row
-            layer.set_outline_color(Color::Rgb(Rgb::new(0.90, 0.90, 0.90, None)));
-            text_renderer.draw_line(
-                layer,
-                x,
-                current_y,
-                x + max_width,
-                current_y,
-                border_thickness * 0.5,
-            );
-            layer.set_outline_color(colors.table_border.clone());
+            cursor.layer.set_outline_color(Color::Rgb(Rgb::new(0.90, 0.90, 0.90, None)));
+            text_renderer.draw_line(&cursor.layer, x, cursor.y, x + max_width, cursor.y, border_thickness * 0.5);
         }
-        
-        Ok(total_height)
+
+        // Outer border last, so row shading does not cover it
+        cursor.layer.set_outline_color(colors.table_border.clone());
+        text_renderer.stroke_rectangle(&cursor.layer, x, table_top, max_width, table_top - cursor.y, border_thickness);
     }
+}
 
-    fn process_formatting(&self, text: &str, _formatting: &[TextFormat]) -> String {
-        // Process embedded formatting markers
-        let mut result = text.to_string();
-        
-        // Handle links
-        while let Some(start) = result.find("[LINK_START:") {
-            if let Some(url_end) = result[start..].find(']') {
-                let url_start = start + 12; // "[LINK_START:".len()
-                let url = &result[url_start..start + url_end];
-                if let Some(link_end) = result[start + url_end..].find("[LINK_END]") {
-                    let text_start = start + url_end + 1;
-                    let text_end = start + url_end + link_end;
-                    let link_text = &result[text_start..text_end];
-                    let replacement = format!("{} ({})", link_text, url);
-                    result.replace_range(start..text_end + 10, &replacement); // 10 = "[LINK_END]".len()
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
+/// Links become `text (url)`; autolinks and links within the document show their text only
+fn expand_links(spans: &[TextSpan]) -> Vec<TextSpan> {
+    let mut expanded = Vec::new();
+    for (index, span) in spans.iter().enumerate() {
+        expanded.push(TextSpan { link: None, ..span.clone() });
+
+        // Add the URL after the last span of each link
+        let Some(url) = &span.link else { continue };
+        if spans.get(index + 1).is_some_and(|next| next.link.as_ref() == Some(url)) {
+            continue;
+        }
+        let start = spans[..index].iter()
+            .rposition(|previous| previous.link.as_ref() != Some(url))
+            .map_or(0, |previous| previous + 1);
+        let text: String = spans[start..=index].iter().map(|span| span.text.as_str()).collect();
+        let shown = url.trim_start_matches("mailto:");
+        if text != shown && !url.starts_with('#') {
+            expanded.push(TextSpan::plain(format!(" ({shown})")));
         }
-        
-        result
     }
-}
\ No newline at end of file
+    expanded
+}
@@ -0,0 +1,391 @@
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use regex::Regex;
+use crate::config::{Config, HeaderFooterConfig, PageNumberPosition};
+use crate::pdf_fonts::HELVETICA_WIDTHS;
+use crate::templates::PageTemplates;
+
+// Resource name for the header/footer font; unlikely to clash with Chrome's /F1...
+const FONT_NAME: &[u8] = b"PcHF";
+pub const TEXT_GRAY: f32 = 0.4;
+
+lazy_static! {
+    static ref CHROME_SPAN_REGEX: Regex = Regex::new(r#"<span class="(pageNumber|totalPages|title|date|url)"></span>"#).expect("Invalid template span regex");
+    static ref TAG_REGEX: Regex = Regex::new(r"<[^>]+>").expect("Invalid tag regex");
+}
+
+/// Left, centre and right text of one page's header and footer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageText {
+    pub header: [String; 3],
+    pub footer: [String; 3],
+}
+
+/// Whether headers and footers must be drawn per page after printing:
+/// first-page, odd/even and running-header templates, or keeping them off the cover
+pub fn varies_by_page(config: &Config) -> bool {
+    let configured: Vec<&HeaderFooterConfig> = [&config.page.header, &config.page.footer]
+        .into_iter()
+        .flatten()
+        .filter(|configured| configured.enabled)
+        .collect();
+    let page_numbers = config.page.page_numbers.as_ref().is_some_and(|page_numbers| page_numbers.enabled);
+
+    configured.iter().any(|configured| configured.varies_by_page())
+        || (config.cover.enabled && (!configured.is_empty() || page_numbers))
+}
+
+/// Resolve every page's header and footer text. `chapters` lists the page
+/// each top-level heading starts on, in document order.
+pub fn page_texts(
+    config: &Config,
+    context: &tera::Context,
+    chapters: &[(u32, String)],
+    page_count: u32,
+) -> Result<Vec<PageText>> {
+    let header = config.page.header_footer(PageNumberPosition::Header);
+    let footer = config.page.header_footer(PageNumberPosition::Footer);
+    let first_number = config.page.page_numbers.as_ref().and_then(|page_numbers| page_numbers.start_from).unwrap_or(1);
+
+    let mut pages = Vec::new();
+    for page in 1..=page_count {
+        let chapter = chapters.iter()
+            .take_while(|(start, _)| *start <= page)
+            .last()
+            .map(|(_, title)| title.as_str())
+            .unwrap_or_default();
+        let opens_chapter = chapters.iter().any(|(start, _)| *start == page);
+        let on_cover = config.cover.enabled && page == 1;
+
+        let mut context = context.clone();
+        context.insert("chapter", chapter);
+        context.insert("page", &(page + first_number - 1));
+        context.insert("total", &(page_count + first_number - 1));
+
+        let render = |configured: &Option<HeaderFooterConfig>| -> Result<[String; 3]> {
+            let Some(configured) = configured else {
+                return Ok(Default::default());
+            };
+            if (on_cover && !configured.show_on_cover) || (opens_chapter && configured.skip_chapter_openings) {
+                return Ok(Default::default());
+            }
+
+            let slots = configured.slots_for_page(page);
+            Ok([
+                render_slot(&slots.left, &context)?,
+                render_slot(&slots.center, &context)?,
+                render_slot(&slots.right, &context)?,
+            ])
+        };
+
+        pages.push(PageText {
+            header: render(&header)?,
+            footer: render(&footer)?,
+        });
+    }
+
+    Ok(pages)
+}
+
+/// Render one slot to plain text. Besides Tera variables, `{page}`, `{total}`,
+/// `{chapter}` and Chrome's template spans are understood.
+fn render_slot(template: &str, context: &tera::Context) -> Result<String> {
+    if template.is_empty() {
+        return Ok(String::new());
+    }
+
+    let value = |name: &str| context.get(name)
+        .map(|value| match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or_default();
+
+    let text = CHROME_SPAN_REGEX.replace_all(template, |caps: &regex::Captures| match &caps[1] {
+        "pageNumber" => "{page}".to_string(),
+        "totalPages" => "{total}".to_string(),
+        "title" => "{{ title }}".to_string(),
+        "date" => chrono::Local::now().format("%-m/%-d/%Y").to_string(),
+        _ => String::new(),
+    });
+    let text = PageTemplates::render_str(&text, context)?
+        .replace("{page}", &value("page"))
+        .replace("{total}", &value("total"))
+        .replace("{chapter}", &value("chapter"));
+
+    Ok(html_escape::decode_html_entities(&TAG_REGEX.replace_all(&text, "")).trim().to_string())
+}
+
+/// One non-empty header or footer slot positioned on a page, in points
+/// from the bottom-left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedText<'a> {
+    pub text: &'a str,
+    pub size: f32,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Position a page's slots on a `width` × `height` point page, centred in the
+/// top and bottom margins. `measure` gives the width of a string at a size.
+pub fn place<'a>(
+    text: &'a PageText,
+    config: &Config,
+    (width, height): (f32, f32),
+    measure: impl Fn(&str, f32) -> f32,
+) -> Vec<PlacedText<'a>> {
+    let margins = &config.page.margins;
+    let [top, right, bottom, left] = [&margins.top, &margins.right, &margins.bottom, &margins.left]
+        .map(|margin| points(margin).unwrap_or(72.0));
+    let header_size = font_size(config.page.header.as_ref());
+    let footer_size = font_size(config.page.footer.as_ref());
+
+    let rows = [
+        (&text.header, header_size, height - top / 2.0 - header_size / 3.0),
+        (&text.footer, footer_size, bottom / 2.0 - footer_size / 3.0),
+    ];
+    let mut placed = Vec::new();
+    for (slots, size, y) in rows {
+        for (slot, content) in slots.iter().enumerate() {
+            if content.is_empty() {
+                continue;
+            }
+            let text_width = measure(content, size);
+            let x = match slot {
+                0 => left,
+                1 => (left + width - right - text_width) / 2.0,
+                _ => width - right - text_width,
+            };
+            placed.push(PlacedText { text: content, size, x, y });
+        }
+    }
+    placed
+}
+
+/// Draw the header and footer text onto each page of a finished PDF, in
+/// Helvetica at `font_size` points, centred in the top and bottom margins.
+/// Characters outside WinAnsiEncoding are lost, so the native engine draws
+/// its own headers with the document fonts instead.
+pub fn stamp(doc: &mut Document, pages: &[PageText], config: &Config) -> Result<()> {
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+
+    for (number, page_id) in doc.get_pages() {
+        let Some(text) = pages.get(number as usize - 1).filter(|text| **text != PageText::default()) else {
+            continue;
+        };
+
+        let mut operations = Vec::new();
+        for placed in place(text, config, media_box(doc, page_id), text_width) {
+            operations.extend([
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec![Object::Name(FONT_NAME.to_vec()), placed.size.into()]),
+                Operation::new("g", vec![TEXT_GRAY.into()]),
+                Operation::new("Td", vec![placed.x.into(), placed.y.into()]),
+                Operation::new("Tj", vec![Object::String(win_ansi(placed.text), StringFormat::Literal)]),
+                Operation::new("ET", vec![]),
+            ]);
+        }
+
+        add_font_resource(doc, page_id, font_id)?;
+        // Chrome leaves its page transform in place, so isolate the page's own content first
+        let mut content = b"Q\n".to_vec();
+        content.extend(Content { operations }.encode().context("Failed to encode header/footer text")?);
+        wrap_page_content(doc, page_id, content)?;
+    }
+
+    Ok(())
+}
+
+fn font_size(configured: Option<&HeaderFooterConfig>) -> f32 {
+    configured
+        .filter(|configured| configured.enabled)
+        .and_then(|configured| configured.font_size.as_deref())
+        .and_then(points)
+        .unwrap_or(8.0)
+}
+
+/// CSS length (`in`, `mm`, `cm`, `pt`, `px`) → PDF points
+pub fn points(length: &str) -> Option<f32> {
+    let length = length.trim();
+    let (number, scale) = [("in", 72.0), ("mm", 72.0 / 25.4), ("cm", 72.0 / 2.54), ("pt", 1.0), ("px", 0.75)]
+        .into_iter()
+        .find_map(|(unit, scale)| length.strip_suffix(unit).map(|number| (number, scale)))?;
+    number.trim().parse::<f32>().ok().map(|number| number * scale)
+}
+
+fn text_width(text: &str, size: f32) -> f32 {
+    let units: u32 = text.chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => HELVETICA_WIDTHS[code as usize - 32] as u32,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// Encode for WinAnsiEncoding; characters outside it become `?`
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Look up a page attribute, following the page tree for inherited ones
+fn inherited<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = doc.get_dictionary(page_id).ok();
+    while let Some(dict) = node {
+        if let Ok(value) = dict.get(key) {
+            return doc.dereference(value).ok().map(|(_, value)| value);
+        }
+        node = dict.get(b"Parent").and_then(Object::as_reference).ok()
+            .and_then(|parent| doc.get_dictionary(parent).ok());
+    }
+    None
+}
+
+fn media_box(doc: &Document, page_id: ObjectId) -> (f32, f32) {
+    inherited(doc, page_id, b"MediaBox")
+        .and_then(|media_box| media_box.as_array().ok())
+        .and_then(|corners| {
+            let corners: Vec<f32> = corners.iter().filter_map(|corner| corner.as_float().ok()).collect();
+            (corners.len() == 4).then(|| (corners[2] - corners[0], corners[3] - corners[1]))
+        })
+        .unwrap_or((595.0, 842.0)) // A4
+}
+
+/// Give the page its own resources with our font added
+fn add_font_resource(doc: &mut Document, page_id: ObjectId, font_id: ObjectId) -> Result<()> {
+    let mut resources = match inherited(doc, page_id, b"Resources") {
+        Some(Object::Dictionary(resources)) => resources.clone(),
+        _ => Dictionary::new(),
+    };
+    let mut fonts = match resources.get(b"Font").ok().map(|fonts| doc.dereference(fonts)) {
+        Some(Ok((_, Object::Dictionary(fonts)))) => fonts.clone(),
+        _ => Dictionary::new(),
+    };
+    fonts.set(FONT_NAME.to_vec(), Object::Reference(font_id));
+    resources.set("Font", Object::Dictionary(fonts));
+
+    doc.get_dictionary_mut(page_id)
+        .context("PDF page is not a dictionary")?
+        .set("Resources", Object::Dictionary(resources));
+    Ok(())
+}
+
+/// Surround the page's content streams with `q` … `content`
+fn wrap_page_content(doc: &mut Document, page_id: ObjectId, content: Vec<u8>) -> Result<()> {
+    let save_id = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+    let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+
+    let page = doc.get_dictionary_mut(page_id).context("PDF page is not a dictionary")?;
+    let mut streams = match page.get(b"Contents") {
+        Ok(Object::Reference(id)) => vec![Object::Reference(*id)],
+        Ok(Object::Array(streams)) => streams.clone(),
+        _ => Vec::new(),
+    };
+    streams.insert(0, Object::Reference(save_id));
+    streams.push(Object::Reference(content_id));
+    page.set("Contents", Object::Array(streams));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HeaderFooterSlots, PageNumberConfig};
+
+    #[test]
+    fn test_first_page_odd_even_and_running_header() {
+        let mut config = Config::default();
+        config.cover.enabled = true;
+        config.page.header = Some(HeaderFooterConfig {
+            enabled: true,
+            even: Some(HeaderFooterSlots { left: "{page}".to_string(), right: "{{ title }}".to_string(), ..Default::default() }),
+            odd: Some(HeaderFooterSlots { left: "{chapter}".to_string(), right: "{page}".to_string(), ..Default::default() }),
+            skip_chapter_openings: true,
+            ..Default::default()
+        });
+        config.page.page_numbers = Some(PageNumberConfig {
+            enabled: true,
+            format: "Page {page} of {total}".to_string(),
+            position: PageNumberPosition::Footer,
+            start_from: None,
+        });
+
+        let mut context = tera::Context::new();
+        context.insert("title", "Field Guide");
+        let chapters = vec![(2, "Birds".to_string()), (4, "Trees & Shrubs".to_string())];
+        let pages = page_texts(&config, &context, &chapters, 5).unwrap();
+
+        // Cover and chapter openings stay bare
+        assert_eq!(pages[0], PageText::default());
+        assert_eq!(pages[1].header, ["", "", ""].map(String::from));
+        assert_eq!(pages[1].footer[1], "Page 2 of 5");
+        assert_eq!(pages[2].header, ["Birds", "", "3"].map(String::from));
+        assert_eq!(pages[3].header, ["", "", ""].map(String::from));
+        assert_eq!(pages[4].header, ["Trees & Shrubs", "", "5"].map(String::from));
+        assert!(varies_by_page(&config));
+
+        // Literal text mentioning a chapter is not a placeholder
+        let plain = HeaderFooterConfig { enabled: true, template: "Chapter notes".to_string(), ..Default::default() };
+        assert!(!plain.varies_by_page());
+        for template in ["{{chapter}}", "{{- chapter | upper }}", "Ch. {chapter}"] {
+            let running = HeaderFooterConfig { template: template.to_string(), ..plain.clone() };
+            assert!(running.varies_by_page(), "{template}");
+        }
+    }
+
+    #[test]
+    fn test_stamp_draws_on_every_page() {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (0..2).map(|_| {
+            let content_id = doc.add_object(Stream::new(Dictionary::new(), b"1 0 0 -1 0 842 cm".to_vec()));
+            doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+            }).into()
+        }).collect();
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => 2,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+
+        let text = |footer: &str| PageText { footer: ["".into(), footer.into(), "".into()], ..Default::default() };
+        stamp(&mut doc, &[text("1 – Intro"), text("2")], &Config::default()).unwrap();
+
+        for (number, page_id) in doc.get_pages() {
+            let content = String::from_utf8_lossy(&doc.get_page_content(page_id).unwrap()).into_owned();
+            assert!(content.starts_with("q\n1 0 0 -1 0 842 cm"));
+            assert!(content.contains("/PcHF 8 Tf"));
+            assert!(doc.get_page_fonts(page_id).contains_key(FONT_NAME));
+            if number == 2 {
+                assert!(content.contains("(2) Tj"));
+            }
+        }
+    }
+}
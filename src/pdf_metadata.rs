@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use lopdf::{Dictionary, Document, Object, Stream};
+use crate::config::DocumentMetadata;
+use crate::pdf_outline::pdf_text_string;
+
+pub const DEFAULT_CREATOR: &str = concat!("PaperCraft ", env!("CARGO_PKG_VERSION"));
+
+/// Write title, author, subject, keywords and creator into the PDF Info
+/// dictionary and an XMP metadata stream, replacing what Chrome emitted
+pub fn apply_metadata(doc: &mut Document, metadata: &DocumentMetadata) -> Result<()> {
+    let creator = metadata.creator.as_deref().unwrap_or(DEFAULT_CREATOR);
+    let keywords = metadata.keywords.join(", ");
+    let author = metadata.author();
+
+    // Keep Chrome's Producer and dates, override the descriptive fields
+    let mut info = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(info_id) => doc.get_dictionary(info_id).cloned().unwrap_or_default(),
+        Err(_) => Dictionary::new(),
+    };
+
+    let fields = [
+        ("Title", metadata.title.as_deref()),
+        ("Author", author.as_deref()),
+        ("Subject", metadata.subject.as_deref()),
+        ("Keywords", Some(keywords.as_str()).filter(|k| !k.is_empty())),
+        ("Creator", Some(creator)),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            info.set(key, pdf_text_string(value));
+        }
+    }
+
+    let producer = info.get(b"Producer").ok()
+        .and_then(|p| p.as_str().ok())
+        .map(|p| String::from_utf8_lossy(p).into_owned());
+
+    let info_id = doc.add_object(info);
+    doc.trailer.set("Info", info_id);
+
+    let mut xmp = Stream::new(Dictionary::new(), xmp_packet(metadata, creator, producer.as_deref()).into_bytes());
+    xmp.dict.set("Type", Object::Name(b"Metadata".to_vec()));
+    xmp.dict.set("Subtype", Object::Name(b"XML".to_vec()));
+    // Metadata streams are left uncompressed so indexers can read them directly
+    xmp.allows_compression = false;
+    let xmp_id = doc.add_object(xmp);
+    doc.catalog_mut().context("PDF has no document catalog")?.set("Metadata", xmp_id);
+    Ok(())
+}
+
+fn xmp_packet(metadata: &DocumentMetadata, creator: &str, producer: Option<&str>) -> String {
+    let mut properties = String::new();
+
+    if let Some(title) = &metadata.title {
+        properties.push_str(&format!(
+            r#"<dc:title><rdf:Alt><rdf:li xml:lang="x-default">{}</rdf:li></rdf:Alt></dc:title>"#,
+            xml_escape(title)
+        ));
+    }
+    if !metadata.authors.is_empty() {
+        let authors: String = metadata.authors.iter()
+            .map(|name| format!("<rdf:li>{}</rdf:li>", xml_escape(name)))
+            .collect();
+        properties.push_str(&format!("<dc:creator><rdf:Seq>{authors}</rdf:Seq></dc:creator>"));
+    }
+    if let Some(subject) = &metadata.subject {
+        properties.push_str(&format!(
+            r#"<dc:description><rdf:Alt><rdf:li xml:lang="x-default">{}</rdf:li></rdf:Alt></dc:description>"#,
+            xml_escape(subject)
+        ));
+    }
+    if !metadata.keywords.is_empty() {
+        let subjects: String = metadata.keywords.iter()
+            .map(|keyword| format!("<rdf:li>{}</rdf:li>", xml_escape(keyword)))
+            .collect();
+        properties.push_str(&format!("<dc:subject><rdf:Bag>{subjects}</rdf:Bag></dc:subject>"));
+        properties.push_str(&format!("<pdf:Keywords>{}</pdf:Keywords>", xml_escape(&metadata.keywords.join(", "))));
+    }
+    if let Some(date) = &metadata.date {
+        properties.push_str(&format!("<dc:date><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:date>", xml_escape(date)));
+    }
+    if let Some(producer) = producer {
+        properties.push_str(&format!("<pdf:Producer>{}</pdf:Producer>", xml_escape(producer)));
+    }
+    properties.push_str(&format!("<xmp:CreatorTool>{}</xmp:CreatorTool>", xml_escape(creator)));
+
+    format!(concat!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+        "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" ",
+        "xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">",
+        "<dc:format>application/pdf</dc:format>{}",
+        "</rdf:Description></rdf:RDF></x:xmpmeta>\n",
+        "<?xpacket end=\"w\"?>"
+    ), properties)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_metadata_written_to_info_and_xmp() {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.add_object(dictionary! { "Type" => "Pages", "Kids" => vec![], "Count" => 0 });
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = doc.add_object(dictionary! { "Producer" => Object::string_literal("Skia/PDF"), "Title" => Object::string_literal("Document") });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+
+        let metadata = DocumentMetadata {
+            title: Some("Résumé".to_string()),
+            authors: vec!["Lovelace, Ada <Countess>".to_string(), "Hopper, Grace".to_string()],
+            keywords: vec!["rust".to_string(), "pdf".to_string()],
+            ..Default::default()
+        };
+        apply_metadata(&mut doc, &metadata).unwrap();
+
+        let info = doc.get_dictionary(doc.trailer.get(b"Info").unwrap().as_reference().unwrap()).unwrap();
+        assert_eq!(info.get(b"Title").unwrap().as_str().unwrap()[..2], [0xFE, 0xFF]);
+        assert_eq!(info.get(b"Keywords").unwrap().as_str().unwrap(), b"rust, pdf");
+        assert_eq!(info.get(b"Creator").unwrap().as_str().unwrap(), DEFAULT_CREATOR.as_bytes());
+        assert_eq!(info.get(b"Producer").unwrap().as_str().unwrap(), b"Skia/PDF");
+
+        let xmp_id = doc.catalog().unwrap().get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = String::from_utf8(doc.get_object(xmp_id).unwrap().as_stream().unwrap().content.clone()).unwrap();
+        assert!(xmp.contains("<rdf:Seq><rdf:li>Lovelace, Ada &lt;Countess&gt;</rdf:li><rdf:li>Hopper, Grace</rdf:li></rdf:Seq>"));
+        assert!(xmp.contains("<pdf:Producer>Skia/PDF</pdf:Producer>"));
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{Context, Result};
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+use std::collections::HashMap;
+use crate::advanced_processing::TocEntry;
+
+/// Where a named destination (an element id) landed in the PDF
+#[derive(Debug, Clone)]
+pub struct Destination {
+    pub page: u32,  // 1-based page number
+    target: Object, // Explicit `[page /XYZ left top zoom]` array
+}
+
+/// Hidden links to every heading. Chrome only writes named destinations for
+/// elements that are the target of an internal link, so this guarantees the
+/// outline can locate headings even when the TOC is disabled.
+pub fn link_targets_html(entries: &[TocEntry]) -> String {
+    let links: String = entries.iter()
+        .map(|entry| format!(r##"<a href="#{}"></a>"##, html_escape::encode_double_quoted_attribute(&entry.id)))
+        .collect();
+
+    format!(r#"<nav class="pdf-outline-targets" aria-hidden="true" style="display: none">{links}</nav>"#)
+}
+
+/// Resolve the document's named destinations, keyed by element id
+pub fn destinations(doc: &Document) -> Result<HashMap<String, Destination>> {
+    let page_numbers: HashMap<ObjectId, u32> = doc.get_pages()
+        .into_iter()
+        .map(|(number, id)| (id, number))
+        .collect();
+
+    let mut destinations = Vec::new();
+    let catalog = doc.catalog().context("PDF has no document catalog")?;
+
+    // PDF 1.1 style: /Dests dictionary in the catalog
+    if let Ok(dests) = catalog.get(b"Dests") {
+        if let (_, Object::Dictionary(dests)) = doc.dereference(dests)? {
+            for (name, dest) in dests.iter() {
+                destinations.push((String::from_utf8_lossy(name).into_owned(), dest));
+            }
+        }
+    }
+
+    // PDF 1.2+ style: /Names → /Dests name tree
+    if let Ok(names) = catalog.get(b"Names") {
+        if let (_, Object::Dictionary(names)) = doc.dereference(names)? {
+            if let Ok(tree) = names.get(b"Dests") {
+                collect_name_tree(doc, tree, &mut destinations)?;
+            }
+        }
+    }
+
+    let mut resolved = HashMap::new();
+    for (name, dest) in destinations {
+        if let Some(target) = explicit_destination(doc, dest) {
+            let page = target.as_array().ok()
+                .and_then(|parts| parts.first()?.as_reference().ok())
+                .and_then(|page_id| page_numbers.get(&page_id).copied());
+            if let Some(page) = page {
+                resolved.insert(name, Destination { page, target });
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Page each linked element landed on, keyed by element id
+pub fn page_numbers(doc: &Document) -> Result<HashMap<String, u32>> {
+    Ok(destinations(doc)?
+        .into_iter()
+        .map(|(name, dest)| (name, dest.page))
+        .collect())
+}
+
+fn collect_name_tree<'a>(doc: &'a Document, node: &'a Object, out: &mut Vec<(String, &'a Object)>) -> Result<()> {
+    let (_, Object::Dictionary(node)) = doc.dereference(node)? else {
+        return Ok(());
+    };
+
+    if let Ok(Object::Array(names)) = node.get(b"Names") {
+        for pair in names.chunks(2) {
+            if let [Object::String(name, _), dest] = pair {
+                out.push((String::from_utf8_lossy(name).into_owned(), dest));
+            }
+        }
+    }
+
+    if let Ok(Object::Array(kids)) = node.get(b"Kids") {
+        for kid in kids {
+            collect_name_tree(doc, kid, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A destination is `[page /XYZ left top zoom]` or a dictionary with a `/D` entry
+fn explicit_destination(doc: &Document, dest: &Object) -> Option<Object> {
+    match doc.dereference(dest).ok()?.1 {
+        array @ Object::Array(_) => Some(array.clone()),
+        Object::Dictionary(dict) => explicit_destination(doc, dict.get(b"D").ok()?),
+        _ => None,
+    }
+}
+
+/// Write named destinations into the catalog's `/Dests`, for PDFs whose
+/// generator does not emit them. Each target is an id, its 1-based page and
+/// the distance from the bottom of that page in points.
+pub fn add_destinations(doc: &mut Document, targets: &[(String, u32, f32)]) -> Result<()> {
+    let pages = doc.get_pages();
+
+    let mut dests = Dictionary::new();
+    for (name, page, top) in targets {
+        if let Some(&page_id) = pages.get(page) {
+            dests.set(name.as_bytes().to_vec(), Object::Array(vec![
+                Object::Reference(page_id),
+                Object::Name(b"XYZ".to_vec()),
+                Object::Integer(0),
+                Object::Real(*top),
+                Object::Null,
+            ]));
+        }
+    }
+
+    let dests_id = doc.add_object(Object::Dictionary(dests));
+    doc.catalog_mut().context("PDF has no document catalog")?.set("Dests", Object::Reference(dests_id));
+    Ok(())
+}
+
+/// Add a nested bookmarks outline for `entries` to a PDF produced by Chrome
+pub fn add_outline(doc: &mut Document, entries: &[TocEntry]) -> Result<()> {
+    let destinations = destinations(doc)?;
+
+    // Build the heading tree; each node lists its children by index
+    let mut nodes: Vec<OutlineNode> = Vec::new();
+    let mut top_level = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+
+    for entry in entries {
+        while stack.last().is_some_and(|&parent| nodes[parent].level >= entry.level) {
+            stack.pop();
+        }
+
+        let index = nodes.len();
+        nodes.push(OutlineNode {
+            level: entry.level,
+            title: format!("{}{}", entry.section_number, entry.text),
+            target: destinations.get(&entry.id).map(|dest| dest.target.clone()),
+            children: Vec::new(),
+        });
+
+        match stack.last() {
+            Some(&parent) => nodes[parent].children.push(index),
+            None => top_level.push(index),
+        }
+        stack.push(index);
+    }
+
+    if top_level.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<ObjectId> = nodes.iter().map(|_| doc.new_object_id()).collect();
+    let outlines_id = doc.new_object_id();
+
+    write_siblings(doc, &nodes, &ids, &top_level, outlines_id);
+
+    let mut outlines = Dictionary::new();
+    outlines.set("Type", Object::Name(b"Outlines".to_vec()));
+    outlines.set("First", Object::Reference(ids[top_level[0]]));
+    outlines.set("Last", Object::Reference(ids[*top_level.last().unwrap()]));
+    outlines.set("Count", Object::Integer(nodes.len() as i64));
+    doc.objects.insert(outlines_id, Object::Dictionary(outlines));
+
+    let catalog = doc.catalog_mut().context("PDF has no document catalog")?;
+    catalog.set("Outlines", Object::Reference(outlines_id));
+    catalog.set("PageMode", Object::Name(b"UseOutlines".to_vec()));
+    Ok(())
+}
+
+struct OutlineNode {
+    level: usize,
+    title: String,
+    target: Option<Object>,
+    children: Vec<usize>,
+}
+
+fn write_siblings(doc: &mut Document, nodes: &[OutlineNode], ids: &[ObjectId], siblings: &[usize], parent: ObjectId) {
+    for (position, &index) in siblings.iter().enumerate() {
+        let node = &nodes[index];
+        let mut item = Dictionary::new();
+        item.set("Title", pdf_text_string(&node.title));
+        item.set("Parent", Object::Reference(parent));
+
+        if position > 0 {
+            item.set("Prev", Object::Reference(ids[siblings[position - 1]]));
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            item.set("Next", Object::Reference(ids[next]));
+        }
+
+        if let (Some(&first), Some(&last)) = (node.children.first(), node.children.last()) {
+            item.set("First", Object::Reference(ids[first]));
+            item.set("Last", Object::Reference(ids[last]));
+            item.set("Count", Object::Integer(descendant_count(nodes, index) as i64));
+            write_siblings(doc, nodes, ids, &node.children, ids[index]);
+        }
+
+        // Headings Chrome did not emit a destination for still keep their place in the tree
+        if let Some(target) = &node.target {
+            item.set("Dest", target.clone());
+        }
+
+        doc.objects.insert(ids[index], Object::Dictionary(item));
+    }
+}
+
+fn descendant_count(nodes: &[OutlineNode], index: usize) -> usize {
+    nodes[index].children.iter()
+        .map(|&child| 1 + descendant_count(nodes, child))
+        .sum()
+}
+
+/// PDF text strings are PDFDocEncoding or UTF-16BE with a byte order mark
+pub fn pdf_text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::String(text.as_bytes().to_vec(), StringFormat::Literal)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    /// Two-page PDF whose `/Dests` point `intro` at page 1 and `usage` at page 2
+    fn sample_pdf() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let page_ids: Vec<ObjectId> = (0..2).map(|_| doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        })).collect();
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids.iter().map(|&id| Object::Reference(id)).collect::<Vec<_>>(),
+            "Count" => 2,
+        }));
+        let dests = doc.add_object(dictionary! {
+            "intro" => vec![page_ids[0].into(), "XYZ".into(), 0.into(), 800.into(), Object::Null],
+            "usage" => vec![page_ids[1].into(), "XYZ".into(), 0.into(), 800.into(), Object::Null],
+        });
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Dests" => dests,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn entry(level: usize, text: &str, id: &str) -> TocEntry {
+        TocEntry { level, text: text.to_string(), id: id.to_string(), section_number: String::new() }
+    }
+
+    #[test]
+    fn test_outline_nests_headings_and_resolves_pages() {
+        let entries = vec![entry(1, "Intro", "intro"), entry(2, "Usage", "usage"), entry(1, "Café", "missing")];
+
+        let mut doc = sample_pdf();
+        add_outline(&mut doc, &entries).unwrap();
+
+        let outlines_ref = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let outlines = doc.get_dictionary(outlines_ref).unwrap();
+        assert_eq!(outlines.get(b"Count").unwrap().as_i64().unwrap(), 3);
+
+        let intro = doc.get_dictionary(outlines.get(b"First").unwrap().as_reference().unwrap()).unwrap();
+        assert_eq!(intro.get(b"Count").unwrap().as_i64().unwrap(), 1);
+        let usage = doc.get_dictionary(intro.get(b"First").unwrap().as_reference().unwrap()).unwrap();
+        let usage_page = usage.get(b"Dest").unwrap().as_array().unwrap()[0].as_reference().unwrap();
+        assert_eq!(doc.get_pages()[&2], usage_page);
+
+        let cafe = doc.get_dictionary(intro.get(b"Next").unwrap().as_reference().unwrap()).unwrap();
+        assert!(cafe.get(b"Dest").is_err());
+        assert_eq!(page_numbers(&doc).unwrap()["usage"], 2);
+    }
+}
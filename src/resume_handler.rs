@@ -68,15 +68,16 @@ impl ResumeHandler {
         Ok(Self { state_dir })
     }
     
-    pub fn create_batch_job(&self, input_paths: Vec<PathBuf>, output_dir: &Path) -> Result<BatchJob> {
+    /// Record a batch whose outputs are written to `output_dir` with `extension`
+    pub fn create_batch_job(&self, input_paths: Vec<PathBuf>, output_dir: &Path, extension: &str) -> Result<BatchJob> {
         let job_id = self.generate_job_id();
         let now = Utc::now();
         
         let jobs: Vec<_> = input_paths.into_iter().enumerate().map(|(i, input_path)| {
             let output_path = if input_path.is_file() {
-                output_dir.join(input_path.file_stem().unwrap()).with_extension("pdf")
+                output_dir.join(input_path.file_stem().unwrap()).with_extension(extension)
             } else {
-                output_dir.join(format!("output_{}.pdf", i))
+                output_dir.join(format!("output_{}.{}", i, extension))
             };
             
             ConversionJob {
@@ -147,10 +148,8 @@ impl ResumeHandler {
                 
                 // Update batch counters
                 match status {
-                    JobStatus::Completed => {
-                        if job.progress >= 100.0 {
-                            batch_job.completed_files += 1;
-                        }
+                    JobStatus::Completed if job.progress >= 100.0 => {
+                        batch_job.completed_files += 1;
                     }
                     JobStatus::Failed => {
                         batch_job.failed_files += 1;
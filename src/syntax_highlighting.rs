@@ -0,0 +1,53 @@
+use lazy_static::lazy_static;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+lazy_static! {
+    // Load syntax and theme sets once, shared by the HTML and DOCX paths
+    pub static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    pub static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+/// Syntax for a code fence language, by extension, name or common alias
+pub fn find_syntax(lang: &str) -> &'static SyntaxReference {
+    SYNTAX_SET.find_syntax_by_extension(lang)
+        .or_else(|| SYNTAX_SET.find_syntax_by_name(lang))
+        .or_else(|| match lang {
+            "rs" | "rust" => SYNTAX_SET.find_syntax_by_name("Rust"),
+            "py" | "python" => SYNTAX_SET.find_syntax_by_name("Python"),
+            "js" | "javascript" => SYNTAX_SET.find_syntax_by_name("JavaScript"),
+            "ts" | "typescript" => SYNTAX_SET.find_syntax_by_name("TypeScript"),
+            "c" => SYNTAX_SET.find_syntax_by_name("C"),
+            "cpp" | "c++" => SYNTAX_SET.find_syntax_by_name("C++"),
+            "java" => SYNTAX_SET.find_syntax_by_name("Java"),
+            "go" => SYNTAX_SET.find_syntax_by_name("Go"),
+            "bash" | "sh" | "shell" => {
+                // Try common bash/shell syntax names
+                SYNTAX_SET.find_syntax_by_extension("sh")
+                    .or_else(|| SYNTAX_SET.find_syntax_by_name("Shell-Unix-Generic"))
+                    .or_else(|| SYNTAX_SET.find_syntax_by_name("Bourne Again Shell (bash)"))
+                    .or_else(|| SYNTAX_SET.find_syntax_by_name("Shell Script (Bash)"))
+                    .or_else(|| SYNTAX_SET.find_syntax_by_name("Bash"))
+            },
+            "console" | "terminal" => SYNTAX_SET.find_syntax_by_extension("sh"),
+            "cmd" | "bat" | "batch" => SYNTAX_SET.find_syntax_by_name("Batch File"),
+            "ps1" | "powershell" => SYNTAX_SET.find_syntax_by_name("PowerShell"),
+            "zsh" => SYNTAX_SET.find_syntax_by_extension("sh"),
+            _ => None
+        })
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text())
+}
+
+/// Syntect theme named by `code.highlight_theme`, ignoring case; "github"
+/// maps to InspiredGitHub and unknown names use `fallback`
+pub fn find_theme(name: &str, fallback: &str) -> &'static Theme {
+    let name = match name.to_lowercase().as_str() {
+        "github" => "InspiredGitHub".to_string(),
+        _ => name.to_string(),
+    };
+
+    THEME_SET.themes.iter()
+        .find(|(theme_name, _)| theme_name.eq_ignore_ascii_case(&name))
+        .map(|(_, theme)| theme)
+        .unwrap_or_else(|| &THEME_SET.themes[fallback])
+}
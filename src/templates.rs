@@ -0,0 +1,200 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::path::Path;
+use tera::Tera;
+use crate::config::{Config, CoverConfig, DocumentMetadata};
+use crate::error_handler::PapercraftError;
+
+// Built-in templates, replaced one by one by the files in `[templates]`
+const SHELL_TEMPLATE: &str = include_str!("themes/templates/document.html");
+const HEADER_TEMPLATE: &str = include_str!("themes/templates/header.html");
+const FOOTER_TEMPLATE: &str = include_str!("themes/templates/footer.html");
+const COVER_LAYOUTS: [(&str, &str); 3] = [
+    ("classic", include_str!("themes/templates/cover/classic.html")),
+    ("modern", include_str!("themes/templates/cover/modern.html")),
+    ("academic", include_str!("themes/templates/cover/academic.html")),
+];
+// Signals Chrome that rendering finished, so it is not user-overridable
+const SCRIPTS_TEMPLATE: &str = include_str!("themes/templates/scripts.html");
+
+pub const SHELL: &str = "document.html";
+pub const TITLE_PAGE: &str = "title_page.html";
+pub const HEADER: &str = "header.html";
+pub const FOOTER: &str = "footer.html";
+pub const SCRIPTS: &str = "scripts.html";
+
+/// Tera templates for the HTML document shell, the optional title page and
+/// the PDF header and footer. An enabled cover page uses a built-in layout
+/// as the title page unless `templates.title_page` is set.
+pub struct PageTemplates {
+    tera: Tera,
+}
+
+impl PageTemplates {
+    pub fn load(config: &Config) -> Result<Self> {
+        let mut tera = Tera::default();
+        // Names end in .html, so Tera escapes every value unless marked `safe`
+        tera.add_raw_templates(vec![
+            (SHELL, SHELL_TEMPLATE),
+            (HEADER, HEADER_TEMPLATE),
+            (FOOTER, FOOTER_TEMPLATE),
+            (SCRIPTS, SCRIPTS_TEMPLATE),
+        ]).map_err(|e| PapercraftError::TemplateError { message: format!("Invalid built-in template: {}", error_chain(&e)) })?;
+
+        if config.cover.enabled {
+            tera.add_raw_template(TITLE_PAGE, cover_layout(config)?)
+                .map_err(|e| PapercraftError::TemplateError { message: format!("Invalid built-in template: {}", error_chain(&e)) })?;
+        }
+
+        let config = &config.templates;
+        for (name, path) in [
+            (SHELL, &config.shell),
+            (TITLE_PAGE, &config.title_page),
+            (HEADER, &config.header),
+            (FOOTER, &config.footer),
+        ] {
+            if let Some(path) = path {
+                tera.add_template_file(path, Some(name))
+                    .map_err(|e| PapercraftError::TemplateError {
+                        message: format!("Failed to load template {}: {}", path.display(), error_chain(&e)),
+                    })?;
+            }
+        }
+
+        Ok(Self { tera })
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.tera.get_template_names().any(|template| template == name)
+    }
+
+    pub fn render(&self, name: &str, context: &tera::Context) -> Result<String> {
+        self.tera.render(name, context)
+            .map_err(|e| PapercraftError::TemplateError { message: format!("Failed to render {name}: {}", error_chain(&e)) }.into())
+    }
+
+    /// Render a template string from the config, e.g. `page.header.template`
+    pub fn render_str(template: &str, context: &tera::Context) -> Result<String> {
+        Tera::one_off(template, context, true)
+            .map_err(|e| PapercraftError::TemplateError { message: format!("Failed to render template '{template}': {}", error_chain(&e)) }.into())
+    }
+}
+
+/// Variables shared by every template: metadata, the first top-level
+/// heading as `chapter`, the input file name and all front matter fields
+pub fn document_context(
+    metadata: &DocumentMetadata,
+    chapter: Option<&str>,
+    source: Option<&Path>,
+    front_matter: &Map<String, Value>,
+) -> tera::Context {
+    let mut context = tera::Context::new();
+    context.insert("title", metadata.title.as_deref().unwrap_or_default());
+    context.insert("author", &metadata.author().unwrap_or_default());
+    context.insert("date", metadata.date.as_deref().unwrap_or_default());
+    context.insert("subject", metadata.subject.as_deref().unwrap_or_default());
+    context.insert("keywords", &metadata.keywords);
+    context.insert("chapter", chapter.unwrap_or_default());
+    context.insert("file_name", &source
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default());
+    context.insert("front_matter", front_matter);
+    context
+}
+
+/// Cover fields for the title page; the date defaults to today
+pub fn insert_cover(context: &mut tera::Context, cover: &CoverConfig, metadata: &DocumentMetadata) {
+    let date = metadata.date.clone()
+        .unwrap_or_else(|| chrono::Local::now().format("%B %-d, %Y").to_string());
+
+    context.insert("authors", &metadata.authors);
+    context.insert("subtitle", cover.subtitle.as_deref().unwrap_or_default());
+    context.insert("organization", cover.organization.as_deref().unwrap_or_default());
+    context.insert("logo", &cover.logo.as_ref()
+        .map(|logo| logo.to_string_lossy().into_owned())
+        .unwrap_or_default());
+    context.insert("version", cover.version.as_deref().unwrap_or_default());
+    context.insert("confidentiality", cover.confidentiality.as_deref().unwrap_or_default());
+    context.insert("cover_date", &date);
+}
+
+/// `cover.layout`, or the layout matching the theme
+fn cover_layout(config: &Config) -> Result<&'static str> {
+    let layout = match (&config.cover.layout, config.theme.built_in.as_deref()) {
+        (Some(layout), _) => layout.to_lowercase(),
+        (None, Some("modern" | "dark")) => "modern".to_string(),
+        (None, Some("academic")) => "academic".to_string(),
+        (None, _) => "classic".to_string(),
+    };
+
+    COVER_LAYOUTS.iter()
+        .find(|(name, _)| *name == layout)
+        .map(|(_, template)| *template)
+        .ok_or_else(|| anyhow::anyhow!("Unknown cover layout '{layout}'. Use classic, modern or academic"))
+}
+
+/// Tera nests the useful message (e.g. the template line) in its sources
+pub fn error_chain(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(inner) = source {
+        message.push_str(&format!(": {inner}"));
+        source = inner.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_templates_override_built_ins() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = dir.path().join("header.html");
+        std::fs::write(&header, "<div>{{ title }} | {{ front_matter.client }} | {{ content | safe }}</div>").unwrap();
+
+        let mut config = Config::default();
+        config.templates.header = Some(header);
+        let templates = PageTemplates::load(&config).unwrap();
+        assert!(!templates.has(TITLE_PAGE));
+
+        let metadata = DocumentMetadata { title: Some("Q3 <Report>".to_string()), ..Default::default() };
+        let front_matter = Map::from_iter([("client".to_string(), Value::from("Acme"))]);
+        let mut context = document_context(&metadata, None, Some(Path::new("docs/q3.md")), &front_matter);
+        context.insert("content", "<span class=\"pageNumber\"></span>");
+
+        assert_eq!(
+            templates.render(HEADER, &context).unwrap(),
+            "<div>Q3 &lt;Report&gt; | Acme | <span class=\"pageNumber\"></span></div>"
+        );
+        assert_eq!(PageTemplates::render_str("{{ file_name }}", &context).unwrap(), "q3.md");
+    }
+
+    #[test]
+    fn test_cover_layout_follows_theme() {
+        let mut config = Config::default();
+        config.cover.enabled = true;
+        config.cover.subtitle = Some("Fiscal 2025".to_string());
+        config.theme.built_in = Some("dark".to_string());
+
+        let metadata = DocumentMetadata {
+            title: Some("Annual Report".to_string()),
+            authors: vec!["Ada".to_string(), "Grace".to_string()],
+            date: Some("2025-01-31".to_string()),
+            ..Default::default()
+        };
+        let mut context = document_context(&metadata, None, None, &Map::new());
+        insert_cover(&mut context, &config.cover, &metadata);
+
+        let cover = PageTemplates::load(&config).unwrap().render(TITLE_PAGE, &context).unwrap();
+        assert!(cover.contains("cover-modern"));
+        assert!(cover.contains("Ada · Grace"));
+        assert!(cover.contains("<p class=\"cover-date\">2025-01-31</p>"));
+        assert!(!cover.contains("cover-confidentiality"));
+
+        config.cover.layout = Some("poster".to_string());
+        assert!(PageTemplates::load(&config).is_err());
+    }
+}
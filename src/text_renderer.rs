@@ -1,21 +1,35 @@
-use printpdf::{IndirectFontRef, Mm, PdfLayerReference, Color, Rgb, Line, Point};
-use crate::TextFormat;
+use printpdf::{Mm, Pt, PdfLayerReference, Color, Rgb, Line, Point, Polygon, PolygonMode, WindingOrder};
+use crate::document_model::{TextFormat, TextSpan};
+use crate::pdf_fonts::{FontChain, FontSystem};
 
+/// A styled word; `space_before` is false where a style changes mid-word
 #[derive(Debug, Clone)]
-struct TextSegment {
+pub struct Word {
     text: String,
     formats: Vec<TextFormat>,
+    space_before: bool,
 }
 
+/// One wrapped line; `ends_paragraph` keeps it out of justification
+#[derive(Debug, Clone, Default)]
+pub struct TextLine {
+    words: Vec<Word>,
+    ends_paragraph: bool,
+}
+
+/// How `render_lines` sets a block of wrapped text
 #[derive(Clone)]
-pub struct FontSystem {
-    pub regular: IndirectFontRef,
-    pub bold: IndirectFontRef,
-    pub italic: IndirectFontRef,
-    pub bold_italic: IndirectFontRef,
-    pub code: IndirectFontRef,
+pub struct LineStyle {
+    pub font_size: f32,
+    pub color: Color,
+    pub justify: bool,
 }
 
+// Inner spacing of code blocks and blockquotes, and the bullet column of list items
+const CODE_PADDING: Mm = Mm(4.0);
+const QUOTE_PADDING: Mm = Mm(3.0);
+const LIST_BULLET_WIDTH: Mm = Mm(7.0);
+
 pub struct TextRenderer {
     fonts: FontSystem,
     font_sizes: FontSizes,
@@ -39,12 +53,9 @@ pub struct ColorScheme {
     pub text: Color,
     pub heading: Color,
     pub code: Color,
-    pub code_bg: Color,
     pub blockquote: Color,
     pub blockquote_border: Color,
     pub table_border: Color,
-    pub table_header: Color,
-    pub link: Color,
 }
 
 impl Default for FontSizes {
@@ -64,31 +75,40 @@ impl Default for FontSizes {
     }
 }
 
+impl FontSizes {
+    /// The default scale, resized around a configured body size
+    pub fn for_body(body: f32) -> Self {
+        let scale = body / Self::default().body;
+        let sizes = Self::default();
+        Self {
+            h1: sizes.h1 * scale,
+            h2: sizes.h2 * scale,
+            h3: sizes.h3 * scale,
+            h4: sizes.h4 * scale,
+            h5: sizes.h5 * scale,
+            h6: sizes.h6 * scale,
+            body,
+            code: sizes.code * scale,
+            small: sizes.small * scale,
+            caption: sizes.caption * scale,
+        }
+    }
+}
+
 impl Default for ColorScheme {
     fn default() -> Self {
         Self {
             text: Color::Rgb(Rgb::new(0.15, 0.15, 0.15, None)),        // Darker text for better readability
             heading: Color::Rgb(Rgb::new(0.05, 0.15, 0.3, None)),      // Professional dark blue headings
             code: Color::Rgb(Rgb::new(0.65, 0.15, 0.35, None)),        // Refined purple-red for code
-            code_bg: Color::Rgb(Rgb::new(0.97, 0.97, 0.98, None)),     // Very light blue-gray background
             blockquote: Color::Rgb(Rgb::new(0.35, 0.35, 0.4, None)),   // Slightly darker gray
             blockquote_border: Color::Rgb(Rgb::new(0.3, 0.5, 0.8, None)), // Professional blue border
             table_border: Color::Rgb(Rgb::new(0.6, 0.6, 0.65, None)),  // Refined gray
-            table_header: Color::Rgb(Rgb::new(0.05, 0.05, 0.1, None)), // Very dark for contrast
-            link: Color::Rgb(Rgb::new(0.1, 0.35, 0.7, None)),          // Professional blue
         }
     }
 }
 
 impl TextRenderer {
-    pub fn new(fonts: FontSystem) -> Self {
-        Self {
-            fonts,
-            font_sizes: FontSizes::default(),
-            colors: ColorScheme::default(),
-        }
-    }
-
     pub fn with_custom_sizes(fonts: FontSystem, font_sizes: FontSizes) -> Self {
         Self {
             fonts,
@@ -97,19 +117,16 @@ impl TextRenderer {
         }
     }
 
-    pub fn calculate_text_width(&self, text: &str, font_size: f32) -> Mm {
-        // More accurate character width estimation based on font size
-        let avg_char_width = font_size * 0.52; // Improved Helvetica character width ratio
-        let width_pt = text.chars().map(|c| {
-            match c {
-                'i' | 'l' | 'j' | '!' | '|' | '.' | ',' | ':' | ';' => avg_char_width * 0.5,
-                'm' | 'w' | 'M' | 'W' => avg_char_width * 1.3,
-                't' | 'f' | 'r' => avg_char_width * 0.7,
-                ' ' => avg_char_width * 0.6,
-                _ => avg_char_width,
-            }
-        }).sum::<f32>();
-        Mm(width_pt * 25.4 / 72.0) // Convert points to mm
+    /// Draw `text` on the baseline at (`x`, `y`), switching faces where the
+    /// first font lacks a character, and return its width
+    pub fn draw_text(&self, layer: &PdfLayerReference, text: &str, font_size: f32, x: Mm, y: Mm, font: &FontChain) -> Mm {
+        let mut current_x = x;
+        for (face, run) in font.runs(text) {
+            let width = font.width(&run, font_size);
+            layer.use_text(run, font_size, current_x, y, face);
+            current_x += width;
+        }
+        current_x - x
     }
 
     pub fn calculate_line_height(&self, font_size: f32) -> Mm {
@@ -119,6 +136,12 @@ impl TextRenderer {
         Mm(line_height_pt * 25.4 / 72.0)
     }
 
+    /// Distance from the top of a line box to its baseline
+    pub fn baseline_offset(&self, font_size: f32) -> Mm {
+        let size = Mm::from(Pt(font_size));
+        (self.calculate_line_height(font_size) - size) / 2.0 + size * 0.8
+    }
+
     pub fn calculate_paragraph_spacing(&self, font_size: f32) -> Mm {
         // Enhanced paragraph spacing for better readability
         Mm((font_size * 1.0) * 25.4 / 72.0)
@@ -126,91 +149,57 @@ impl TextRenderer {
 
     pub fn calculate_heading_spacing_before(&self, level: u8) -> Mm {
         // More generous spacing before headings for visual hierarchy
-        match level {
-            1 => Mm(24.0),  // Large space before H1
-            2 => Mm(20.0),  // Good space before H2
-            3 => Mm(16.0),  // Medium space before H3
-            4 => Mm(14.0),  // Smaller space before H4
-            5 => Mm(12.0),  // Minimal space before H5
-            _ => Mm(10.0),  // Default for H6+
-        }
+        Mm::from(Pt(match level {
+            1 => 24.0,  // Large space before H1
+            2 => 20.0,  // Good space before H2
+            3 => 16.0,  // Medium space before H3
+            4 => 14.0,  // Smaller space before H4
+            5 => 12.0,  // Minimal space before H5
+            _ => 10.0,  // Default for H6+
+        }))
     }
 
     pub fn calculate_heading_spacing_after(&self, level: u8) -> Mm {
         // Balanced spacing after headings
-        match level {
-            1 => Mm(16.0),  // Good space after H1
-            2 => Mm(14.0),  // Good space after H2
-            3 => Mm(12.0),  // Medium space after H3
-            4 => Mm(10.0),  // Smaller space after H4
-            5 => Mm(8.0),   // Minimal space after H5
-            _ => Mm(6.0),   // Default for H6+
-        }
+        Mm::from(Pt(match level {
+            1 => 16.0,  // Good space after H1
+            2 => 14.0,  // Good space after H2
+            3 => 12.0,  // Medium space after H3
+            4 => 10.0,  // Smaller space after H4
+            5 => 8.0,   // Minimal space after H5
+            _ => 6.0,   // Default for H6+
+        }))
     }
 
-    pub fn wrap_text(&self, text: &str, max_width: Mm, font_size: f32) -> Vec<String> {
-        let avg_char_width = font_size * 0.55; // More accurate for Helvetica
-        let max_width_pt = max_width.0 * 72.0 / 25.4; // Convert mm to points
-        let max_chars_per_line = (max_width_pt / avg_char_width) as usize;
-        
-        if max_chars_per_line == 0 {
-            return vec![text.to_string()];
-        }
-
+    /// Break plain text into lines that fit `max_width`, splitting overlong words
+    pub fn wrap_text(&self, text: &str, max_width: Mm, font_size: f32, font: &FontChain) -> Vec<String> {
         let mut lines = Vec::new();
-        let words: Vec<&str> = text.split_whitespace().collect();
         let mut current_line = String::new();
-        
-        for word in words {
-            let test_line = if current_line.is_empty() {
-                word.to_string()
-            } else {
-                format!("{} {}", current_line, word)
-            };
 
-            // Check if the test line fits
-            if self.calculate_text_width(&test_line, font_size).0 <= max_width.0 {
-                current_line = test_line;
-            } else {
-                // Current line doesn't fit, save it and start a new line
-                if !current_line.is_empty() {
-                    lines.push(current_line);
-                }
-                
-                // Handle words that are longer than the line width
-                if self.calculate_text_width(word, font_size).0 > max_width.0 {
-                    let chars: Vec<char> = word.chars().collect();
-                    let mut remaining = String::new();
-                    
-                    for char in chars {
-                        let test_word = format!("{}{}", remaining, char);
-                        if self.calculate_text_width(&test_word, font_size).0 <= max_width.0 {
-                            remaining = test_word;
-                        } else {
-                            if !remaining.is_empty() {
-                                lines.push(remaining);
-                            }
-                            remaining = char.to_string();
-                        }
-                    }
-                    current_line = remaining;
+        for word in text.split_whitespace() {
+            for piece in self.split_to_width(word, max_width, font_size, font) {
+                let test_line = if current_line.is_empty() {
+                    piece.clone()
                 } else {
-                    current_line = word.to_string();
+                    format!("{} {}", current_line, piece)
+                };
+
+                if current_line.is_empty() || font.width(&test_line, font_size) <= max_width {
+                    current_line = test_line;
+                } else {
+                    lines.push(std::mem::replace(&mut current_line, piece));
                 }
             }
         }
-        
-        if !current_line.is_empty() {
+
+        if !current_line.is_empty() || lines.is_empty() {
             lines.push(current_line);
         }
-        
-        if lines.is_empty() {
-            lines.push(String::new());
-        }
-        
+
         lines
     }
 
+    /// Heading text in bold, top edge at `y`; H1 and H2 are underlined
     pub fn render_heading(
         &self,
         layer: &PdfLayerReference,
@@ -220,230 +209,188 @@ impl TextRenderer {
         y: Mm,
         max_width: Mm,
     ) -> (Mm, usize) {
-        let font_size = match level {
-            1 => self.font_sizes.h1,
-            2 => self.font_sizes.h2,
-            3 => self.font_sizes.h3,
-            4 => self.font_sizes.h4,
-            5 => self.font_sizes.h5,
-            _ => self.font_sizes.h6,
-        };
+        let font_size = self.heading_font_size(level);
 
         // Set heading color
         layer.set_fill_color(self.colors.heading.clone());
-        
-        let lines = self.wrap_text(text, max_width, font_size);
+
+        let lines = self.wrap_text(text, max_width, font_size, &self.fonts.bold);
         let line_height = self.calculate_line_height(font_size);
-        let mut current_y = y;
-        
+        let mut baseline = y - self.baseline_offset(font_size);
+
         for line in &lines {
-            layer.use_text(line, font_size, x, current_y, &self.fonts.bold);
-            current_y -= line_height;
+            self.draw_text(layer, line, font_size, x, baseline, &self.fonts.bold);
+            baseline -= line_height;
         }
 
         // Add underline for H1 and H2
-        if level <= 2 && !lines.is_empty() {
-            let underline_y = current_y + Mm(2.0);
-            let underline_width = self.calculate_text_width(&lines[0], font_size);
-            self.draw_line(layer, x, underline_y, x + underline_width, underline_y, 0.5);
+        if level <= 2 {
+            let underline_y = y - line_height * lines.len() as f32;
+            layer.set_outline_color(self.colors.heading.clone());
+            self.draw_line(layer, x, underline_y, x + max_width, underline_y, 0.5);
         }
-        
+
         // Reset text color
         layer.set_fill_color(self.colors.text.clone());
-        
+
         let total_height = line_height * lines.len() as f32;
         (total_height, lines.len())
     }
 
-    pub fn render_paragraph(
-        &self,
-        layer: &PdfLayerReference,
-        text: &str,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        justify: bool,
-    ) -> (Mm, usize) {
-        self.render_formatted_text(layer, text, x, y, max_width, justify, &[], self.font_sizes.body)
+    pub fn heading_font_size(&self, level: u8) -> f32 {
+        match level {
+            1 => self.font_sizes.h1,
+            2 => self.font_sizes.h2,
+            3 => self.font_sizes.h3,
+            4 => self.font_sizes.h4,
+            5 => self.font_sizes.h5,
+            _ => self.font_sizes.h6,
+        }
     }
 
-    pub fn render_formatted_text(
+    /// Body text style, justified or ragged
+    pub fn body_style(&self, justify: bool) -> LineStyle {
+        LineStyle {
+            font_size: self.font_sizes.body,
+            color: self.colors.text.clone(),
+            justify,
+        }
+    }
+
+    /// Break styled spans into lines of words that fit `max_width`; every
+    /// word also gets `base_formats`
+    pub fn wrap_formatted(&self, spans: &[TextSpan], base_formats: &[TextFormat], max_width: Mm, font_size: f32) -> Vec<TextLine> {
+        let mut words: Vec<Word> = Vec::new();
+        let mut pending_space = false;
+
+        for span in spans {
+            let formats = [base_formats, &span.formats].concat();
+            let mut rest = span.text.as_str();
+            loop {
+                let trimmed = rest.trim_start();
+                pending_space |= trimmed.len() != rest.len();
+                if trimmed.is_empty() {
+                    break;
+                }
+                let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+                words.push(Word {
+                    text: trimmed[..end].to_string(),
+                    formats: formats.clone(),
+                    space_before: pending_space,
+                });
+                pending_space = false;
+                rest = &trimmed[end..];
+            }
+        }
+
+        let space_width = self.fonts.regular.width(" ", font_size);
+        let mut lines = Vec::new();
+        let mut line = TextLine::default();
+        let mut line_width = Mm(0.0);
+
+        for word in words {
+            // Words longer than a line are broken into line-sized pieces
+            let font = self.get_font_for_formats(&word.formats);
+            for (index, piece) in self.split_to_width(&word.text, max_width, font_size, font).into_iter().enumerate() {
+                let piece_width = font.width(&piece, font_size);
+                let space_before = word.space_before || index > 0;
+                let gap = if space_before && !line.words.is_empty() { space_width } else { Mm(0.0) };
+
+                if !line.words.is_empty() && space_before && line_width + gap + piece_width > max_width {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = Mm(0.0);
+                } else {
+                    line_width += gap;
+                }
+
+                line_width += piece_width;
+                line.words.push(Word { text: piece, formats: word.formats.clone(), space_before });
+            }
+        }
+
+        line.ends_paragraph = true;
+        lines.push(line);
+        lines
+    }
+
+    fn split_to_width(&self, word: &str, max_width: Mm, font_size: f32, font: &FontChain) -> Vec<String> {
+        if font.width(word, font_size) <= max_width {
+            return vec![word.to_string()];
+        }
+
+        let mut pieces = Vec::new();
+        let mut piece = String::new();
+        for c in word.chars() {
+            piece.push(c);
+            if font.width(&piece, font_size) > max_width && piece.chars().count() > 1 {
+                piece.pop();
+                pieces.push(std::mem::replace(&mut piece, c.to_string()));
+            }
+        }
+        pieces.push(piece);
+        pieces
+    }
+
+    /// Draw wrapped lines with their top edge at `y`, returning the height used.
+    /// Justified text leaves each paragraph's last line ragged.
+    pub fn render_lines(
         &self,
         layer: &PdfLayerReference,
-        text: &str,
+        lines: &[TextLine],
         x: Mm,
         y: Mm,
         max_width: Mm,
-        justify: bool,
-        formats: &[TextFormat],
-        font_size: f32,
-    ) -> (Mm, usize) {
+        style: &LineStyle,
+    ) -> Mm {
+        let font_size = style.font_size;
         let line_height = self.calculate_line_height(font_size);
-        let lines = self.wrap_text(text, max_width, font_size);
-        let mut current_y = y;
-        
-        // Select appropriate font based on formatting
-        let font = self.get_font_for_formats(formats);
-        
-        // Set text color based on format
-        if formats.contains(&TextFormat::Code) {
-            layer.set_fill_color(self.colors.code.clone());
-        } else {
-            layer.set_fill_color(self.colors.text.clone());
-        }
-        
-        for (i, line) in lines.iter().enumerate() {
-            let is_last_line = i == lines.len() - 1;
-            
-            if justify && !is_last_line && lines.len() > 1 {
-                self.render_justified_text_with_font(layer, line, x, current_y, max_width, font_size, font);
+        let space_width = self.fonts.regular.width(" ", font_size);
+        let mut baseline = y - self.baseline_offset(font_size);
+
+        for line in lines {
+            let gaps = line.words.iter().skip(1).filter(|word| word.space_before).count();
+            let natural_width = line.words.iter().enumerate()
+                .map(|(index, word)| {
+                    let gap = if index > 0 && word.space_before { space_width } else { Mm(0.0) };
+                    gap + self.get_font_for_formats(&word.formats).width(&word.text, font_size)
+                })
+                .fold(Mm(0.0), |total, width| total + width);
+            let extra = if style.justify && !line.ends_paragraph && gaps > 0 && natural_width < max_width {
+                (max_width - natural_width) / gaps as f32
             } else {
-                layer.use_text(line, font_size, x, current_y, font);
-            }
-            
-            // Add strikethrough if needed
-            if formats.contains(&TextFormat::Strikethrough) {
-                let text_width = self.calculate_text_width(line, font_size);
-                let strike_y = current_y + Mm(font_size * 0.3 * 25.4 / 72.0);
-                self.draw_line(layer, x, strike_y, x + text_width, strike_y, 0.5);
-            }
-            
-            current_y -= line_height;
-        }
-        
-        // Reset text color
-        layer.set_fill_color(self.colors.text.clone());
-        
-        let total_height = line_height * lines.len() as f32;
-        (total_height, lines.len())
-    }
+                Mm(0.0)
+            };
 
+            let mut current_x = x;
+            for (index, word) in line.words.iter().enumerate() {
+                if index > 0 && word.space_before {
+                    current_x += space_width + extra;
+                }
 
-    fn parse_formatting(&self, text: &str, base_formats: &[TextFormat]) -> Vec<TextSegment> {
-        let mut segments = Vec::new();
-        let mut current_text = String::new();
-        let mut format_stack = base_formats.to_vec();
-        let mut i = 0;
-        let chars: Vec<char> = text.chars().collect();
-        
-        while i < chars.len() {
-            if chars[i] == '[' {
-                // Check for formatting markers
-                let remaining: String = chars[i..].iter().collect();
-                
-                if remaining.starts_with("[BOLD_START]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.push(TextFormat::Bold);
-                    i += "[BOLD_START]".len();
-                    continue;
-                } else if remaining.starts_with("[BOLD_END]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.retain(|f| !matches!(f, TextFormat::Bold));
-                    i += "[BOLD_END]".len();
-                    continue;
-                } else if remaining.starts_with("[ITALIC_START]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.push(TextFormat::Italic);
-                    i += "[ITALIC_START]".len();
-                    continue;
-                } else if remaining.starts_with("[ITALIC_END]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.retain(|f| !matches!(f, TextFormat::Italic));
-                    i += "[ITALIC_END]".len();
-                    continue;
-                } else if remaining.starts_with("[STRIKE_START]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.push(TextFormat::Strikethrough);
-                    i += "[STRIKE_START]".len();
-                    continue;
-                } else if remaining.starts_with("[STRIKE_END]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.retain(|f| !matches!(f, TextFormat::Strikethrough));
-                    i += "[STRIKE_END]".len();
-                    continue;
-                } else if remaining.starts_with("[CODE_START]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.push(TextFormat::Code);
-                    i += "[CODE_START]".len();
-                    continue;
-                } else if remaining.starts_with("[CODE_END]") {
-                    if !current_text.is_empty() {
-                        segments.push(TextSegment {
-                            text: current_text.clone(),
-                            formats: format_stack.clone(),
-                        });
-                        current_text.clear();
-                    }
-                    format_stack.retain(|f| !matches!(f, TextFormat::Code));
-                    i += "[CODE_END]".len();
-                    continue;
+                if word.formats.contains(&TextFormat::Code) {
+                    layer.set_fill_color(self.colors.code.clone());
+                } else {
+                    layer.set_fill_color(style.color.clone());
+                }
+                let word_width = self.draw_text(layer, &word.text, font_size, current_x, baseline, self.get_font_for_formats(&word.formats));
+                if word.formats.contains(&TextFormat::Strikethrough) {
+                    let strike_y = baseline + Mm::from(Pt(font_size * 0.3));
+                    layer.set_outline_color(style.color.clone());
+                    self.draw_line(layer, current_x, strike_y, current_x + word_width, strike_y, 0.5);
                 }
+                current_x += word_width;
             }
-            
-            current_text.push(chars[i]);
-            i += 1;
-        }
-        
-        if !current_text.is_empty() {
-            segments.push(TextSegment {
-                text: current_text,
-                formats: format_stack,
-            });
-        }
-        
-        if segments.is_empty() {
-            segments.push(TextSegment {
-                text: String::new(),
-                formats: base_formats.to_vec(),
-            });
+
+            baseline -= line_height;
         }
-        
-        segments
+
+        // Reset text color
+        layer.set_fill_color(self.colors.text.clone());
+
+        line_height * lines.len() as f32
     }
 
-    fn get_font_for_formats(&self, formats: &[TextFormat]) -> &IndirectFontRef {
+    fn get_font_for_formats(&self, formats: &[TextFormat]) -> &FontChain {
         let has_bold = formats.contains(&TextFormat::Bold);
         let has_italic = formats.contains(&TextFormat::Italic);
         let has_code = formats.contains(&TextFormat::Code);
@@ -461,6 +408,8 @@ impl TextRenderer {
         }
     }
 
+    /// Code lines in a shaded box whose top edge is `y`. `first_line` numbers
+    /// the lines, so a block split across pages keeps counting.
     pub fn render_code_block(
         &self,
         layer: &PdfLayerReference,
@@ -468,296 +417,114 @@ impl TextRenderer {
         x: Mm,
         y: Mm,
         max_width: Mm,
-        background: bool,
+        first_line: Option<usize>,
     ) -> (Mm, usize) {
         let font_size = self.font_sizes.code;
         let line_height = self.calculate_line_height(font_size);
         let lines: Vec<&str> = text.lines().collect();
-        let padding = Mm(6.0); // More generous padding
-        let line_number_width = Mm(15.0);
-        let mut current_y = y;
-        
-        // Draw professional code block background
-        if background {
-            let bg_height = line_height * lines.len() as f32 + (padding * 2.0);
-            
-            // Main background with subtle gradient effect
-            self.draw_rectangle(
-                layer,
-                x - padding,
-                current_y + padding,
-                max_width + (padding * 2.0),
-                bg_height,
-                Some(Color::Rgb(Rgb::new(0.96, 0.97, 0.98, None))), // Very light blue-gray
-                Some(Color::Rgb(Rgb::new(0.8, 0.82, 0.85, None))), // Professional border
-                1.0,
-            );
-            
-            // Left accent border for code blocks
-            self.draw_rectangle(
-                layer,
-                x - padding,
-                current_y + padding,
-                Mm(3.0),
-                bg_height,
-                Some(Color::Rgb(Rgb::new(0.3, 0.4, 0.6, None))), // Blue accent
-                None,
-                0.0,
-            );
-            
-            // Line numbers background
-            if lines.len() > 5 { // Only show line numbers for longer code blocks
-                self.draw_rectangle(
-                    layer,
-                    x - padding + Mm(3.0),
-                    current_y + padding,
-                    line_number_width,
-                    bg_height,
-                    Some(Color::Rgb(Rgb::new(0.92, 0.93, 0.95, None))), // Darker background for line numbers
-                    None,
-                    0.0,
-                );
-            }
-        }
-        
+        let height = self.code_block_height(lines.len());
+
+        // Main background with a border, then the left accent
+        self.draw_rectangle(layer, x, y, max_width, height, Some(Color::Rgb(Rgb::new(0.96, 0.97, 0.98, None)))); // Very light blue-gray
+        layer.set_outline_color(Color::Rgb(Rgb::new(0.8, 0.82, 0.85, None))); // Professional border
+        self.stroke_rectangle(layer, x, y, max_width, height, 1.0);
+        self.draw_rectangle(layer, x, y, Mm(1.5), height, Some(Color::Rgb(Rgb::new(0.3, 0.4, 0.6, None)))); // Blue accent
+
+        let text_x = x + CODE_PADDING + if first_line.is_some() { Mm(10.0) } else { Mm(0.0) };
+        let mut baseline = y - CODE_PADDING - self.baseline_offset(font_size);
+
         // Render code lines with syntax highlighting simulation
-        for (line_num, line) in lines.iter().enumerate() {
-            let x_offset = if background { 
-                x + padding + if lines.len() > 5 { line_number_width + Mm(3.0) } else { Mm(0.0) }
-            } else { 
-                x 
-            };
-            
-            // Render line numbers for longer code blocks
-            if background && lines.len() > 5 {
+        for (index, line) in lines.iter().enumerate() {
+            if let Some(first_line) = first_line {
                 layer.set_fill_color(Color::Rgb(Rgb::new(0.6, 0.6, 0.6, None))); // Gray line numbers
-                layer.use_text(
-                    &format!("{:3}", line_num + 1),
-                    font_size * 0.85,
-                    x + Mm(1.0),
-                    current_y,
-                    &self.fonts.regular,
-                );
+                self.draw_text(layer, &format!("{:3}", first_line + index), font_size * 0.85, x + Mm(2.0), baseline, &self.fonts.code);
             }
-            
-            // Simple syntax highlighting - keywords in bold, strings in different color
-            if line.contains("fn ") || line.contains("function ") || line.contains("def ") || 
+
+            // Simple syntax highlighting - keywords in blue, comments in green
+            if line.contains("fn ") || line.contains("function ") || line.contains("def ") ||
                line.contains("class ") || line.contains("struct ") || line.contains("impl ") {
                 layer.set_fill_color(Color::Rgb(Rgb::new(0.2, 0.3, 0.8, None))); // Blue for keywords
-                layer.use_text(*line, font_size, x_offset, current_y, &self.fonts.bold);
-            } else if line.trim_start().starts_with("//") || line.trim_start().starts_with("#") {
+            } else if line.trim_start().starts_with("//") || line.trim_start().starts_with('#') {
                 layer.set_fill_color(Color::Rgb(Rgb::new(0.5, 0.6, 0.5, None))); // Green for comments
-                layer.use_text(*line, font_size, x_offset, current_y, &self.fonts.italic);
             } else {
                 layer.set_fill_color(self.colors.code.clone());
-                layer.use_text(*line, font_size, x_offset, current_y, &self.fonts.code);
             }
-            
-            current_y -= line_height;
+            self.draw_text(layer, line, font_size, text_x, baseline, &self.fonts.code);
+
+            baseline -= line_height;
         }
-        
+
         // Reset text color
         layer.set_fill_color(self.colors.text.clone());
-        
-        let total_height = line_height * lines.len() as f32 + if background { padding * 2.0 } else { Mm(0.0) };
-        (total_height, lines.len())
+
+        (height, lines.len())
     }
 
+    pub fn code_block_height(&self, line_count: usize) -> Mm {
+        self.calculate_line_height(self.font_sizes.code) * line_count as f32 + CODE_PADDING * 2.0
+    }
+
+    /// List item text beside its bullet, number or checkbox, top edge at `y`
     pub fn render_list_item(
         &self,
         layer: &PdfLayerReference,
-        text: &str,
+        spans: &[TextSpan],
         bullet: &str,
         x: Mm,
         y: Mm,
         max_width: Mm,
-        indent_level: usize,
     ) -> (Mm, usize) {
-        let font_size = self.font_sizes.body;
-        let line_height = self.calculate_line_height(font_size);
-        let indent = Mm(16.0 * indent_level as f32); // Increased indent for better hierarchy
-        let bullet_width = Mm(12.0); // Wider space for bullets
-        
-        // Enhanced bullet rendering with better typography
-        let bullet_x = x + indent;
-        let bullet_y_offset = line_height * 0.15; // Slight vertical adjustment for better alignment
-        
-        // Use different bullet styles based on type and nesting
-        let enhanced_bullet = if bullet.starts_with(char::is_numeric) {
-            // For numbered lists, keep as is but with better formatting
-            bullet.to_string()
-        } else {
-            // Enhanced bullet characters for better visual hierarchy
-            match indent_level {
-                0 => "●".to_string(),      // Solid circle for first level
-                1 => "◦".to_string(),      // Open circle for second level  
-                2 => "▪".to_string(),      // Small square for third level
-                _ => "▫".to_string(),      // Open square for deeper levels
-            }
-        };
-        
-        // Render bullet with proper color and positioning
+        let style = self.body_style(false);
+
+        // Render bullet with proper color, on the first line's baseline
         layer.set_fill_color(Color::Rgb(Rgb::new(0.3, 0.4, 0.6, None))); // Professional blue for bullets
-        layer.use_text(
-            &enhanced_bullet, 
-            font_size,
-            bullet_x, 
-            y + bullet_y_offset, 
-            &self.fonts.bold
-        );
-        
-        // Reset text color for list content
-        layer.set_fill_color(self.colors.text.clone());
-        
-        // Render text with appropriate wrapping and formatting
-        let text_x = x + indent + bullet_width;
-        let text_max_width = max_width - indent - bullet_width - Mm(4.0); // Extra margin
-        
-        let (height, lines) = self.render_formatted_text(
-            layer,
-            text,
-            text_x,
-            y,
-            text_max_width,
-            false,
-            &[],
-            font_size,
-        );
-        
-        (height, lines)
+        self.draw_text(layer, bullet, style.font_size, x, y - self.baseline_offset(style.font_size), &self.fonts.bold);
+
+        let lines = self.list_item_lines(spans, max_width);
+        let height = self.render_lines(layer, &lines, x + LIST_BULLET_WIDTH, y, max_width - LIST_BULLET_WIDTH, &style);
+        (height, lines.len())
     }
 
+    pub fn list_item_lines(&self, spans: &[TextSpan], max_width: Mm) -> Vec<TextLine> {
+        self.wrap_formatted(spans, &[], max_width - LIST_BULLET_WIDTH, self.font_sizes.body)
+    }
+
+    /// Italic quote text with a left border, inside a box whose top edge is `y`
     pub fn render_blockquote(
         &self,
         layer: &PdfLayerReference,
-        text: &str,
+        spans: &[TextSpan],
         x: Mm,
         y: Mm,
         max_width: Mm,
     ) -> (Mm, usize) {
-        let font_size = self.font_sizes.body * 0.95; // Slightly smaller for quotes
-        let border_width = Mm(4.0);
-        let padding_left = Mm(12.0);
-        let padding_vertical = Mm(6.0);
-        let background_padding = Mm(8.0);
-        
-        // Calculate text area
-        let text_x = x + border_width + padding_left;
-        let text_width = max_width - border_width - padding_left - background_padding;
-        
-        // Render text with blockquote formatting (italic style for quotes)
-        layer.set_fill_color(self.colors.blockquote.clone());
-        let (text_height, line_count) = self.render_formatted_text(
-            layer,
-            text,
-            text_x,
-            y - padding_vertical,
-            text_width,
-            false,
-            &[TextFormat::Italic], // Make blockquotes italic
-            font_size,
-        );
-        
-        let total_height = text_height + padding_vertical * 2.0;
-        
-        // Draw subtle background rectangle
-        self.draw_rectangle(
-            layer,
-            x,
-            y,
-            max_width,
-            total_height,
-            Some(Color::Rgb(Rgb::new(0.98, 0.98, 0.99, None))), // Very light background
-            None,
-            0.0,
-        );
-        
-        // Draw thick left border with gradient effect (multiple lines)
-        layer.set_outline_color(self.colors.blockquote_border.clone());
-        self.draw_line(
-            layer,
-            x + border_width / 2.0,
-            y + Mm(1.0),
-            x + border_width / 2.0,
-            y - total_height + Mm(1.0),
-            border_width.0,
-        );
-        
-        // Add a thinner highlight line next to the main border
-        layer.set_outline_color(Color::Rgb(Rgb::new(0.6, 0.75, 0.9, None))); // Light blue accent
-        self.draw_line(
-            layer,
-            x + border_width + Mm(1.0),
-            y + Mm(1.0),
-            x + border_width + Mm(1.0),
-            y - total_height + Mm(1.0),
-            1.0,
-        );
-        
-        // Add quotation mark decoration at the top
-        layer.set_fill_color(Color::Rgb(Rgb::new(0.7, 0.7, 0.75, None)));
-        layer.use_text(
-            "\"", // Opening quote
-            font_size * 1.8,
-            x + border_width + Mm(2.0),
-            y - Mm(2.0),
-            &self.fonts.italic,
-        );
-        
-        // Reset colors
-        layer.set_fill_color(self.colors.text.clone());
-        
-        (total_height + Mm(4.0), line_count)
-    }
+        let lines = self.blockquote_lines(spans, max_width);
+        let height = self.blockquote_height(lines.len());
 
-    fn render_justified_text(
-        &self,
-        layer: &PdfLayerReference,
-        text: &str,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        font_size: f32,
-    ) {
-        self.render_justified_text_with_font(layer, text, x, y, max_width, font_size, &self.fonts.regular);
+        // Subtle background and left border first, so the text is drawn over them
+        self.draw_rectangle(layer, x, y, max_width, height, Some(Color::Rgb(Rgb::new(0.97, 0.97, 0.99, None))));
+        self.draw_rectangle(layer, x, y, Mm(1.5), height, Some(self.colors.blockquote_border.clone()));
+
+        let style = LineStyle {
+            font_size: self.blockquote_font_size(),
+            color: self.colors.blockquote.clone(),
+            justify: false,
+        };
+        self.render_lines(layer, &lines, x + QUOTE_PADDING * 2.0, y - QUOTE_PADDING, max_width - QUOTE_PADDING * 4.0, &style);
+
+        (height, lines.len())
     }
 
-    fn render_justified_text_with_font(
-        &self,
-        layer: &PdfLayerReference,
-        text: &str,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        font_size: f32,
-        font: &IndirectFontRef,
-    ) {
-        let words: Vec<&str> = text.split_whitespace().collect();
-        if words.len() <= 1 {
-            layer.use_text(text, font_size, x, y, font);
-            return;
-        }
+    pub fn blockquote_lines(&self, spans: &[TextSpan], max_width: Mm) -> Vec<TextLine> {
+        self.wrap_formatted(spans, &[TextFormat::Italic], max_width - QUOTE_PADDING * 4.0, self.blockquote_font_size())
+    }
 
-        // Calculate total text width without spaces
-        let text_without_spaces = words.join("");
-        let text_width = self.calculate_text_width(&text_without_spaces, font_size);
-        let available_space = max_width - text_width;
-        let space_count = words.len() - 1;
-        
-        if space_count == 0 || available_space.0 <= 0.0 {
-            layer.use_text(text, font_size, x, y, font);
-            return;
-        }
-        
-        let space_width = available_space.0 / space_count as f32;
+    pub fn blockquote_height(&self, line_count: usize) -> Mm {
+        self.calculate_line_height(self.blockquote_font_size()) * line_count as f32 + QUOTE_PADDING * 2.0
+    }
 
-        // Render words with calculated spacing
-        let mut current_x = x;
-        for word in words.iter() {
-            layer.use_text(*word, font_size, current_x, y, font);
-            let word_width = self.calculate_text_width(word, font_size);
-            current_x += word_width + Mm(space_width);
-        }
+    fn blockquote_font_size(&self) -> f32 {
+        self.font_sizes.body * 0.95 // Slightly smaller for quotes
     }
 
     pub fn draw_line(&self, layer: &PdfLayerReference, x1: Mm, y1: Mm, x2: Mm, y2: Mm, thickness: f32) {
@@ -772,32 +539,24 @@ impl TextRenderer {
         layer.add_line(line);
     }
 
-    pub fn draw_rectangle(&self, layer: &PdfLayerReference, x: Mm, y: Mm, width: Mm, height: Mm, fill_color: Option<Color>, border_color: Option<Color>, border_thickness: f32) {
+    pub fn draw_rectangle(&self, layer: &PdfLayerReference, x: Mm, y: Mm, width: Mm, height: Mm, fill_color: Option<Color>) {
         if let Some(fill) = fill_color {
             layer.set_fill_color(fill);
         }
-        if let Some(border) = border_color {
-            layer.set_outline_color(border);
-            layer.set_outline_thickness(border_thickness);
-        }
-        
-        // Draw rectangle using lines for better control
-        let top_left = Point::new(x, y);
-        let top_right = Point::new(x + width, y);
-        let bottom_right = Point::new(x + width, y - height);
-        let bottom_left = Point::new(x, y - height);
-        
-        let rect = Line {
-            points: vec![
-                (top_left, false),
-                (top_right, false),
-                (bottom_right, false),
-                (bottom_left, false),
-            ],
+        layer.add_polygon(Polygon {
+            rings: vec![rectangle_points(x, y, width, height)],
+            mode: PolygonMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        });
+    }
+
+    /// Outline a rectangle in the current outline color
+    pub fn stroke_rectangle(&self, layer: &PdfLayerReference, x: Mm, y: Mm, width: Mm, height: Mm, thickness: f32) {
+        layer.set_outline_thickness(thickness);
+        layer.add_line(Line {
+            points: rectangle_points(x, y, width, height),
             is_closed: true,
-        };
-        
-        layer.add_line(rect);
+        });
     }
 
     pub fn get_font_size(&self, element_type: &str) -> f32 {
@@ -822,25 +581,14 @@ impl TextRenderer {
     pub fn get_fonts(&self) -> &FontSystem {
         &self.fonts
     }
+}
 
-    pub fn get_font_sizes(&self) -> &FontSizes {
-        &self.font_sizes
-    }
-
-    // Helper method for rendering with proper spacing
-    pub fn render_with_spacing(
-        &self,
-        layer: &PdfLayerReference,
-        text: &str,
-        x: Mm,
-        y: Mm,
-        max_width: Mm,
-        element_type: &str,
-    ) -> (Mm, usize) {
-        match element_type {
-            "blockquote" => self.render_blockquote(layer, text, x, y, max_width),
-            "code_block" => self.render_code_block(layer, text, x, y, max_width, true),
-            _ => self.render_paragraph(layer, text, x, y, max_width, true),
-        }
-    }
-}
\ No newline at end of file
+/// Corners of a rectangle hanging down from its top-left corner at (`x`, `y`)
+fn rectangle_points(x: Mm, y: Mm, width: Mm, height: Mm) -> Vec<(Point, bool)> {
+    vec![
+        (Point::new(x, y), false),
+        (Point::new(x + width, y), false),
+        (Point::new(x + width, y - height), false),
+        (Point::new(x, y - height), false),
+    ]
+}